@@ -0,0 +1,77 @@
+use crate::game::Game;
+use crate::fen::game_to_fen;
+use crate::pgn::*;
+use crate::uci::parse_uci_move;
+use std::io::Read;
+
+// plays every move of the input without prompting and prints the outcome as "key: value"
+// lines followed by the PGN, returns the process exit code
+pub fn run_batch(mut game: Game, file: &Option<String>) -> i32 {
+    let input = match file {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("couldn't open {}: {}", path, err);
+                return 2;
+            }
+        },
+        None => {
+            let mut contents = String::new();
+            if let Err(err) = std::io::stdin().read_to_string(&mut contents) {
+                eprintln!("couldn't read stdin: {}", err);
+                return 2;
+            }
+            contents
+        }
+    };
+
+    let mut error: Option<String> = None;
+    let mut ply = 0;
+    for token in input.split_whitespace() {
+        // move numbers like "12." or "12...e5" and a trailing result are allowed
+        if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token) {
+            continue;
+        }
+        let token = match token.rfind('.') {
+            Some(index) => &token[index + 1..],
+            None => token,
+        };
+        if token.is_empty() {
+            continue;
+        }
+
+        ply += 1;
+        if let Some(result) = game.result() {
+            error = Some(format!("move {} at ply {} comes after the game ended ({})", token, ply, result));
+            break;
+        }
+        match parse_uci_move(&game, token).or_else(|_| parse_san(&game, token)) {
+            Ok(legal_move) => game.play_move(legal_move),
+            Err(_) => {
+                error = Some(format!("illegal move {} at ply {}", token, ply));
+                break;
+            }
+        }
+    }
+
+    println!("status: {}", if error.is_some() {"illegal"} else {"ok"});
+    if let Some(error) = &error {
+        println!("error: {}", error);
+    }
+    println!("plies: {}", game.move_history.len());
+    println!("fen: {}", game_to_fen(&game));
+    match game.result() {
+        Some(result) => {
+            println!("result: {}", result.pgn_result());
+            println!("termination: {}", result.termination());
+        }
+        None => {
+            println!("result: *");
+            println!("termination: none");
+        }
+    }
+    println!();
+    println!("{}", game_to_pgn(&game));
+
+    return if error.is_some() {1} else {0};
+}
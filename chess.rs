@@ -1,317 +1,695 @@
-
-mod position;
-mod piece;
-mod board;
-mod legal_moves;
-
-use crate::piece::Piece;
-use crate::piece::PieceType;
-use crate::piece::Color;
-use crate::piece::Color::*;
-use crate::board::Board;
-use crate::board::setup_board;
-use crate::board::print_board;
-use crate::position::*;
-use crate::legal_moves::*;
-use std::collections::HashMap;
+// the code base keeps explicit returns, index loops over the board and the original
+// camelCase history fields, so those lints are silenced crate wide
+#![allow(non_snake_case)]
+#![allow(clippy::needless_return, clippy::needless_range_loop, clippy::clone_on_copy)]
+#![allow(clippy::collapsible_if, clippy::ptr_arg, clippy::useless_format)]
+
+use chess::piece::Color;
+use chess::position::*;
+use chess::game::Game;
+use chess::fen::*;
+use chess::pgn::*;
+use chess::render::*;
+use chess::clock::Clock;
+use chess::cli::*;
+use chess::game::evaluate_timeout;
+use chess::engine::SearchLimits;
+use chess::players::*;
+use chess::random::Random;
+use chess::engine;
+use chess::tui;
+use chess::uci;
+use chess::json_api;
+use chess::batch;
+use chess::perft;
+use chess::solver;
+use chess::savegame;
+use chess::savegame::GameRecord;
+use chess::network;
+use chess::fics;
+use chess::engine_match;
+use chess::book::*;
+use chess::editor::*;
+use chess::validate::position_problems;
+use chess::syzygy::Tablebase;
+use chess::endgame::EndgameTables;
+use chess::runner::*;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+const HINT_DEPTH: u32 = 3;
+
+const HELP_TEXT: &str = "\
+moves are entered as coordinates (e2e4, e7e8q) or in SAN (Nf3, exd5, O-O)
+commands:
+  help            show this text
+  new             start a new game
+  fen             print the position as FEN
+  setfen <fen>    start from the given FEN
+  pgn             print the game so far as PGN
+  save <file>     write the game to a PGN file
+  load <file>     read a game from a PGN file
+  savegame <file> save everything about the game, clocks and players included
+  loadgame <file> carry on with a game written by savegame or the autosave
+  moves <square>  list the legal moves of the piece on a square
+  flip            turn the board around
+  display <mode>  ascii, unicode, color, plain, auto (follow the side to move), white or black
+  undo / redo     take back a move or play it again
+  hint            let the engine suggest a move
+  resign          give up the game
+  quit            leave";
+
+const EDITOR_HELP_TEXT: &str = "\
+commands:
+  help              show this text
+  put <piece> <squares>
+                    place a piece, eg put wN f3 or put bp a7 b7 c7
+  clear <squares>   take the pieces off the squares
+  empty             clear the whole board
+  start             the position games start from
+  side white|black  who is to move
+  castle <rights>   castling rights as in a FEN, eg KQ, kq or -
+  ep <square>|-     the square a pawn just skipped, for en passant
+  fen [<fen>]       print the position as FEN, or set it up from one
+  check             list what makes the position illegal
+  play              start a game from the position
+  analyze           print the engine's evaluation of the position
+  quit              leave";
 
 fn main() {
-    let empty_piece = Piece {piece: PieceType::Null, color: Color::Null};
-
-    let mut move_history: Vec<MoveHistoryEntry> = Vec::new();
-
-    let mut three_fold_repetition_map = HashMap::new(); // (position, occurances_count)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let game = match starting_game(&options) {
+        Ok(game) => game,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let mut board: Board = [[empty_piece; 8]; 8];
-    let mut turn: Color = White;
-    setup_board(&mut board);
-    
-    loop {
-        println!("Current turn is {:?}", turn);
-        let mut current_position_str = String::new();
-
-        // the game position is serialized by printing board state and all legal moves
-        // TODO: optimize this board state
-        for rank in 0..8 {
-            for file in 0..8 {
-                current_position_str.push_str(&format!("{:?}", board[rank][file]));
+    match options.command.clone() {
+        Command::Help => println!("{}", USAGE),
+        Command::Uci => uci::run_uci(),
+        Command::Json => json_api::run_json_api(game),
+        Command::Perft(depth) => run_perft(&game, depth),
+        Command::Solve(stipulation) => run_solve(&game, stipulation),
+        Command::Batch(file) => std::process::exit(batch::run_batch(game, &file)),
+        Command::Analyze => run_analyze(&game, options.depth, load_tablebase(&options), load_tables(&options)),
+        Command::Book(path) => run_book(&game, &path),
+        Command::MakeBook(pgn, path) => run_make_book(&pgn, &path, options.max_ply, options.min_games),
+        Command::MakeTables(path) => run_make_tables(&path),
+        Command::Play => play(game, &options),
+        Command::Edit => run_editor(game, &options),
+        Command::Host => {
+            let color = options.color.unwrap_or(if Random::from_time().below(2) == 0 {Color::White} else {Color::Black});
+            let flipped = (color == Color::Black) != options.flip;
+            let render_options = RenderOptions {
+                ascii: options.ascii,
+                perspective: if flipped {Perspective::Black} else {Perspective::White},
+                ..RenderOptions::default()
+            };
+            if let Err(err) = network::run_host(game, options.port, color, render_options) {
+                eprintln!("{}", err);
+                std::process::exit(1);
             }
         }
+        Command::Fics => {
+            if let Err(err) = fics::run_fics(options.port, options.ratings.clone()) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Match(first, second) => {
+            let settings = engine_match::MatchSettings {tables: load_tables(&options), ..options.match_settings.clone()};
+            if let Err(err) = engine_match::run_match(&first, &second, game, options.time_control, &settings) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Join(address) => {
+            let render_options = RenderOptions {ascii: options.ascii, ..RenderOptions::default()};
+            if let Err(err) = network::run_join(&address, render_options, options.flip) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-        let legal_moves = find_all_legal_moves(&board, turn, &move_history);
-        for legal_move in legal_moves.iter() {
-            //println!("{:?}", legal_move);
-            current_position_str.push_str(&format!("{:?}", legal_move));
+// the book given with --book, the program stops if it can't be read
+fn load_book(options: &Options) -> Option<OpeningBook> {
+    let path = options.book.as_ref()?;
+    match OpeningBook::open(path) {
+        Ok(book) => Some(book),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
+    }
+}
 
-        match three_fold_repetition_map.get(&current_position_str) {
-            Some(count) => { three_fold_repetition_map.insert(current_position_str, count + 1); }
-            None => { three_fold_repetition_map.insert(current_position_str, 1); }
+fn load_tables(options: &Options) -> Option<Arc<EndgameTables>> {
+    let path = options.tables.as_ref()?;
+    match EndgameTables::open(path) {
+        Ok(tables) => Some(Arc::new(tables)),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
+    }
+}
 
-        let game_result = evaluate_win_draw(turn, &board, &legal_moves, &move_history, &three_fold_repetition_map);
-        if let Some(result) = game_result {
-            println!("{}", result);
-            break;
+fn load_tablebase(options: &Options) -> Option<Arc<Tablebase>> {
+    let paths = options.syzygy.as_ref()?;
+    match Tablebase::open(paths) {
+        Ok(tablebase) => Some(Arc::new(tablebase)),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
+    }
+}
 
-        print_board(board);
-        println!();
+fn play(mut game: Game, options: &Options) {
+    if options.white_external.is_some() || options.black_external.is_some() {
+        run_with_runner(game, options);
+        return;
+    }
 
-        let input_move = match read_input_move() {
-            Ok(v) => v,
-            _ => {
-                println!("illegal move format");
-                continue;
+    let book = load_book(options);
+    let mut render_options = RenderOptions {ascii: options.ascii, ..RenderOptions::default()};
+    let mut clock = Clock::new(options.time_control);
+    let mut saved_players: Option<Players> = None;
+    if let Some(path) = &options.resume {
+        match savegame::load_game(path) {
+            Ok((saved_game, players, saved_clock)) => {
+                println!("resuming {}", path.display());
+                game = saved_game;
+                saved_players = Some(players);
+                if options.time_control.is_none() {
+                    clock = saved_clock;
+                }
             }
-        };
-
-        // the reason for this odd search is that the user input move
-        // won't have some details like special move type, input parsing could be improved
-        let mut user_legal_move = None;
-        for legal_move in legal_moves.iter() {
-            if legal_move.from == input_move.from && legal_move.to == input_move.to {
-                user_legal_move = Some(legal_move.clone());
-                break;
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
             }
         }
+    }
 
-        if let Some(user_move) = user_legal_move {
-            move_history.push(MoveHistoryEntry {
-                moveEntry: user_move.clone(),
-                boardState: board.clone(),
-                isCaptureMove: board[user_move.to.rank][user_move.to.file].piece != PieceType::Null,
-            });
-
-            play_move(&mut board, user_move.clone());
+    // players given on the command line win over a save, and scripts piping moves in aren't asked
+    let players = if options.white.is_some() || options.black.is_some() {
+        Players {
+            white: options.white.unwrap_or(PlayerKind::Human),
+            black: options.black.unwrap_or(PlayerKind::Human),
+        }
+    } else if let Some(players) = saved_players {
+        players
+    } else if std::io::stdin().is_terminal() {
+        prompt_players()
+    } else {
+        Players::humans()
+    };
+    if options.flip || (players.is_engine(Color::White) && !players.is_engine(Color::Black)) {
+        render_options.perspective = Perspective::Black;
+    }
 
-            if user_move.special_move == Some(SpecialMoveType::Promote) {
-                prompt_promotion(&mut board, user_move.to.clone());
+    // the full screen board needs a terminal, pipes and redirects get the line based loop
+    if !options.line && tui::is_supported() {
+        let tui_options = RenderOptions {colors: true, ..render_options.clone()};
+        match tui::run(game.clone(), tui_options, clock.clone(), players, book.clone()) {
+            Ok(game) => {
+                if let Some(result) = game.result() {
+                    println!("{}", result);
+                }
+                println!("Game Over");
+                return;
             }
+            Err(err) => println!("can't start the full screen board ({}), using line mode", err),
+        }
+    }
 
-            turn = if turn == White {Black} else {White};
+    run_line_mode(game, players, render_options, clock, book);
+}
+
+// any mix of players through the GameRunner, used as soon as one side is neither somebody at
+// this terminal nor the built-in engine
+fn run_with_runner(game: Game, options: &Options) {
+    let mut render_options = RenderOptions {ascii: options.ascii, ..RenderOptions::default()};
+    let book = load_book(options).map(Rc::new);
+    let endgame = load_tables(options);
+    let at_terminal = |kind: Option<PlayerKind>, external: &Option<ExternalPlayer>| {
+        external.is_none() && !matches!(kind, Some(PlayerKind::Engine(_)))
+    };
+    if options.flip || (!at_terminal(options.white, &options.white_external) && at_terminal(options.black, &options.black_external)) {
+        render_options.perspective = Perspective::Black;
+    }
 
-        } else {
-            println!("illegal move, pick another");
+    let mut seats: Vec<Box<dyn Player>> = vec![];
+    let sides = [
+        (Color::White, options.white, &options.white_external),
+        (Color::Black, options.black, &options.black_external),
+    ];
+    for (color, kind, external) in sides {
+        let player: Result<Box<dyn Player>, String> = match external {
+            Some(ExternalPlayer::Uci(command)) => UciEnginePlayer::spawn(command).map(|player| Box::new(player) as Box<dyn Player>),
+            Some(ExternalPlayer::Network) => NetworkPlayer::accept(options.port, color, &game).map(|player| Box::new(player) as Box<dyn Player>),
+            None => match kind {
+                Some(PlayerKind::Engine(level)) => {
+                    let mut player = EnginePlayer::new(level);
+                    player.book = book.clone();
+                    player.endgame = endgame.clone();
+                    Ok(Box::new(player))
+                }
+                _ => Ok(Box::new(TerminalPlayer::new(render_options.clone()))),
+            },
+        };
+        match player {
+            Ok(player) => seats.push(player),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
+    }
 
+    let black = seats.pop().unwrap();
+    let white = seats.pop().unwrap();
+    println!("{} (White) vs {} (Black)", white.name(), black.name());
+    let mut runner = GameRunner::new(game, Clock::new(options.time_control), white, black);
+    let result = runner.run();
+    println!("{}", game_to_pgn(&runner.game).lines().last().unwrap_or(""));
+    println!("{}", result);
+    println!("Game Over");
+}
 
-        /*println!("----");
-        for hist_move in move_history.iter() {
-            println!("{:?}", hist_move);
+fn run_solve(game: &Game, stipulation: solver::Stipulation) {
+    println!("{}", game_to_fen(game));
+    let started = std::time::Instant::now();
+    let solutions = solver::solve(game, stipulation);
+    match solutions.len() {
+        0 => println!("{}: no solution", stipulation),
+        1 => println!("{}: 1 solution", stipulation),
+        count => println!("{}: {} solutions (cooked)", stipulation, count),
+    }
+    match solutions {
+        solver::Solutions::Keys(keys) => {
+            for key in keys.iter() {
+                println!();
+                println!("in {}", key.moves);
+                print!("{}", solver::format_mate_tree(game, key));
+            }
+        }
+        solver::Solutions::Lines(lines) => {
+            println!();
+            for line in lines.iter() {
+                println!("{}", solver::format_line(game, line));
+            }
         }
-        println!("----");*/
     }
+    println!();
+    println!("solved in {:.2}s", started.elapsed().as_secs_f64());
+}
 
-
-    println!("Game Over");
+fn run_perft(game: &Game, depth: u32) {
+    let started = std::time::Instant::now();
+    let mut divide: Vec<(String, u64)> = perft::perft_divide(game, depth).iter()
+        .map(|(legal_move, nodes)| (move_to_uci(legal_move), *nodes))
+        .collect();
+    divide.sort();
+
+    let mut total = 0;
+    for (uci_move, nodes) in divide.iter() {
+        println!("{}: {}", uci_move, nodes);
+        total += nodes;
+    }
+    if depth == 0 {
+        total = 1;
+    }
+    println!();
+    println!("Nodes searched: {}", total);
+    println!("Time: {:.3}s", started.elapsed().as_secs_f64());
 }
 
-fn read_input_move() -> Result<Move, ()> {
-    let mut user_input = String::new();
-    let _b = match std::io::stdin().read_line(&mut user_input) {
-        Ok(v) => v,
-        _ => {return Err(());}
+fn run_book(game: &Game, path: &Path) {
+    let book = match OpeningBook::open(path) {
+        Ok(book) => book,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
     };
 
-    let file_to_num: HashMap<char, usize> = HashMap::from([
-        ('a', 0), ('A', 0),
-        ('b', 1), ('B', 1),
-        ('c', 2), ('C', 2),
-        ('d', 3), ('D', 3),
-        ('e', 4), ('E', 4),
-        ('f', 5), ('F', 5),
-        ('g', 6), ('G', 6),
-        ('h', 7), ('H', 7),
-    ]);
-    
-    Ok(Move {
-        from: Square {
-            file: *file_to_num.get(&user_input.chars().nth(0).ok_or(())?).ok_or(())?,
-            rank: (user_input.chars().nth(1).ok_or(())?.to_digit(10).ok_or(())? - 1) as usize,
-        },
-        to: Square {
-            file: *file_to_num.get(&user_input.chars().nth(2).ok_or(())?).ok_or(())?,
-            rank: (user_input.chars().nth(3).ok_or(())?.to_digit(10).ok_or(())? - 1) as usize, 
-        },
-        // piece and special_move fields are in legal_moves array, dont need to find them here
-        piece: Piece {piece: PieceType::Null, color: Color::Null}, 
-        special_move: None,
-    })
+    println!("{}", game_to_fen(game));
+    println!("key {:016x}", polyglot_key(game));
+    let moves = book.moves(game);
+    if moves.is_empty() {
+        println!("no book moves for this position");
+        return;
+    }
+    let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+    for (book_move, weight) in moves.iter() {
+        let share = if total > 0 {*weight as f64 * 100.0 / total as f64} else {0.0};
+        println!("{:<8} {:<6} weight {:>5} {:>5.1}%", move_to_san(game, book_move), move_to_uci(book_move), weight, share);
+    }
 }
 
-fn play_move(board: &mut Board, input_move: Move) {
-    board[input_move.to.rank][input_move.to.file] = board[input_move.from.rank][input_move.from.file];
-    board[input_move.from.rank][input_move.from.file] = Piece {piece: PieceType::Null, color: Color::Null};
-    
-    if input_move.special_move == Some(SpecialMoveType::EnPassant) {
-        if input_move.piece.color == Color::White {
-            let captured_pawn_square = move_down(input_move.to.clone()).unwrap();
-            board[captured_pawn_square.rank][captured_pawn_square.file] = Piece {piece: PieceType::Null, color: Color::Null};
-        } else if input_move.piece.color == Color::Black {
-            let captured_pawn_square = move_up(input_move.to.clone()).unwrap();
-            board[captured_pawn_square.rank][captured_pawn_square.file] = Piece {piece: PieceType::Null, color: Color::Null};
+fn run_make_book(pgn: &str, path: &Path, max_ply: usize, min_games: u32) {
+    let games = std::fs::read_to_string(pgn)
+        .map_err(|err| format!("couldn't open {}: {}", pgn, err))
+        .and_then(|contents| parse_pgn_games(&contents).map_err(|err| format!("couldn't read {}: {}", pgn, err)));
+    let games = match games {
+        Ok(games) => games,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
-    } else if input_move.special_move == Some(SpecialMoveType::CastleLong) {
-        if input_move.piece.color == Color::White {
-            board[0][0] = Piece {piece: PieceType::Null, color: Color::Null};
-            board[0][3] = Piece {piece: PieceType::Rook, color: Color::White};
-        } else if input_move.piece.color == Color::Black {
-            board[7][0] = Piece {piece: PieceType::Null, color: Color::Null};
-            board[7][3] = Piece {piece: PieceType::Rook, color: Color::Black};
-        }
-    } else if input_move.special_move == Some(SpecialMoveType::CastleShort) {
-        if input_move.piece.color == Color::White {
-            board[0][7] = Piece {piece: PieceType::Null, color: Color::Null};
-            board[0][5] = Piece {piece: PieceType::Rook, color: Color::White};
-        } else if input_move.piece.color == Color::Black {
-            board[7][7] = Piece {piece: PieceType::Null, color: Color::Null};
-            board[7][5] = Piece {piece: PieceType::Rook, color: Color::Black};
+    };
+
+    let book = build_book(&games, max_ply, min_games);
+    if let Err(err) = book.save(path) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    println!("{} entries from {} games written to {}", book.entries.len(), games.len(), path.display());
+}
+
+fn run_make_tables(path: &Path) {
+    let tables = EndgameTables::generate_all().and_then(|tables| tables.save(path).map(|_| tables));
+    match tables {
+        Ok(tables) => println!("{} written to {}", tables.names().join(", "), path.display()),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
     }
 }
 
-fn prompt_promotion(board: &mut Board, square: Square) {
-    loop {
-       println!("Which piece would you like to promote to (enter Q,K,B,R)");
+fn run_analyze(game: &Game, depth: u32, tablebase: Option<Arc<Tablebase>>, endgame: Option<Arc<EndgameTables>>) {
+    println!("{}", game_to_fen(game));
+    if let Some(result) = game.result() {
+        println!("{}", result);
+        return;
+    }
 
-        let mut user_input = String::new();
-        let _b = match std::io::stdin().read_line(&mut user_input) {
-            Ok(v) => v,
-            _ => {println!("Illegal input, please enter Q, K, B, or R"); continue}
-        };
-    
-        if vec!['Q', 'K', 'B', 'R'].contains(&user_input.chars().nth(0).unwrap()) {
-            match user_input.chars().nth(0).unwrap() {
-                'Q' => board[square.rank][square.file].piece = PieceType::Queen,
-                'K' => board[square.rank][square.file].piece = PieceType::Knight,
-                'B' => board[square.rank][square.file].piece = PieceType::Bishop,
-                'R' => board[square.rank][square.file].piece = PieceType::Rook,
-                _ => {}
+    if let Some((best_move, dtm)) = endgame.as_ref().and_then(|endgame| endgame.best_move(game)) {
+        println!("endgame table {} for {}, best {}", dtm, if game.turn == Color::White {"White"} else {"Black"}, move_to_san(game, &best_move));
+        return;
+    }
+
+    // with the position in the tables there is nothing to search, every move's exact result
+    // and distance to zeroing are known
+    if let Some(tablebase) = &tablebase {
+        if let (Some(wdl), Some(dtz), Some(root_moves)) = (tablebase.probe_wdl(game), tablebase.probe_dtz(game), tablebase.root_moves(game)) {
+            println!("tablebase {} for {}, dtz {}", wdl, if game.turn == Color::White {"White"} else {"Black"}, dtz);
+            for root_move in root_moves {
+                println!("  {} {} dtz {}", move_to_san(game, &root_move.played), root_move.wdl, root_move.dtz);
             }
-            break;
+            return;
         }
-
-        println!("Illegal input, please enter Q, K, B, or R");
     }
-}
 
-fn evaluate_win_draw(
-    turn: Color, 
-    board: &Board, 
-    legal_moves: &Vec<Move>, 
-    move_history: &Vec<MoveHistoryEntry>,
-    three_fold_repetition_map: &HashMap<String, i32>,
-) -> Option<String> {
-    if is_50_move_draw(move_history) {
-        return Some(format!("draw by 50 move rule"));
-    } else if is_3_fold_repetition_draw(three_fold_repetition_map) {
-        return Some(format!("draw by 3 fold repetition"));
-    } else if is_insuffiecient_material_draw(board) {
-        return Some(format!("draw by insufficient material"));
+    // one line per depth so a deep search shows progress, scores are from White's side
+    for current_depth in 1..=depth.max(1) {
+        let search_result = engine::search(game, &SearchLimits {tablebase: tablebase.clone(), endgame: endgame.clone(), ..SearchLimits::depth(current_depth)});
+        let best_move = match search_result.best_move {
+            Some(best_move) => best_move,
+            None => return,
+        };
+        let score = if game.turn == Color::White {search_result.score} else {-search_result.score};
+        let score_text = match engine::mate_in(score) {
+            Some(moves) => format!("{}#{}", if moves > 0 {""} else {"-"}, moves.abs()),
+            None => format!("{:+.2}", score as f64 / 100.0),
+        };
+        println!("depth {} score {} best {} nodes {}", current_depth, score_text, move_to_san(game, &best_move), search_result.nodes);
+
+        if engine::mate_in(score).is_some() {
+            break;
+        }
     }
+}
 
-    let opponent_turn = if turn == White {Black} else {White};
-    let opponent_legal_moves = find_basic_legal_moves(&board, opponent_turn);
+// the position editor, nothing is checked until the position is played or analyzed
+fn run_editor(game: Game, options: &Options) {
+    let mut builder = PositionBuilder::from_game(&game);
+    let render_options = RenderOptions {ascii: options.ascii, ..RenderOptions::default()};
+    println!("type help for the commands");
+    loop {
+        let preview = Game::from_start(builder.position().clone());
+        println!("{}", render_board(&preview, &render_options, None, None));
+        println!("{:?} to move", preview.turn);
 
-    // locate the current player's king
-    let mut king_position: Square = Square {rank: 8, file: 8};
-    for i in 0..8 {
-        for j in 0..8 {
-            let piece = board[i][j];
-            if piece.piece == PieceType::King && piece.color == turn {
-                king_position = Square {rank: i, file: j};
+        let user_input = match read_input_line() {
+            Some(v) => v,
+            None => break, // stdin closed
+        };
+        match user_input.trim() {
+            "" => {}
+            "help" => println!("{}", EDITOR_HELP_TEXT),
+            "quit" => break,
+            "fen" => println!("{}", game_to_fen(&preview)),
+            "check" => {
+                let start = builder.position();
+                let problems = position_problems(&start.board, start.turn, &start.castling_rights, &start.en_passant);
+                if problems.is_empty() {
+                    println!("the position is legal");
+                }
+                for problem in problems {
+                    println!("{}", problem);
+                }
+            }
+            "play" | "analyze" => match builder.build() {
+                Ok(start) if user_input.trim() == "play" => {
+                    play(Game::from_start(start), options);
+                    return;
+                }
+                Ok(start) => run_analyze(&Game::from_start(start), options.depth, load_tablebase(options), load_tables(options)),
+                Err(err) => println!("{}", err),
+            },
+            command => {
+                if let Err(err) = apply_editor_command(&mut builder, command) {
+                    println!("{}", err);
+                }
             }
         }
     }
+}
 
-    // check if any opponent move can take my king
-    let mut opponent_can_capture_king = false;
-    for legal_move in opponent_legal_moves {
-        if legal_move.to == king_position {
-            opponent_can_capture_king = true;
+fn prompt_choice(question: &str, choices: &[&str], default: &str) -> String {
+    loop {
+        println!("{} [{}]", question, default);
+        let answer = match read_input_line() {
+            Some(v) => v.trim().to_lowercase(),
+            None => return default.to_string(),
+        };
+        if answer.is_empty() {
+            return default.to_string();
+        }
+        if choices.contains(&answer.as_str()) {
+            return answer;
         }
+        println!("please answer one of {}", choices.join(", "));
     }
+}
 
-    if legal_moves.len() == 0 && opponent_can_capture_king {
-        return Some(format!("{:?} wins", opponent_turn));
-    } else if legal_moves.len() == 0 && !opponent_can_capture_king {
-        return Some(format!("draw by stalemate"));
+fn prompt_players() -> Players {
+    if prompt_choice("Play against (h)uman or (c)omputer?", &["h", "c"], "h") == "h" {
+        return Players::humans();
     }
 
-    return None;
-}
+    let human_color = match prompt_choice("Play as (w)hite, (b)lack or (r)andom?", &["w", "b", "r"], "w").as_str() {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => if Random::from_time().below(2) == 0 {Color::White} else {Color::Black},
+    };
+    println!("You play {:?}", human_color);
 
-fn is_50_move_draw(move_history: &Vec<MoveHistoryEntry>) -> bool {
-    // since a move is defined as white turn then black turn, 2*50 MoveHistoryEntry structs need to be considered
-    let move_history_len = move_history.len();
-    let mut start_index;
+    let levels: Vec<String> = (1..=engine::MAX_LEVEL).map(|level| level.to_string()).collect();
+    let level_choices: Vec<&str> = levels.iter().map(|level| level.as_str()).collect();
+    let level: u32 = prompt_choice(&format!("Difficulty 1-{}?", engine::MAX_LEVEL), &level_choices, "3").parse().unwrap_or(3);
 
-    if move_history_len < 100 { // 50 moves haven't even been played yet
-        return false;
-    } else {
-        start_index = move_history_len - 100;
+    let computer = PlayerKind::Engine(level);
+    if human_color == Color::White {
+        return Players {white: PlayerKind::Human, black: computer};
     }
+    return Players {white: computer, black: PlayerKind::Human};
+}
 
-    for i in start_index..move_history_len {
-        if move_history[i].isCaptureMove {
-            return false;
-        }
-        if move_history[i].moveEntry.piece.piece == PieceType::Pawn {
-            return false;
-        }
+fn run_line_mode(mut game: Game, mut players: Players, mut render_options: RenderOptions, mut clock: Clock, book: Option<OpeningBook>) {
+    // a resumed game carries on the clock right away
+    if !game.move_history.is_empty() && game.result().is_none() {
+        clock.start(game.turn);
     }
-    
-    return true;
-}
+    // saved after every command that changes the game or who plays it, undo and a different
+    // move or setfen leave the number of moves as it was
+    let mut autosaved = (GameRecord::of(&game), players);
 
-fn is_insuffiecient_material_draw(board: &Board) -> bool {
-    let mut all_pieces_on_board: Vec<Piece> = Vec::new();
-    let mut non_king_pieces_on_board: Vec<(Piece, Color)> = Vec::new(); // piece and square color
+    loop {
+        println!("Current turn is {:?}", game.turn);
 
-    for rank in 0..8 {
-        for file in 0..8 {
-            if board[rank][file].piece != PieceType::Null {
-                all_pieces_on_board.push(board[rank][file].clone())
+        if game.result().is_none() {
+            if let Some(flagged) = clock.flagged() {
+                game.ended = Some(evaluate_timeout(&game.board, flagged));
             }
-
-            if board[rank][file].piece != PieceType::Null && board[rank][file].piece != PieceType::King {
-                let square_color = if (rank+file)%2 == 0 {
-                    Color::White
-                } else {
-                    Color::Black
-                };
-                non_king_pieces_on_board.push((board[rank][file].clone(),square_color));
+        }
+        if (GameRecord::of(&game), players) != autosaved {
+            if let Err(err) = savegame::autosave(&game, &players, &clock) {
+                println!("autosave failed: {}", err);
             }
+            autosaved = (GameRecord::of(&game), players);
+        }
+        if let Some(result) = game.result() {
+            println!("{}", result);
+            break;
         }
-    }
 
-    if all_pieces_on_board.len() == 2 { // king vs king
-        return true;
-    } else if all_pieces_on_board.len() == 3 { // king+bishop vs king or king+knight vs king
-        for piece in all_pieces_on_board {
-            if piece.piece == PieceType::Bishop || piece.piece == PieceType::Knight {
-                return true;
-            }
+        let mover = game.turn;
+        if let Some(engine_move) = players.engine_move(&game, book.as_ref()) {
+            println!("{:?} (computer) plays {}", game.turn, move_to_san(&game, &engine_move));
+            game.play_move(engine_move);
+            clock.press(mover);
+            continue;
         }
-    } else if all_pieces_on_board.len() == 4 { // king+bishop vs king+bishop same color bishops
-        if non_king_pieces_on_board.len() == 2 &&
-            non_king_pieces_on_board[0].0.piece == PieceType::Bishop && 
-            non_king_pieces_on_board[1].0.piece == PieceType::Bishop &&
-            non_king_pieces_on_board[0].1 == non_king_pieces_on_board[1].1 {
-            return true;
+
+        println!("{}", render_board(&game, &render_options, None, None));
+        if clock.time_control.is_some() {
+            println!("White {}  Black {}", clock.display(Color::White), clock.display(Color::Black));
         }
-    }
+        let moves_before = game.move_history.len();
 
-    return false;
-}
+        let user_input = match read_input_line() {
+            Some(v) => v,
+            None => break, // stdin closed
+        };
+        let user_input = user_input.trim();
+        let (command, argument) = match user_input.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (user_input, ""),
+        };
+
+        match command {
+            "" => {}
+            "help" => println!("{}", HELP_TEXT),
+            "new" => {
+                game = Game::new();
+                clock = Clock::new(clock.time_control);
+            }
+            "fen" => println!("{}", game_to_fen(&game)),
+            "setfen" => match parse_fen(argument) {
+                Ok(start) => game = Game::from_start(start),
+                Err(err) => println!("invalid FEN: {}", err),
+            },
+            "pgn" => println!("{}", game_to_pgn(&game)),
+            "save" => {
+                if argument.is_empty() {
+                    println!("usage: save <file>");
+                } else if let Err(err) = std::fs::write(argument, game_to_pgn(&game)) {
+                    println!("couldn't save to {}: {}", argument, err);
+                } else {
+                    println!("saved to {}", argument);
+                }
+            }
+            "savegame" => {
+                if argument.is_empty() {
+                    println!("usage: savegame <file>");
+                } else if let Err(err) = savegame::save_game(&PathBuf::from(argument), &game, &players, &clock) {
+                    println!("{}", err);
+                } else {
+                    println!("saved to {}", argument);
+                }
+            }
+            "loadgame" => match savegame::load_game(&PathBuf::from(argument)) {
+                Ok((loaded, loaded_players, loaded_clock)) => {
+                    game = loaded;
+                    players = loaded_players;
+                    clock = loaded_clock;
+                    if !game.move_history.is_empty() && game.result().is_none() {
+                        clock.start(game.turn);
+                    }
+                }
+                Err(err) => println!("{}", err),
+            },
+            "load" => match std::fs::read_to_string(argument) {
+                Ok(contents) => match parse_pgn(&contents) {
+                    Ok(loaded) => game = loaded,
+                    Err(err) => println!("couldn't read {}: {}", argument, err),
+                },
+                Err(err) => println!("couldn't open {}: {}", argument, err),
+            },
+            "moves" => match parse_square(argument) {
+                Ok(square) => {
+                    let mut sans: Vec<String> = vec![];
+                    for legal_move in game.legal_moves() {
+                        if legal_move.from == square {
+                            sans.push(move_to_san(&game, &legal_move));
+                        }
+                    }
+                    if sans.is_empty() {
+                        println!("no legal moves from {}", argument);
+                    } else {
+                        println!("{}", render_board(&game, &render_options, Some(&square), None));
+                        println!("{}", sans.join(" "));
+                    }
+                }
+                Err(_) => println!("usage: moves <square>, eg moves e2"),
+            },
+            "flip" => {
+                render_options.perspective = if render_options.is_flipped(game.turn) {
+                    Perspective::White
+                } else {
+                    Perspective::Black
+                };
+            }
+            "display" => match argument {
+                "ascii" => render_options.ascii = true,
+                "unicode" => render_options.ascii = false,
+                "color" => render_options.colors = true,
+                "plain" => render_options.colors = false,
+                "auto" => render_options.perspective = Perspective::SideToMove,
+                "white" => render_options.perspective = Perspective::White,
+                "black" => render_options.perspective = Perspective::Black,
+                _ => println!("usage: display ascii|unicode|color|plain|auto|white|black"),
+            },
+            "undo" => {
+                if !players.undo(&mut game) {
+                    println!("nothing to undo");
+                }
+            }
+            "redo" => {
+                if !players.redo(&mut game) {
+                    println!("nothing to redo");
+                }
+            }
+            "hint" => {
+                let search_result = engine::search(&game, &SearchLimits::depth(HINT_DEPTH));
+                if let Some(best_move) = search_result.best_move {
+                    println!("hint: {} ({:+.2})", move_to_san(&game, &best_move), search_result.score as f64 / 100.0);
+                }
+            }
+            "resign" => game.resign(game.turn),
+            "quit" | "exit" => break,
+            _ => match input_move(&game, user_input) {
+                Ok(legal_move) => game.play_move(legal_move),
+                Err(err) => println!("{}", err),
+            },
+        }
 
-fn is_3_fold_repetition_draw(three_fold_repetition_map: &HashMap<String, i32>) -> bool {
-    for (_position, occurances) in three_fold_repetition_map {
-        if *occurances >= 3 {
-            return true;
+        // the clock starts with the first move, like in the full screen board, a move made
+        // after the flag fell doesn't earn an increment
+        if game.move_history.len() > moves_before && game.result().is_none() && clock.flagged().is_none() {
+            clock.press(mover);
         }
     }
 
-    return false;
-}
\ No newline at end of file
+
+    println!("Game Over");
+}
@@ -0,0 +1,372 @@
+use crate::piece::Color;
+use crate::game::Game;
+use crate::fen::parse_fen;
+use crate::pgn::parse_pgn;
+use crate::clock::TimeControl;
+use crate::players::PlayerKind;
+use crate::engine::MAX_LEVEL;
+use crate::savegame::autosave_path;
+use crate::network::DEFAULT_PORT;
+use crate::fics::ratings_path;
+use crate::engine_match::MatchEngine;
+use crate::engine_match::MatchSettings;
+use crate::engine_match::parse_match_engine;
+use crate::solver::Stipulation;
+use crate::solver::parse_stipulation;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const COMMANDS: [&str; 16] = ["play", "perft", "uci", "json", "batch", "host", "join", "fics", "match", "book", "make-book", "make-tables", "analyze", "mate", "solve", "edit"];
+
+pub const USAGE: &str = "\
+usage: chess [command] [options]
+
+commands:
+  play              play a game (the default)
+  perft <depth>     count the positions reachable in <depth> moves, per first move
+  uci               talk the UCI protocol on stdin/stdout for chess GUIs
+  json              read JSON requests line by line on stdin and answer each with
+                    the game state as JSON on stdout
+  batch [file]      play the UCI or SAN moves in the file (or stdin) and print
+                    the final FEN, PGN and result, exits with 1 on an illegal move
+  host              wait for another player to join over the network
+  join <host[:port]>
+                    play against a hosted game
+  fics              run a server in the style of FICS where many players log in
+                    over telnet, seek or challenge each other and play rated games
+  match <first> <second>
+                    play engines against each other and estimate the Elo difference,
+                    each is engine[:<level>] or uci:<command>, games go to match.pgn
+  book <file>       list the moves a Polyglot opening book has for the --fen/--pgn position
+  make-book <pgn> <file>
+                    write a Polyglot opening book of the games in a PGN file
+  make-tables <file>
+                    work out distance to mate tables for KQK, KRK, KPK, KBNK and KQKR
+                    (takes a few minutes) and write them to a file for --tables
+  analyze           print the engine's evaluation of the --fen/--pgn position, and the
+                    exact result with --syzygy
+  mate <n>          prove or refute a forced mate in n moves from the --fen/--pgn position
+                    and print every key with the answers to all defenses
+  solve <stipulation>
+                    solve the --fen/--pgn position as a problem: #n mate, h#n (or h#n.5)
+                    helpmate, s#n selfmate or r#n reflexmate, printing every solution
+  edit              set up a position piece by piece, starting from the --fen/--pgn position,
+                    then play or analyze it
+
+options:
+  --fen <fen>                 start from a FEN position
+  --pgn <file>                start from the end of the game in a PGN file
+  --white <player>            who plays White: human, engine, uci:<command> for another
+                              UCI engine or network for somebody joining with chess join
+  --black <player>            who plays Black, the same choices
+  --level <1-5>               engine strength, 3 by default
+  --time <minutes>[+<secs>]   time control, eg 5+3
+  --depth <n>                 search depth for analyze, 6 by default
+  --variant standard          the only variant there is for now
+  --port <n>                  port to host or serve on, 7777 by default
+  --color white|black|random  the host's side, random by default
+  --ratings <file>            where fics keeps the ratings, ~/.rustchess-ratings by default
+  --games <n>                 games in a match, 10 by default
+  --openings <file>           FENs, one a line, or a PGN file of openings for a match
+  --out <file>                the PGN file a match appends its games to
+  --sprt <elo0>,<elo1>        stop the match once a sequential test between the two decides
+  --no-adjudication           play match games out instead of ending clear ones early
+  --book <file>               a Polyglot opening book for the engine to play from
+  --max-ply <n>               how deep into the games make-book goes, 20 plies by default
+  --min-games <n>             the fewest games a move needs for make-book to keep it, 3 by default
+  --tables <file>             endgame tables from make-tables for the engine, analyze and match
+                              adjudication
+  --syzygy <dir>              Syzygy endgame tables for analyze, several directories separated by ':'
+  --ascii                     draw pieces with letters instead of chess symbols
+  --flip                      show the board from Black's side
+  --line                      use the line based interface even in a terminal
+  --resume [file]             carry on with a saved game, the last autosave without a
+                              file, games are autosaved to ~/.rustchess-autosave.json
+  -h, --help                  show this text";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Play,
+    Perft(u32),
+    Solve(Stipulation),
+    Batch(Option<String>),
+    Host,
+    Join(String),
+    Fics,
+    Match(MatchEngine, MatchEngine),
+    Book(PathBuf),
+    MakeBook(String, PathBuf), // the PGN file and the book to write
+    MakeTables(PathBuf),
+    Uci,
+    Json,
+    Analyze,
+    Edit,
+    Help,
+}
+
+// players only the GameRunner can seat
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternalPlayer {
+    Uci(String), // the command that starts the engine
+    Network,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub fen: Option<String>,
+    pub pgn: Option<String>,
+    pub white: Option<PlayerKind>,
+    pub black: Option<PlayerKind>,
+    pub white_external: Option<ExternalPlayer>,
+    pub black_external: Option<ExternalPlayer>,
+    pub time_control: Option<TimeControl>,
+    pub depth: u32,
+    pub ascii: bool,
+    pub flip: bool,
+    pub line: bool,
+    pub resume: Option<PathBuf>,
+    pub port: u16,
+    pub color: Option<Color>, // the host's side, random if not given
+    pub ratings: PathBuf,
+    pub match_settings: MatchSettings,
+    pub book: Option<PathBuf>,
+    pub max_ply: usize, // for make-book
+    pub min_games: u32,
+    pub syzygy: Option<String>, // tablebase directories
+    pub tables: Option<PathBuf>, // from make-tables
+}
+
+// args without the program name
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        command: Command::Play,
+        fen: None,
+        pgn: None,
+        white: None,
+        black: None,
+        white_external: None,
+        black_external: None,
+        time_control: None,
+        depth: 6,
+        ascii: false,
+        flip: false,
+        line: false,
+        resume: None,
+        port: DEFAULT_PORT,
+        color: None,
+        ratings: ratings_path(),
+        match_settings: MatchSettings::default(),
+        book: None,
+        max_ply: 20,
+        min_games: 3,
+        syzygy: None,
+        tables: None,
+    };
+    // whether each side is an engine, if given
+    let mut white_engine: Option<bool> = None;
+    let mut black_engine: Option<bool> = None;
+    let mut level = 3;
+
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| match args.next() {
+            Some(value) => Ok(value.clone()),
+            None => Err(format!("{} needs a value", name)),
+        };
+
+        match arg.as_str() {
+            "play" => options.command = Command::Play,
+            "uci" => options.command = Command::Uci,
+            "json" => options.command = Command::Json,
+            "analyze" => options.command = Command::Analyze,
+            "edit" => options.command = Command::Edit,
+            "batch" => {
+                // an optional file to read the moves from, stdin without one
+                let file = args.next_if(|next| !next.starts_with('-')).cloned();
+                options.command = Command::Batch(file);
+            }
+            "perft" => {
+                let depth = value("perft")?;
+                options.command = Command::Perft(depth.parse().map_err(|_| format!("invalid perft depth {}", depth))?);
+            }
+            "mate" => {
+                let moves = value("mate")?;
+                match moves.parse() {
+                    Ok(moves) if moves > 0 => options.command = Command::Solve(Stipulation::Mate(moves)),
+                    _ => return Err(format!("invalid number of moves {}", moves)),
+                }
+            }
+            "solve" => options.command = Command::Solve(parse_stipulation(&value("solve")?)?),
+            "host" => options.command = Command::Host,
+            "join" => options.command = Command::Join(value("join")?),
+            "fics" => options.command = Command::Fics,
+            "book" => options.command = Command::Book(PathBuf::from(value("book")?)),
+            "make-book" => {
+                let pgn = value("make-book")?;
+                options.command = Command::MakeBook(pgn, PathBuf::from(value("make-book")?));
+            }
+            "make-tables" => options.command = Command::MakeTables(PathBuf::from(value("make-tables")?)),
+            "match" => {
+                let first = parse_match_engine(&value("match")?)?;
+                let second = parse_match_engine(&value("match")?)?;
+                options.command = Command::Match(first, second);
+            }
+            "-h" | "--help" | "help" => options.command = Command::Help,
+            "--fen" => options.fen = Some(value("--fen")?),
+            "--pgn" => options.pgn = Some(value("--pgn")?),
+            "--white" => {
+                let text = value("--white")?;
+                match parse_external_player(&text) {
+                    Some(external) => options.white_external = Some(external),
+                    None => white_engine = Some(parse_player(&text)?),
+                }
+            }
+            "--black" => {
+                let text = value("--black")?;
+                match parse_external_player(&text) {
+                    Some(external) => options.black_external = Some(external),
+                    None => black_engine = Some(parse_player(&text)?),
+                }
+            }
+            "--level" => {
+                let text = value("--level")?;
+                level = match text.parse::<u32>() {
+                    Ok(level) if (1..=MAX_LEVEL).contains(&level) => level,
+                    _ => return Err(format!("level must be between 1 and {}", MAX_LEVEL)),
+                };
+            }
+            "--time" => options.time_control = Some(parse_time_control(&value("--time")?)?),
+            "--depth" => {
+                let text = value("--depth")?;
+                options.depth = text.parse().map_err(|_| format!("invalid depth {}", text))?;
+            }
+            "--variant" => {
+                let variant = value("--variant")?;
+                if !["standard", "classical", "chess"].contains(&variant.as_str()) {
+                    return Err(format!("unknown variant {}, only standard chess is supported", variant));
+                }
+            }
+            "--port" => {
+                let text = value("--port")?;
+                options.port = text.parse().map_err(|_| format!("invalid port {}", text))?;
+            }
+            "--color" => {
+                options.color = match value("--color")?.as_str() {
+                    "white" => Some(Color::White),
+                    "black" => Some(Color::Black),
+                    "random" => None,
+                    other => return Err(format!("color must be white, black or random, not {}", other)),
+                };
+            }
+            "--ratings" => options.ratings = PathBuf::from(value("--ratings")?),
+            "--games" => {
+                let text = value("--games")?;
+                options.match_settings.games = text.parse().map_err(|_| format!("invalid number of games {}", text))?;
+            }
+            "--openings" => options.match_settings.openings = Some(PathBuf::from(value("--openings")?)),
+            "--out" => options.match_settings.out = PathBuf::from(value("--out")?),
+            "--sprt" => {
+                let text = value("--sprt")?;
+                let bounds = text.split_once(',').and_then(|(elo0, elo1)| Some((elo0.parse().ok()?, elo1.parse().ok()?)));
+                options.match_settings.sprt = match bounds {
+                    Some((elo0, elo1)) if elo0 < elo1 => Some((elo0, elo1)),
+                    _ => return Err(format!("invalid SPRT bounds {}, expected <elo0>,<elo1> with elo0 below elo1", text)),
+                };
+            }
+            "--no-adjudication" => options.match_settings.adjudication = None,
+            "--book" => options.book = Some(PathBuf::from(value("--book")?)),
+            "--tables" => options.tables = Some(PathBuf::from(value("--tables")?)),
+            "--syzygy" => options.syzygy = Some(value("--syzygy")?),
+            "--max-ply" => {
+                let text = value("--max-ply")?;
+                options.max_ply = text.parse().map_err(|_| format!("invalid number of plies {}", text))?;
+            }
+            "--min-games" => {
+                let text = value("--min-games")?;
+                options.min_games = text.parse().map_err(|_| format!("invalid number of games {}", text))?;
+            }
+            "--ascii" => options.ascii = true,
+            "--flip" => options.flip = true,
+            "--line" => options.line = true,
+            "--resume" => {
+                // the autosave unless a file follows
+                let file = args.next_if(|next| !next.starts_with('-') && !COMMANDS.contains(&next.as_str()));
+                options.resume = Some(file.map(PathBuf::from).unwrap_or_else(autosave_path));
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let player = |engine: bool| if engine {PlayerKind::Engine(level)} else {PlayerKind::Human};
+    options.white = white_engine.map(player);
+    options.black = black_engine.map(player);
+
+    if [options.fen.is_some(), options.pgn.is_some(), options.resume.is_some()].iter().filter(|given| **given).count() > 1 {
+        return Err(format!("only one of --fen, --pgn and --resume can be used"));
+    }
+
+    return Ok(options);
+}
+
+// true for engine, false for human
+fn parse_player(text: &str) -> Result<bool, String> {
+    match text {
+        "human" => Ok(false),
+        "engine" | "computer" => Ok(true),
+        _ => Err(format!("player must be human, engine, uci:<command> or network, not {}", text)),
+    }
+}
+
+fn parse_external_player(text: &str) -> Option<ExternalPlayer> {
+    if text == "network" {
+        return Some(ExternalPlayer::Network);
+    }
+    return text.strip_prefix("uci:").map(|command| ExternalPlayer::Uci(command.to_string()));
+}
+
+// "5" is five minutes each, "5+3" adds three seconds per move
+pub fn parse_time_control(text: &str) -> Result<TimeControl, String> {
+    let invalid = || format!("invalid time control {}, expected minutes or minutes+seconds", text);
+    let (minutes, seconds) = text.split_once('+').unwrap_or((text, "0"));
+    let minutes: f64 = minutes.parse().map_err(|_| invalid())?;
+    let seconds: u64 = seconds.parse().map_err(|_| invalid())?;
+    // also keeps out nan and inf, which Duration can't hold
+    if !(minutes > 0.0 && minutes <= 600.0) || seconds > 600 {
+        return Err(format!("invalid time control {}, expected up to 600 minutes and 600 seconds", text));
+    }
+
+    return Ok(TimeControl {
+        initial: Duration::from_secs_f64(minutes * 60.0),
+        increment: Duration::from_secs(seconds),
+    });
+}
+
+// the game described by --fen or --pgn, the classical start without either
+pub fn starting_game(options: &Options) -> Result<Game, String> {
+    if let Some(fen) = &options.fen {
+        return Ok(Game::from_start(parse_fen(fen).map_err(|err| format!("invalid FEN: {}", err))?));
+    }
+    if let Some(path) = &options.pgn {
+        let contents = std::fs::read_to_string(path).map_err(|err| format!("couldn't open {}: {}", path, err))?;
+        return parse_pgn(&contents).map_err(|err| format!("couldn't read {}: {}", path, err));
+    }
+
+    return Ok(Game::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_controls_out_of_range_are_rejected() {
+        let control = parse_time_control("5+3").unwrap();
+        assert_eq!(control.initial, Duration::from_secs(300));
+        assert_eq!(control.increment, Duration::from_secs(3));
+
+        for text in ["nan", "inf", "-inf", "1e300", "0", "-5", "601", "5+601", "5+18446744073709551615"] {
+            assert!(parse_time_control(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::piece::Color;
+use crate::piece::Color::*;
+use std::time::Duration;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+// chess clock for both sides, without a time control it just counts the time each side used
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub time_control: Option<TimeControl>,
+    // time left with a time control, time used without one, as of the last time the clock stopped
+    banked: [Duration; 2], // White, Black
+    running: Option<(Color, Instant)>,
+}
+
+fn color_index(color: Color) -> usize {
+    if color == White {0} else {1}
+}
+
+impl Clock {
+    pub fn new(time_control: Option<TimeControl>) -> Clock {
+        let initial = match time_control {
+            Some(time_control) => time_control.initial,
+            None => Duration::ZERO,
+        };
+
+        Clock {
+            time_control,
+            banked: [initial, initial],
+            running: None,
+        }
+    }
+
+    // a stopped clock with the given time left on each side, for resuming a saved game
+    pub fn restore(time_control: TimeControl, white_left: Duration, black_left: Duration) -> Clock {
+        Clock {
+            time_control: Some(time_control),
+            banked: [white_left, black_left],
+            running: None,
+        }
+    }
+
+    pub fn start(&mut self, color: Color) {
+        self.stop();
+        self.running = Some((color, Instant::now()));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((color, since)) = self.running.take() {
+            let banked = &mut self.banked[color_index(color)];
+            if self.time_control.is_some() {
+                *banked = banked.saturating_sub(since.elapsed());
+            } else {
+                *banked += since.elapsed();
+            }
+        }
+    }
+
+    // called when `color` finishes its move, adds the increment and starts the opponent's clock
+    pub fn press(&mut self, color: Color) {
+        self.stop();
+        if let Some(time_control) = self.time_control {
+            self.banked[color_index(color)] += time_control.increment;
+        }
+        self.start(if color == White {Black} else {White});
+    }
+
+    // the side whose time is going down, None while the clock is stopped
+    pub fn running(&self) -> Option<Color> {
+        return self.running.map(|(color, _)| color);
+    }
+
+    // time left with a time control, time used without one
+    pub fn current(&self, color: Color) -> Duration {
+        let banked = self.banked[color_index(color)];
+        match self.running {
+            Some((running_color, since)) if running_color == color => {
+                if self.time_control.is_some() {
+                    banked.saturating_sub(since.elapsed())
+                } else {
+                    banked + since.elapsed()
+                }
+            }
+            _ => banked,
+        }
+    }
+
+    // the side whose flag fell, if any
+    pub fn flagged(&self) -> Option<Color> {
+        self.time_control?;
+        return [White, Black].into_iter().find(|color| self.current(*color) == Duration::ZERO);
+    }
+
+    pub fn display(&self, color: Color) -> String {
+        let seconds = self.current(color).as_secs();
+        if seconds >= 3600 {
+            return format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+        }
+
+        return format!("{:02}:{:02}", seconds / 60, seconds % 60);
+    }
+}
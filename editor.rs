@@ -0,0 +1,213 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::position::*;
+use crate::fen::fen_char_to_piece;
+use crate::fen::parse_fen_unchecked;
+use crate::fen::parse_castling;
+use crate::game::Game;
+use crate::game::StartPosition;
+use crate::validate::validate_position;
+
+// a position put together piece by piece, for problems and test positions. Nothing is
+// checked until build, so it can go through positions that aren't legal on the way
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionBuilder {
+    start: StartPosition,
+}
+
+impl PositionBuilder {
+    // an empty board with White to move and no castling
+    pub fn empty() -> PositionBuilder {
+        let empty_piece = Piece {piece: PieceType::Null, color: Color::Null};
+        let board: Board = [[empty_piece; 8]; 8];
+        return PositionBuilder {
+            start: StartPosition {
+                board,
+                turn: White,
+                castling_rights: NO_CASTLING,
+                en_passant: None,
+                halfmove_clock: 0,
+                fullmove_number: 1,
+            },
+        };
+    }
+
+    pub fn from_start(start: StartPosition) -> PositionBuilder {
+        return PositionBuilder {start};
+    }
+
+    // the game's current position, not the one it started from
+    pub fn from_game(game: &Game) -> PositionBuilder {
+        return PositionBuilder::from_start(StartPosition {
+            board: game.board,
+            turn: game.turn,
+            castling_rights: game.castling_rights(),
+            en_passant: game.en_passant_square(),
+            halfmove_clock: game.halfmove_clock(),
+            fullmove_number: game.fullmove_number(),
+        });
+    }
+
+    pub fn put(&mut self, square: &Square, piece: Piece) -> &mut PositionBuilder {
+        self.start.board[square.rank][square.file] = piece;
+        return self;
+    }
+
+    pub fn clear(&mut self, square: &Square) -> &mut PositionBuilder {
+        return self.put(square, Piece {piece: PieceType::Null, color: Color::Null});
+    }
+
+    pub fn clear_all(&mut self) -> &mut PositionBuilder {
+        let turn = self.start.turn;
+        *self = PositionBuilder::empty();
+        self.start.turn = turn;
+        return self;
+    }
+
+    pub fn side(&mut self, turn: Color) -> &mut PositionBuilder {
+        self.start.turn = turn;
+        return self;
+    }
+
+    pub fn castle(&mut self, castling_rights: CastlingRights) -> &mut PositionBuilder {
+        self.start.castling_rights = castling_rights;
+        return self;
+    }
+
+    pub fn en_passant(&mut self, square: Option<Square>) -> &mut PositionBuilder {
+        self.start.en_passant = square;
+        return self;
+    }
+
+    // the position as it stands, legal or not
+    pub fn position(&self) -> &StartPosition {
+        return &self.start;
+    }
+
+    pub fn build(&self) -> Result<StartPosition, String> {
+        validate_position(&self.start)?;
+        return Ok(self.start.clone());
+    }
+}
+
+// "wN", "bq" or a FEN letter like "N" and "q"
+pub fn parse_piece_name(name: &str) -> Result<Piece, String> {
+    let invalid = || format!("invalid piece {}, expected eg wN, bQ or a FEN letter", name);
+    let chars: Vec<char> = name.chars().collect();
+    return match chars.as_slice() {
+        [color @ ('w' | 'b'), letter] => {
+            let piece = fen_char_to_piece(*letter).map_err(|_| invalid())?;
+            Ok(Piece {piece: piece.piece, color: if *color == 'w' {White} else {Black}})
+        }
+        [letter] => fen_char_to_piece(*letter).map_err(|_| invalid()),
+        _ => Err(invalid()),
+    };
+}
+
+fn square_argument(argument: &str) -> Result<Square, String> {
+    return parse_square(argument).map_err(|_| format!("invalid square {}", argument));
+}
+
+// one editing command, `put wN f3`, `clear e4`, `empty`, `start`, `side black`, `castle KQ`,
+// `ep e6` or `fen <fen>`
+pub fn apply_editor_command(builder: &mut PositionBuilder, line: &str) -> Result<(), String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["put", piece, squares @ ..] if !squares.is_empty() => {
+            let piece = parse_piece_name(piece)?;
+            for square in squares {
+                builder.put(&square_argument(square)?, piece);
+            }
+        }
+        ["clear", squares @ ..] if !squares.is_empty() => {
+            for square in squares {
+                builder.clear(&square_argument(square)?);
+            }
+        }
+        ["empty"] => {
+            builder.clear_all();
+        }
+        ["start"] => *builder = PositionBuilder::from_start(StartPosition::classical()),
+        ["side", side] => {
+            let turn = match *side {
+                "white" | "w" => White,
+                "black" | "b" => Black,
+                _ => return Err(format!("side must be white or black, got {}", side)),
+            };
+            builder.side(turn);
+        }
+        ["castle", rights] => {
+            builder.castle(parse_castling(rights)?);
+        }
+        ["ep", "-"] => {
+            builder.en_passant(None);
+        }
+        ["ep", square] => {
+            builder.en_passant(Some(square_argument(square)?));
+        }
+        ["fen", ..] => {
+            let fen = line.trim_start().trim_start_matches("fen").trim();
+            *builder = PositionBuilder::from_start(parse_fen_unchecked(fen)?);
+        }
+        _ => return Err(format!("unknown editing command {}", line.trim())),
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::game_to_fen;
+    use crate::pgn::parse_pgn;
+
+    fn apply(builder: &mut PositionBuilder, commands: &[&str]) {
+        for command in commands {
+            apply_editor_command(builder, command).unwrap();
+        }
+    }
+
+    #[test]
+    fn nothing_is_checked_until_build() {
+        let mut builder = PositionBuilder::empty();
+        // no kings and a pawn on the first rank, fine to pass through
+        apply(&mut builder, &["fen 8/8/8/8/8/8/8/P7 w KQ e3 0 1"]);
+        assert!(builder.position().castling_rights.white_short);
+        assert!(builder.build().is_err());
+
+        apply(&mut builder, &["clear a1", "put wK e1", "put wR a1 h1", "put bk e8", "ep -"]);
+        let start = builder.build().unwrap();
+        assert_eq!(start.board[0][7], Piece {piece: PieceType::Rook, color: White});
+
+        apply(&mut builder, &["side black", "put bQ e2"]);
+        assert!(builder.build().unwrap_err().contains("White is in check"));
+    }
+
+    #[test]
+    fn a_game_is_edited_from_where_it_stands() {
+        let game = parse_pgn("1. e4 e5 2. Nf3 *").unwrap();
+        let builder = PositionBuilder::from_game(&game);
+        let start = builder.position();
+        assert_eq!(start.board, game.board);
+        assert_eq!(start.turn, Black);
+        assert_eq!(start.castling_rights, game.castling_rights());
+        assert_eq!((start.halfmove_clock, start.fullmove_number), (1, 2));
+        assert_eq!(game_to_fen(&Game::from_start(builder.build().unwrap())), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        // a double step just played leaves its en passant square
+        let game = parse_pgn("1. e4 *").unwrap();
+        assert_eq!(PositionBuilder::from_game(&game).position().en_passant, Some(Square {rank: 2, file: 4}));
+    }
+
+    #[test]
+    fn bad_commands_are_errors() {
+        let mut builder = PositionBuilder::empty();
+        for command in ["put wX e4", "put wN i9", "side green", "castle KX", "fen not a fen", "jump"] {
+            assert!(apply_editor_command(&mut builder, command).is_err(), "{}", command);
+        }
+        assert_eq!(builder, PositionBuilder::empty());
+    }
+}
@@ -0,0 +1,748 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::PieceType::*;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::position::*;
+use crate::legal_moves::*;
+use crate::game::Game;
+use crate::game::play_move;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// distance to mate tables for endings with few pieces, worked out backwards from every mate
+// with the normal move rules, the side with more material is White in the tables and
+// positions with Black stronger are looked up with colors and ranks flipped
+
+// the endings generate_all makes, in an order where each one's captures and promotions lead
+// to endings made before it
+pub const ENDINGS: [&str; 5] = ["KQvK", "KRvK", "KPvK", "KBNvK", "KQvKR"];
+
+const MAGIC: &[u8] = b"RCDTM1";
+
+// stored values are plies to mate plus one, odd plies are wins for the side to move and even
+// plies losses
+const UNKNOWN: u8 = 0; // a draw once generation is done
+const INVALID: u8 = 255; // impossible, or a mirror image of a position stored elsewhere
+
+// the a1-d1-d4 triangle the strong king of a pawnless ending is mirrored into
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+const ROOK_STEPS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_STEPS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+// the result with best play for the side to move, in plies to mate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Dtm {
+    // the same position from the side that just moved, one ply earlier
+    fn before(&self) -> Dtm {
+        match self {
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+            Dtm::Draw => Dtm::Draw,
+        }
+    }
+
+    fn rank(&self) -> i64 {
+        match self {
+            Dtm::Win(plies) => 1000 - *plies as i64,
+            Dtm::Loss(plies) => -1000 + *plies as i64,
+            Dtm::Draw => 0,
+        }
+    }
+
+    // whether this is better for the side to move than `other`, quicker mates and slower
+    // losses being better
+    pub fn is_better_than(&self, other: &Dtm) -> bool {
+        return self.rank() > other.rank();
+    }
+}
+
+impl fmt::Display for Dtm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dtm::Win(plies) => write!(f, "mate in {}", plies.div_ceil(2)),
+            Dtm::Loss(0) => write!(f, "mated"),
+            Dtm::Loss(plies) => write!(f, "mated in {}", plies.div_ceil(2)),
+            Dtm::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+fn piece_letter(piece: PieceType) -> char {
+    match piece {
+        King => 'K',
+        Queen => 'Q',
+        Rook => 'R',
+        Bishop => 'B',
+        Knight => 'N',
+        Pawn => 'P',
+        PieceType::Null => '?',
+    }
+}
+
+// one side's pieces the way ending names write them, eg KBN
+fn material(board: &Board, color: Color) -> String {
+    let mut res = String::new();
+    for piece in [King, Queen, Rook, Bishop, Knight, Pawn] {
+        for square in 0..64 {
+            if board[square / 8][square % 8] == (Piece {piece, color}) {
+                res.push(piece_letter(piece));
+            }
+        }
+    }
+
+    return res;
+}
+
+// K v K and a lone minor piece can't mate
+fn is_trivial_draw(board: &Board) -> bool {
+    let pieces: Vec<PieceType> = board.iter().flatten()
+        .map(|piece| piece.piece)
+        .filter(|piece| *piece != PieceType::Null && *piece != King)
+        .collect();
+    return pieces.is_empty() || (pieces.len() == 1 && (pieces[0] == Bishop || pieces[0] == Knight));
+}
+
+fn transform(square: usize, symmetry: usize) -> usize {
+    let (mut file, mut rank) = (square % 8, square / 8);
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        std::mem::swap(&mut file, &mut rank);
+    }
+
+    return rank * 8 + file;
+}
+
+fn step(square: usize, (file_step, rank_step): (i32, i32)) -> Option<usize> {
+    let file = (square % 8) as i32 + file_step;
+    let rank = (square / 8) as i32 + rank_step;
+    if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+        return None;
+    }
+
+    return Some((rank * 8 + file) as usize);
+}
+
+fn opposite(color: Color) -> Color {
+    return if color == White {Black} else {White};
+}
+
+// one ending, every position indexed by the side to move, where the anchor piece stands
+// after mirroring and where the others stand
+pub struct DtmTable {
+    pub name: String,
+    pieces: Vec<Piece>, // in the order of the name, White first
+    anchor: usize, // the strong king, or the first pawn when there are pawns
+    has_pawns: bool,
+    values: Vec<u8>,
+}
+
+impl DtmTable {
+    fn new(name: &str) -> Result<DtmTable, String> {
+        let (white, black) = name.split_once('v').ok_or_else(|| format!("bad ending name {}", name))?;
+        let mut pieces = vec![];
+        for (side, color) in [(white, White), (black, Black)] {
+            for letter in side.chars() {
+                let piece = match letter {
+                    'K' => King,
+                    'Q' => Queen,
+                    'R' => Rook,
+                    'B' => Bishop,
+                    'N' => Knight,
+                    'P' => Pawn,
+                    _ => return Err(format!("bad ending name {}", name)),
+                };
+                // two alike pieces would need sorting into one order, none of the endings has them
+                if pieces.contains(&Piece {piece, color}) {
+                    return Err(format!("{} has two pieces of a kind, which isn't supported", name));
+                }
+                pieces.push(Piece {piece, color});
+            }
+        }
+        if !white.starts_with('K') || !black.starts_with('K') || white.matches('K').count() != 1 || black.matches('K').count() != 1 {
+            return Err(format!("{} needs one king a side", name));
+        }
+        let has_pawns = name.contains('P');
+        let anchor = if has_pawns {pieces.iter().position(|piece| piece.piece == Pawn).unwrap()} else {0};
+        if has_pawns && pieces[anchor].color != White {
+            return Err(format!("{} has to have White's pawn first", name));
+        }
+
+        let mut table = DtmTable {name: name.to_string(), pieces, anchor, has_pawns, values: vec![]};
+        table.values = vec![UNKNOWN; table.size()];
+        return Ok(table);
+    }
+
+    fn anchor_squares(&self) -> usize {
+        // pawns on files a to d and ranks 2 to 7
+        return if self.has_pawns {24} else {TRIANGLE.len()};
+    }
+
+    fn size(&self) -> usize {
+        return 2 * self.anchor_squares() * 64usize.pow(self.pieces.len() as u32 - 1);
+    }
+
+    fn anchor_code(&self, square: usize) -> Option<usize> {
+        if self.has_pawns {
+            let (file, rank) = (square % 8, square / 8);
+            return if file <= 3 && (1..=6).contains(&rank) {Some((rank - 1) * 4 + file)} else {None};
+        }
+        return TRIANGLE.iter().position(|corner| *corner == square);
+    }
+
+    fn anchor_square(&self, code: usize) -> usize {
+        if self.has_pawns {
+            return (code / 4 + 1) * 8 + code % 4;
+        }
+        return TRIANGLE[code];
+    }
+
+    // the smallest index among the mirror images of the position, pawns only mirror left to
+    // right, squares are in the table's piece order
+    fn index(&self, squares: &[usize], turn: Color) -> usize {
+        let symmetries = if self.has_pawns {2} else {8};
+        let mut best = usize::MAX;
+        for symmetry in 0..symmetries {
+            let code = match self.anchor_code(transform(squares[self.anchor], symmetry)) {
+                Some(code) => code,
+                None => continue,
+            };
+            let mut index = (turn == Black) as usize * self.anchor_squares() + code;
+            for (i, square) in squares.iter().enumerate() {
+                if i != self.anchor {
+                    index = index * 64 + transform(*square, symmetry);
+                }
+            }
+            best = best.min(index);
+        }
+
+        return best;
+    }
+
+    fn decode(&self, index: usize) -> (Vec<usize>, Color) {
+        let mut squares = vec![0; self.pieces.len()];
+        let mut rest = index;
+        for i in (0..self.pieces.len()).rev() {
+            if i != self.anchor {
+                squares[i] = rest % 64;
+                rest /= 64;
+            }
+        }
+        squares[self.anchor] = self.anchor_square(rest % self.anchor_squares());
+        let turn = if rest / self.anchor_squares() == 1 {Black} else {White};
+
+        return (squares, turn);
+    }
+
+    fn board(&self, squares: &[usize]) -> Board {
+        let mut board = [[Piece {piece: PieceType::Null, color: Color::Null}; 8]; 8];
+        for (piece, square) in self.pieces.iter().zip(squares) {
+            board[square / 8][square % 8] = *piece;
+        }
+
+        return board;
+    }
+
+    // the squares of the table's pieces on a board with exactly this material
+    fn squares(&self, board: &Board) -> Vec<usize> {
+        return self.pieces.iter()
+            .map(|piece| (0..64).find(|square| board[square / 8][square % 8] == *piece).unwrap_or(64))
+            .collect();
+    }
+
+    fn value(&self, index: usize) -> Option<Dtm> {
+        match self.values[index] {
+            INVALID => None,
+            UNKNOWN => Some(Dtm::Draw),
+            stored => {
+                let plies = stored as u32 - 1;
+                return Some(if plies % 2 == 1 {Dtm::Win(plies)} else {Dtm::Loss(plies)});
+            }
+        }
+    }
+
+    fn probe(&self, board: &Board, turn: Color) -> Option<Dtm> {
+        let squares = self.squares(board);
+        if squares.contains(&64) {
+            return None;
+        }
+        return self.value(self.index(&squares, turn));
+    }
+
+    // positions one quiet move before this one, as indexes
+    fn predecessors(&self, index: usize) -> Vec<usize> {
+        let (squares, turn) = self.decode(index);
+        let mover = opposite(turn);
+        let occupied = |square: usize| squares.contains(&square);
+
+        let mut res = vec![];
+        for (i, piece) in self.pieces.iter().enumerate() {
+            if piece.color != mover {
+                continue;
+            }
+            let square = squares[i];
+            let mut origins = vec![];
+            match piece.piece {
+                King | Knight => {
+                    let steps = if piece.piece == King {KING_STEPS} else {KNIGHT_STEPS};
+                    origins.extend(steps.iter().filter_map(|direction| step(square, *direction)).filter(|origin| !occupied(*origin)));
+                }
+                Queen | Rook | Bishop => {
+                    let mut directions = vec![];
+                    if piece.piece != Bishop {
+                        directions.extend(ROOK_STEPS);
+                    }
+                    if piece.piece != Rook {
+                        directions.extend(BISHOP_STEPS);
+                    }
+                    for direction in directions {
+                        let mut current = square;
+                        while let Some(origin) = step(current, direction) {
+                            if occupied(origin) {
+                                break;
+                            }
+                            origins.push(origin);
+                            current = origin;
+                        }
+                    }
+                }
+                Pawn => {
+                    let back: i32 = if mover == White {-1} else {1};
+                    if let Some(origin) = step(square, (0, back)) {
+                        let start_rank = if mover == White {1} else {6};
+                        if !occupied(origin) && origin / 8 != 0 && origin / 8 != 7 {
+                            origins.push(origin);
+                            // two squares from the start
+                            if origin / 8 == (start_rank - back) as usize {
+                                if let Some(start) = step(origin, (0, back)).filter(|start| !occupied(*start)) {
+                                    origins.push(start);
+                                }
+                            }
+                        }
+                    }
+                }
+                PieceType::Null => {}
+            }
+
+            for origin in origins {
+                let mut before = squares.clone();
+                before[i] = origin;
+                res.push(self.index(&before, mover));
+            }
+        }
+
+        return res;
+    }
+}
+
+// a set of tables, looked up by the material on the board
+pub struct EndgameTables {
+    tables: HashMap<String, DtmTable>,
+}
+
+impl fmt::Debug for EndgameTables {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.tables.keys().collect();
+        names.sort();
+        write!(f, "EndgameTables {:?}", names)
+    }
+}
+
+// sets with the same endings hold the same values
+impl PartialEq for EndgameTables {
+    fn eq(&self, other: &EndgameTables) -> bool {
+        return self.tables.len() == other.tables.len()
+            && self.tables.iter().all(|(name, table)| other.tables.get(name).is_some_and(|other| other.values == table.values));
+    }
+}
+
+impl EndgameTables {
+    pub fn empty() -> EndgameTables {
+        return EndgameTables {tables: HashMap::new()};
+    }
+
+    // every ending in ENDINGS, progress goes to stderr since the bigger ones take a while
+    pub fn generate_all() -> Result<EndgameTables, String> {
+        let mut tables = EndgameTables::empty();
+        for name in ENDINGS {
+            eprintln!("generating {}", name);
+            tables.generate(name)?;
+        }
+
+        return Ok(tables);
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        return names;
+    }
+
+    // the result for the side to move, None when no table has the material or the position
+    // is impossible
+    pub fn probe_board(&self, board: &Board, turn: Color) -> Option<Dtm> {
+        if is_trivial_draw(board) {
+            return Some(Dtm::Draw);
+        }
+        let (white, black) = (material(board, White), material(board, Black));
+        if let Some(table) = self.tables.get(&format!("{}v{}", white, black)) {
+            return table.probe(board, turn);
+        }
+
+        // Black has the stronger side, look it up with the board upside down
+        let table = self.tables.get(&format!("{}v{}", black, white))?;
+        let mut flipped = *board;
+        for rank in 0..8 {
+            for file in 0..8 {
+                let piece = board[7 - rank][file];
+                flipped[rank][file] = Piece {piece: piece.piece, color: match piece.color {
+                    White => Black,
+                    Black => White,
+                    Color::Null => Color::Null,
+                }};
+            }
+        }
+        return table.probe(&flipped, opposite(turn));
+    }
+
+    // castling rights take a position out of the tables, there's no en passant in them to lose
+    pub fn probe(&self, game: &Game) -> Option<Dtm> {
+        if game.castling_rights() != NO_CASTLING {
+            return None;
+        }
+        return self.probe_board(&game.board, game.turn);
+    }
+
+    // the move to play and what it leads to, quickest mate first
+    pub fn best_move(&self, game: &Game) -> Option<(Move, Dtm)> {
+        if game.castling_rights() != NO_CASTLING {
+            return None;
+        }
+        let mut best: Option<(Move, Dtm)> = None;
+        for legal_move in game.legal_moves() {
+            let mut board = game.board;
+            play_move(&mut board, legal_move.clone());
+            let value = self.probe_board(&board, opposite(game.turn))?.before();
+            if best.as_ref().is_none_or(|(_, best_value)| value.is_better_than(best_value)) {
+                best = Some((legal_move, value));
+            }
+        }
+
+        return best;
+    }
+
+    // works out one ending, the endings its captures and promotions lead to have to be
+    // here already
+    pub fn generate(&mut self, name: &str) -> Result<(), String> {
+        let mut table = DtmTable::new(name)?;
+        let size = table.size();
+        // in table moves not yet known to lose, it can go below zero for positions that
+        // mirror onto themselves, a loss is only ever set after checking every move
+        let mut remaining = vec![0i8; size];
+        // positions to set at each ply
+        let mut pending: Vec<Vec<u32>> = vec![vec![]];
+        let push = |pending: &mut Vec<Vec<u32>>, plies: u32, index: usize| {
+            while pending.len() <= plies as usize {
+                pending.push(vec![]);
+            }
+            pending[plies as usize].push(index as u32);
+        };
+
+        for index in 0..size {
+            let (squares, turn) = table.decode(index);
+            let mut seen = [false; 64];
+            let impossible = squares.iter().any(|square| std::mem::replace(&mut seen[*square], true))
+                || table.pieces.iter().zip(squares.iter()).any(|(piece, square)| piece.piece == Pawn && (*square < 8 || *square >= 56));
+            if impossible || table.index(&squares, turn) != index {
+                table.values[index] = INVALID;
+                continue;
+            }
+            let board = table.board(&squares);
+            if is_in_check(&board, opposite(turn)) {
+                table.values[index] = INVALID;
+                continue;
+            }
+
+            let moves = find_all_legal_moves(&board, turn, &NO_CASTLING, &None);
+            if moves.is_empty() {
+                if is_in_check(&board, turn) {
+                    push(&mut pending, 0, index);
+                }
+                continue;
+            }
+
+            // captures and promotions leave the ending, their results are known already
+            let mut best_exit: Option<Dtm> = None;
+            let mut in_table = 0;
+            for legal_move in moves.iter() {
+                if !is_exit(&board, legal_move) {
+                    in_table += 1;
+                    continue;
+                }
+                let mut after = board;
+                play_move(&mut after, legal_move.clone());
+                let value = self.probe_board(&after, opposite(turn))
+                    .ok_or_else(|| format!("{} needs the ending {}v{} first", name, material(&after, White), material(&after, Black)))?
+                    .before();
+                if best_exit.is_none_or(|best| value.is_better_than(&best)) {
+                    best_exit = Some(value);
+                }
+            }
+            remaining[index] = in_table;
+
+            match best_exit {
+                Some(Dtm::Win(plies)) => push(&mut pending, plies, index),
+                Some(Dtm::Loss(plies)) if in_table == 0 => push(&mut pending, plies, index),
+                _ => {}
+            }
+        }
+
+        // going up one ply at a time: positions a move away from a loss are wins, and
+        // positions whose every move is to a win are losses
+        let mut plies = 0;
+        while plies < pending.len() {
+            for index in std::mem::take(&mut pending[plies]) {
+                let index = index as usize;
+                if table.values[index] != UNKNOWN {
+                    continue;
+                }
+                table.values[index] = plies as u8 + 1;
+                if plies as u8 + 1 == INVALID - 1 {
+                    return Err(format!("{} has mates too long to store", name));
+                }
+
+                for previous in table.predecessors(index) {
+                    if table.values[previous] != UNKNOWN {
+                        continue;
+                    }
+                    if plies % 2 == 0 {
+                        push(&mut pending, plies as u32 + 1, previous);
+                        continue;
+                    }
+                    remaining[previous] -= 1;
+                    if remaining[previous] <= 0 {
+                        if let Some(Dtm::Loss(loss)) = self.forced_loss(&table, previous) {
+                            push(&mut pending, loss, previous);
+                        }
+                    }
+                }
+            }
+            plies += 1;
+        }
+
+        self.tables.insert(name.to_string(), table);
+        return Ok(());
+    }
+
+    // the position's result if every move from it is known to lose
+    fn forced_loss(&self, table: &DtmTable, index: usize) -> Option<Dtm> {
+        let (squares, turn) = table.decode(index);
+        let board = table.board(&squares);
+        let mut longest = 0;
+        for legal_move in find_all_legal_moves(&board, turn, &NO_CASTLING, &None) {
+            let mut after = board;
+            play_move(&mut after, legal_move.clone());
+            let value = if is_exit(&board, &legal_move) {
+                self.probe_board(&after, opposite(turn))?
+            } else {
+                table.value(table.index(&table.squares(&after), opposite(turn)))?
+            };
+            match value {
+                Dtm::Win(plies) => longest = longest.max(plies),
+                _ => return None,
+            }
+        }
+
+        return Some(Dtm::Loss(longest + 1));
+    }
+}
+
+fn is_exit(board: &Board, played_move: &Move) -> bool {
+    return board[played_move.to.rank][played_move.to.file].piece != PieceType::Null || played_move.promotion.is_some();
+}
+
+// the file is the magic, then for each table its name, how many values it has and those
+// values run length encoded as a value and a varint count
+impl EndgameTables {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        let names = self.names();
+        bytes.push(names.len() as u8);
+        for name in names {
+            let table = &self.tables[&name];
+            bytes.push(name.len() as u8);
+            bytes.extend(name.as_bytes());
+            bytes.extend((table.values.len() as u32).to_le_bytes());
+
+            let mut runs = vec![];
+            let mut start = 0;
+            while start < table.values.len() {
+                let value = table.values[start];
+                let mut end = start;
+                while end < table.values.len() && table.values[end] == value {
+                    end += 1;
+                }
+                runs.push(value);
+                let mut count = end - start;
+                loop {
+                    let byte = (count & 0x7F) as u8;
+                    count >>= 7;
+                    if count == 0 {
+                        runs.push(byte);
+                        break;
+                    }
+                    runs.push(byte | 0x80);
+                }
+                start = end;
+            }
+            bytes.extend((runs.len() as u32).to_le_bytes());
+            bytes.extend(runs);
+        }
+
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<EndgameTables, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(format!("not an endgame table file"));
+        }
+        let truncated = || format!("endgame table file is truncated");
+        let read_u32 = |at: usize| -> Result<u32, String> {
+            let word = bytes.get(at..at + 4).ok_or_else(truncated)?;
+            return Ok(u32::from_le_bytes(word.try_into().unwrap()));
+        };
+
+        let mut tables = EndgameTables::empty();
+        let mut at = MAGIC.len();
+        let count = *bytes.get(at).ok_or_else(truncated)?;
+        at += 1;
+        for _ in 0..count {
+            let name_len = *bytes.get(at).ok_or_else(truncated)? as usize;
+            let name = String::from_utf8(bytes.get(at + 1..at + 1 + name_len).ok_or_else(truncated)?.to_vec())
+                .map_err(|_| format!("bad ending name in endgame table file"))?;
+            at += 1 + name_len;
+            let mut table = DtmTable::new(&name)?;
+            let size = read_u32(at)? as usize;
+            let runs_len = read_u32(at + 4)? as usize;
+            at += 8;
+            if size != table.size() {
+                return Err(format!("{} has {} values instead of {}", name, size, table.size()));
+            }
+
+            let runs = bytes.get(at..at + runs_len).ok_or_else(truncated)?;
+            at += runs_len;
+            table.values.clear();
+            let mut i = 0;
+            while i < runs.len() {
+                let value = runs[i];
+                i += 1;
+                let mut count = 0;
+                let mut shift = 0;
+                loop {
+                    let byte = *runs.get(i).ok_or_else(truncated)?;
+                    i += 1;
+                    count |= ((byte & 0x7F) as usize) << shift;
+                    shift += 7;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                table.values.extend(std::iter::repeat_n(value, count));
+            }
+            if table.values.len() != size {
+                return Err(format!("{} doesn't decode to {} values", name, size));
+            }
+            tables.tables.insert(name, table);
+        }
+
+        return Ok(tables);
+    }
+
+    pub fn open(path: &Path) -> Result<EndgameTables, String> {
+        let bytes = std::fs::read(path).map_err(|err| format!("couldn't open {}: {}", path.display(), err))?;
+        return EndgameTables::from_bytes(&bytes).map_err(|err| format!("{}: {}", path.display(), err));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        return std::fs::write(path, self.to_bytes()).map_err(|err| format!("couldn't write {}: {}", path.display(), err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+    use std::sync::OnceLock;
+
+    fn probe(tables: &EndgameTables, fen: &str) -> Option<Dtm> {
+        return tables.probe(&Game::from_start(parse_fen(fen).unwrap()));
+    }
+
+    // the queen and rook endings need no other table, worked out once for all the tests
+    fn queen_and_rook() -> &'static EndgameTables {
+        static TABLES: OnceLock<EndgameTables> = OnceLock::new();
+        return TABLES.get_or_init(|| {
+            let mut tables = EndgameTables::empty();
+            for name in [ENDINGS[0], ENDINGS[1]] {
+                tables.generate(name).unwrap();
+            }
+            return tables;
+        });
+    }
+
+    #[test]
+    fn queen_and_rook_endings() {
+        let tables = queen_and_rook();
+        assert_eq!(tables.names(), vec!["KQvK".to_string(), "KRvK".to_string()]);
+
+        assert_eq!(probe(tables, "7k/8/6K1/8/8/8/8/R7 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe(tables, "7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe(tables, "4k3/8/8/8/8/8/8/4K2Q w - - 0 1"), Some(Dtm::Win(13)));
+        assert_eq!(probe(tables, "4k3/8/8/8/8/8/8/4K2Q b - - 0 1"), Some(Dtm::Loss(16)));
+        assert_eq!(probe(tables, "4k3/8/8/8/8/8/8/R3K3 b - - 0 1"), Some(Dtm::Loss(28)));
+
+        // Black with the queen or rook is looked up upside down
+        assert_eq!(probe(tables, "4k2q/8/8/8/8/8/8/4K3 b - - 0 1"), Some(Dtm::Win(13)));
+        assert_eq!(probe(tables, "4k2q/8/8/8/8/8/8/4K3 w - - 0 1"), Some(Dtm::Loss(16)));
+        assert_eq!(probe(tables, "r3k3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(Dtm::Loss(28)));
+
+        // bare kings need no table, other material and castling rights aren't in these
+        assert_eq!(probe(tables, "4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(probe(tables, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), None);
+        assert_eq!(probe(tables, "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), None);
+
+        let (best, dtm) = tables.best_move(&Game::from_start(parse_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap())).unwrap();
+        assert_eq!((move_to_uci(&best), dtm), ("a1a8".to_string(), Dtm::Win(1)));
+    }
+
+    #[test]
+    fn tables_survive_a_round_trip_through_bytes() {
+        let tables = queen_and_rook();
+        let bytes = tables.to_bytes();
+        let read = EndgameTables::from_bytes(&bytes).unwrap();
+        assert_eq!(&read, tables);
+        assert_eq!(probe(&read, "4k3/8/8/8/8/8/8/R3K3 b - - 0 1"), Some(Dtm::Loss(28)));
+
+        assert_eq!(EndgameTables::from_bytes(&bytes[..bytes.len() - 1]), Err(format!("endgame table file is truncated")));
+        assert_eq!(EndgameTables::from_bytes(&bytes[..MAGIC.len() + 3]), Err(format!("endgame table file is truncated")));
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(EndgameTables::from_bytes(&bad_magic), Err(format!("not an endgame table file")));
+    }
+}
@@ -0,0 +1,474 @@
+use crate::piece::PieceType;
+use crate::piece::PieceType::*;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::position::*;
+use crate::legal_moves::*;
+use crate::game::Game;
+use crate::game::play_move;
+use crate::random::Random;
+use crate::syzygy::Tablebase;
+use crate::syzygy::Wdl;
+use crate::endgame::Dtm;
+use crate::endgame::EndgameTables;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+pub const MATE_SCORE: i32 = 100000;
+// a tablebase win, below every mate score so it doesn't read as a mate
+pub const TB_WIN_SCORE: i32 = MATE_SCORE - 2000;
+
+// bonuses by [rank][file] from White's side, mirrored for Black
+const PAWN_TABLE: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [5, 10, 10, -20, -20, 10, 10, 5],
+    [5, -5, -10, 0, 0, -10, -5, 5],
+    [0, 0, 0, 20, 20, 0, 0, 0],
+    [5, 5, 10, 25, 25, 10, 5, 5],
+    [10, 10, 20, 30, 30, 20, 10, 10],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+const KNIGHT_TABLE: [[i32; 8]; 8] = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20, 0, 5, 5, 0, -20, -40],
+    [-30, 5, 10, 15, 15, 10, 5, -30],
+    [-30, 0, 15, 20, 20, 15, 0, -30],
+    [-30, 5, 15, 20, 20, 15, 5, -30],
+    [-30, 0, 10, 15, 15, 10, 0, -30],
+    [-40, -20, 0, 0, 0, 0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+const BISHOP_TABLE: [[i32; 8]; 8] = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10, 5, 0, 0, 0, 0, 5, -10],
+    [-10, 10, 10, 10, 10, 10, 10, -10],
+    [-10, 0, 10, 10, 10, 10, 0, -10],
+    [-10, 5, 5, 10, 10, 5, 5, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+const ROOK_TABLE: [[i32; 8]; 8] = [
+    [0, 0, 0, 5, 5, 0, 0, 0],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [5, 10, 10, 10, 10, 10, 10, 5],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+const KING_TABLE: [[i32; 8]; 8] = [
+    [20, 30, 10, 0, 0, 10, 30, 20],
+    [20, 20, 0, 0, 0, 0, 20, 20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+];
+
+pub fn piece_value(piece: PieceType) -> i32 {
+    match piece {
+        Pawn => 100,
+        Knight => 320,
+        Bishop => 330,
+        Rook => 500,
+        Queen => 900,
+        _ => 0,
+    }
+}
+
+// static evaluation in centipawns, positive is good for White
+pub fn evaluate(board: &Board) -> i32 {
+    let mut score = 0;
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let piece = board[rank][file];
+            if piece.color == Color::Null {
+                continue;
+            }
+
+            // tables are written from White's side
+            let table_rank = if piece.color == White {rank} else {7 - rank};
+            let bonus = match piece.piece {
+                Pawn => PAWN_TABLE[table_rank][file],
+                Knight => KNIGHT_TABLE[table_rank][file],
+                Bishop => BISHOP_TABLE[table_rank][file],
+                Rook => ROOK_TABLE[table_rank][file],
+                Queen => BISHOP_TABLE[table_rank][file] / 2,
+                King => KING_TABLE[table_rank][file],
+                _ => 0,
+            };
+
+            let value = piece_value(piece.piece) + bonus;
+            if piece.color == White {
+                score += value;
+            } else {
+                score -= value;
+            }
+        }
+    }
+
+    return score;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+    // the most centipawns randomly added to or taken from each root move, makes weak levels err
+    pub noise: i32,
+    // endgame tables the search looks positions with few enough pieces up in
+    pub tablebase: Option<Arc<Tablebase>>,
+    // our own distance to mate tables, exact where they have the position
+    pub endgame: Option<Arc<EndgameTables>>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {depth, nodes: None, time: None, noise: 0, tablebase: None, endgame: None}
+    }
+}
+
+pub const MAX_LEVEL: u32 = 5;
+
+// difficulty levels from 1 (makes plenty of mistakes) to MAX_LEVEL
+pub fn limits_for_level(level: u32) -> SearchLimits {
+    match level {
+        0 | 1 => SearchLimits {depth: 1, nodes: Some(500), time: None, noise: 200, tablebase: None, endgame: None},
+        2 => SearchLimits {depth: 2, nodes: Some(5000), time: None, noise: 100, tablebase: None, endgame: None},
+        3 => SearchLimits {depth: 3, nodes: None, time: Some(Duration::from_secs(2)), noise: 40, tablebase: None, endgame: None},
+        4 => SearchLimits {depth: 4, nodes: None, time: Some(Duration::from_secs(5)), noise: 0, tablebase: None, endgame: None},
+        _ => SearchLimits {depth: 6, nodes: None, time: Some(Duration::from_secs(10)), noise: 0, tablebase: None, endgame: None},
+    }
+}
+
+// full moves to mate for a mate score, negative when the side to move gets mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score.abs() <= MATE_SCORE - 1000 {
+        return None;
+    }
+
+    let moves = (MATE_SCORE - score.abs() + 1) / 2;
+    return Some(if score > 0 {moves} else {-moves});
+}
+
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32, // centipawns for the side to move
+    pub depth: u32, // deepest iteration that finished
+    pub nodes: u64,
+}
+
+// a bare position the search can copy around, unlike Game it keeps no history, the
+// solvers use it too
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SearchPosition {
+    pub(crate) board: Board,
+    pub(crate) turn: Color,
+    pub(crate) castling_rights: CastlingRights,
+    pub(crate) en_passant: Option<Square>,
+}
+
+impl SearchPosition {
+    pub(crate) fn from_game(game: &Game) -> SearchPosition {
+        return SearchPosition {
+            board: game.board,
+            turn: game.turn,
+            castling_rights: game.castling_rights(),
+            en_passant: game.en_passant_square(),
+        };
+    }
+
+    pub(crate) fn legal_moves(&self) -> Vec<Move> {
+        return find_all_legal_moves(&self.board, self.turn, &self.castling_rights, &self.en_passant);
+    }
+
+    pub(crate) fn in_check(&self) -> bool {
+        return is_in_check(&self.board, self.turn);
+    }
+
+    pub(crate) fn after(&self, played_move: &Move) -> SearchPosition {
+        let mut next = self.clone();
+        play_move(&mut next.board, played_move.clone());
+        update_castling_rights(&mut next.castling_rights, played_move);
+        next.en_passant = en_passant_target(played_move);
+        next.turn = if self.turn == White {Black} else {White};
+
+        return next;
+    }
+
+    fn is_capture(&self, played_move: &Move) -> bool {
+        return self.board[played_move.to.rank][played_move.to.file].piece != PieceType::Null
+            || played_move.special_move == Some(SpecialMoveType::EnPassant);
+    }
+}
+
+struct Searcher {
+    limits: SearchLimits,
+    started: Instant,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl Searcher {
+    fn out_of_budget(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if let Some(max_nodes) = self.limits.nodes {
+            if self.nodes >= max_nodes {
+                self.aborted = true;
+            }
+        }
+        if let Some(max_time) = self.limits.time {
+            if self.started.elapsed() >= max_time {
+                self.aborted = true;
+            }
+        }
+
+        return self.aborted;
+    }
+}
+
+// iterative deepening search of the game's current position, stops at the first limit reached
+pub fn search(game: &Game, limits: &SearchLimits) -> SearchResult {
+    let root = SearchPosition::from_game(game);
+
+    let mut searcher = Searcher {limits: limits.clone(), started: Instant::now(), nodes: 0, aborted: false};
+    let mut random = Random::from_time();
+
+    let mut moves = root.legal_moves();
+    if moves.is_empty() {
+        return SearchResult {best_move: None, score: terminal_score(&root, 0), depth: 0, nodes: 0};
+    }
+    order_moves(&root, &mut moves);
+
+    if let Some((best_move, dtm)) = limits.endgame.as_ref().and_then(|endgame| endgame.best_move(game)) {
+        return SearchResult {best_move: Some(best_move), score: endgame_score(dtm, 0), depth: 1, nodes: 1};
+    }
+
+    // in the tables the move that keeps the result and gets to the next capture or pawn move
+    // soonest is best, searching can't do better
+    if let Some(tablebase) = &limits.tablebase {
+        if let Some(root_moves) = tablebase.root_moves(game) {
+            let best = &root_moves[0];
+            return SearchResult {best_move: Some(best.played.clone()), score: tablebase_score(best.wdl, 0), depth: 1, nodes: 1};
+        }
+    }
+
+    let mut result = SearchResult {best_move: Some(moves[0].clone()), score: 0, depth: 0, nodes: 0};
+    for depth in 1..=limits.depth.max(1) {
+        let mut best: Option<(Move, i32, i32)> = None; // move, score, score with noise
+        let mut alpha = -MATE_SCORE - 1;
+        let beta = MATE_SCORE + 1;
+
+        for candidate in moves.iter() {
+            // with noise every root move needs an exact score, not just a bound
+            let window_alpha = if limits.noise > 0 {-MATE_SCORE - 1} else {alpha};
+            let score = -negamax(&mut searcher, &root.after(candidate), depth - 1, -beta, -window_alpha, 1);
+            if searcher.aborted {
+                break;
+            }
+
+            let noisy_score = score + random.spread(limits.noise);
+            if best.as_ref().is_none_or(|(_, _, best_noisy)| noisy_score > *best_noisy) {
+                best = Some((candidate.clone(), score, noisy_score));
+            }
+            alpha = alpha.max(score);
+        }
+
+        // a partly searched iteration still counts if it found something better than the last one
+        if let Some((best_move, score, _)) = best {
+            if !searcher.aborted || score > result.score || result.depth == 0 {
+                result.best_move = Some(best_move.clone());
+                result.score = score;
+            }
+            // search the best move first next time
+            if let Some(index) = moves.iter().position(|m| *m == best_move) {
+                let best_move = moves.remove(index);
+                moves.insert(0, best_move);
+            }
+        }
+        if searcher.aborted {
+            break;
+        }
+        result.depth = depth;
+
+        // no point looking deeper once a forced mate is found
+        if result.score.abs() > MATE_SCORE - 1000 {
+            break;
+        }
+    }
+    result.nodes = searcher.nodes;
+
+    return result;
+}
+
+fn terminal_score(position: &SearchPosition, ply: i32) -> i32 {
+    if position.in_check() {
+        // prefer the quickest mate
+        return -MATE_SCORE + ply;
+    }
+
+    return 0;
+}
+
+// mate scores counted from the root, like terminal_score's
+fn endgame_score(dtm: Dtm, ply: i32) -> i32 {
+    match dtm {
+        Dtm::Win(plies) => MATE_SCORE - ply - plies as i32,
+        Dtm::Loss(plies) => -MATE_SCORE + ply + plies as i32,
+        Dtm::Draw => 0,
+    }
+}
+
+// a tablebase result for the side to move, wins further away score lower like mates do
+fn tablebase_score(wdl: Wdl, ply: i32) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN_SCORE - ply,
+        Wdl::Loss => -TB_WIN_SCORE + ply,
+        // won or lost on the board but drawn by the 50 move rule
+        Wdl::CursedWin => 1,
+        Wdl::BlessedLoss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+fn negamax(searcher: &mut Searcher, position: &SearchPosition, depth: u32, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+    searcher.nodes += 1;
+    if searcher.out_of_budget() {
+        return 0;
+    }
+
+    let mut moves = position.legal_moves();
+    if moves.is_empty() {
+        return terminal_score(position, ply);
+    }
+    if let Some(endgame) = &searcher.limits.endgame {
+        if position.castling_rights == NO_CASTLING {
+            if let Some(dtm) = endgame.probe_board(&position.board, position.turn) {
+                return endgame_score(dtm, ply);
+            }
+        }
+    }
+    if let Some(tablebase) = &searcher.limits.tablebase {
+        if let Some(wdl) = tablebase.probe_wdl_board(&position.board, position.turn, &position.castling_rights, &position.en_passant) {
+            return tablebase_score(wdl, ply);
+        }
+    }
+    if depth == 0 {
+        return quiescence(searcher, position, moves, alpha, beta, ply);
+    }
+
+    order_moves(position, &mut moves);
+    for candidate in moves {
+        let score = -negamax(searcher, &position.after(&candidate), depth - 1, -beta, -alpha, ply + 1);
+        if score >= beta {
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+        }
+    }
+
+    return alpha;
+}
+
+// keeps searching captures past the depth limit so exchanges aren't cut off half way
+fn quiescence(searcher: &mut Searcher, position: &SearchPosition, moves: Vec<Move>, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+    let stand_pat = if position.turn == White {evaluate(&position.board)} else {-evaluate(&position.board)};
+    if stand_pat >= beta {
+        return beta;
+    }
+    if stand_pat > alpha {
+        alpha = stand_pat;
+    }
+
+    let mut captures: Vec<Move> = moves.into_iter().filter(|m| position.is_capture(m)).collect();
+    order_moves(position, &mut captures);
+    for capture in captures {
+        searcher.nodes += 1;
+        if searcher.out_of_budget() {
+            return alpha;
+        }
+
+        let next = position.after(&capture);
+        let next_moves = next.legal_moves();
+        let score = if next_moves.is_empty() {
+            -terminal_score(&next, ply + 1)
+        } else {
+            -quiescence(searcher, &next, next_moves, -beta, -alpha, ply + 1)
+        };
+        if score >= beta {
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+        }
+    }
+
+    return alpha;
+}
+
+// most valuable victim first, then least valuable attacker, promotions before quiet moves
+fn order_moves(position: &SearchPosition, moves: &mut [Move]) {
+    moves.sort_by_key(|m| {
+        let victim = if m.special_move == Some(SpecialMoveType::EnPassant) {
+            piece_value(Pawn)
+        } else {
+            piece_value(position.board[m.to.rank][m.to.file].piece)
+        };
+        let promotion = m.promotion.map(piece_value).unwrap_or(0);
+        let mut key = -(promotion + victim * 10);
+        if victim > 0 {
+            key += piece_value(m.piece.piece) / 10;
+        }
+        key
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+    use crate::position::move_to_uci;
+
+    fn best(fen: &str, depth: u32) -> (String, Option<i32>) {
+        let game = Game::from_start(parse_fen(fen).unwrap());
+        let result = search(&game, &SearchLimits::depth(depth));
+        return (move_to_uci(&result.best_move.unwrap()), mate_in(result.score));
+    }
+
+    #[test]
+    fn finds_a_mate_in_one() {
+        assert_eq!(best("7k/8/6K1/8/8/8/8/R7 w - - 0 1", 2), ("a1a8".to_string(), Some(1)));
+        assert_eq!(best("6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1", 3), ("e1e8".to_string(), Some(1)));
+    }
+
+    #[test]
+    fn finds_a_mate_in_two() {
+        // no check mates at once, the king has to close the box first, Kb6 and Kc7 both do
+        let (key, mate) = best("k7/8/2K5/8/8/8/8/7R w - - 0 1", 4);
+        assert!(key == "c6b6" || key == "c6c7", "{}", key);
+        assert_eq!(mate, Some(2));
+    }
+
+    #[test]
+    fn sees_its_own_mate_coming() {
+        let (_, mate) = best("k7/8/1K6/8/8/8/8/7R b - - 0 1", 3);
+        assert_eq!(mate, Some(-1));
+        assert_eq!(mate_in(123), None);
+    }
+}
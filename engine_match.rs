@@ -0,0 +1,326 @@
+use crate::piece::Color::*;
+use crate::game::Game;
+use crate::game::GameResult;
+use crate::clock::Clock;
+use crate::clock::TimeControl;
+use crate::fen::parse_fen;
+use crate::pgn::*;
+use crate::engine::MAX_LEVEL;
+use crate::runner::*;
+use crate::endgame::EndgameTables;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// one side of a match
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchEngine {
+    BuiltIn(u32), // the level
+    Uci(String), // the command that starts the engine
+}
+
+// "engine", "engine:<level>" or "uci:<command>"
+pub fn parse_match_engine(text: &str) -> Result<MatchEngine, String> {
+    if let Some(command) = text.strip_prefix("uci:") {
+        return Ok(MatchEngine::Uci(command.to_string()));
+    }
+    if text == "engine" {
+        return Ok(MatchEngine::BuiltIn(3));
+    }
+    if let Some(level) = text.strip_prefix("engine:") {
+        return match level.parse::<u32>() {
+            Ok(level) if (1..=MAX_LEVEL).contains(&level) => Ok(MatchEngine::BuiltIn(level)),
+            _ => Err(format!("level must be between 1 and {}", MAX_LEVEL)),
+        };
+    }
+
+    return Err(format!("engine must be engine, engine:<level> or uci:<command>, not {}", text));
+}
+
+fn spawn_engine(engine: &MatchEngine, tables: &Option<Arc<EndgameTables>>) -> Result<Box<dyn Player>, String> {
+    match engine {
+        MatchEngine::BuiltIn(level) => {
+            let mut player = EnginePlayer::new(*level);
+            player.endgame = tables.clone();
+            Ok(Box::new(player))
+        }
+        MatchEngine::Uci(command) => Ok(Box::new(UciEnginePlayer::spawn(command)?)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSettings {
+    pub games: u32,
+    pub openings: Option<PathBuf>, // FENs or a PGN file, each opening is played with both colors
+    pub out: PathBuf, // every game is appended to this PGN file
+    pub sprt: Option<(f64, f64)>, // elo0 and elo1, the match stops once the test decides
+    pub adjudication: Option<Adjudication>,
+    // endgame tables for the built-in engine and, with adjudication on, to end games with
+    pub tables: Option<Arc<EndgameTables>>,
+}
+
+impl Default for MatchSettings {
+    fn default() -> MatchSettings {
+        MatchSettings {
+            games: 10,
+            openings: None,
+            out: PathBuf::from("match.pgn"),
+            sprt: None,
+            adjudication: Some(Adjudication::default()),
+            tables: None,
+        }
+    }
+}
+
+// the bounds reported when the match wasn't asked to run a test
+const DEFAULT_SPRT: (f64, f64) = (0.0, 5.0);
+// the chances of accepting a wrong hypothesis either way
+const SPRT_ALPHA: f64 = 0.05;
+const SPRT_BETA: f64 = 0.05;
+
+// the results from the first engine's side
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+fn expected_score(elo: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+}
+
+fn elo_for_score(score: f64) -> f64 {
+    return -400.0 * (1.0 / score - 1.0).log10();
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        return self.wins + self.draws + self.losses;
+    }
+
+    // the average points per game
+    pub fn score(&self) -> f64 {
+        return (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64;
+    }
+
+    // the variance of a single game's points
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        let games = self.games() as f64;
+        return (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2)) / games;
+    }
+
+    // the Elo difference and the half width of its 95% confidence interval, infinite after a
+    // clean sweep either way
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        if self.games() == 0 {
+            return None;
+        }
+        let score = self.score();
+        if score == 0.0 || score == 1.0 {
+            return Some((elo_for_score(score), f64::INFINITY));
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = elo_for_score((score - 1.96 * deviation).max(0.0));
+        let high = elo_for_score((score + 1.96 * deviation).min(1.0));
+
+        return Some((elo_for_score(score), (high - low) / 2.0));
+    }
+
+    // the log likelihood ratio of elo1 against elo0, in the usual normal approximation
+    pub fn sprt_llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        let variance = self.variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let (score0, score1) = (expected_score(elo0), expected_score(elo1));
+
+        return self.games() as f64 * (score1 - score0) * (2.0 * self.score() - score0 - score1) / (2.0 * variance);
+    }
+}
+
+// Some(true) once elo1 is accepted, Some(false) once elo0 is
+pub fn sprt_verdict(llr: f64) -> Option<bool> {
+    if llr >= ((1.0 - SPRT_BETA) / SPRT_ALPHA).ln() {
+        return Some(true);
+    }
+    if llr <= (SPRT_BETA / (1.0 - SPRT_ALPHA)).ln() {
+        return Some(false);
+    }
+
+    return None;
+}
+
+// the positions the games start from, either one FEN a line or the games of a PGN file
+pub fn load_openings(path: &Path) -> Result<Vec<Game>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| format!("couldn't open {}: {}", path.display(), err))?;
+    let lines: Vec<&str> = contents.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    if lines.is_empty() {
+        return Err(format!("no openings in {}", path.display()));
+    }
+
+    if lines.iter().all(|line| parse_fen(line).is_ok()) {
+        return Ok(lines.iter().map(|line| Game::from_start(parse_fen(line).unwrap())).collect());
+    }
+    let mut games = parse_pgn_games(&contents).map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+    for (index, game) in games.iter_mut().enumerate() {
+        // the result of the game the opening came from doesn't matter, only the moves do
+        game.ended = None;
+        if game.result().is_some() {
+            return Err(format!("opening {} in {} is already over", index + 1, path.display()));
+        }
+    }
+
+    return Ok(games);
+}
+
+fn termination_tag(result: &GameResult, adjudicated: bool) -> &'static str {
+    if adjudicated {
+        return "adjudication";
+    }
+    match result {
+        GameResult::Timeout(_) | GameResult::TimeoutVsInsufficientMaterial => "time forfeit",
+        _ => "normal",
+    }
+}
+
+fn format_elo(elo: f64) -> String {
+    if !elo.is_infinite() {
+        return format!("{:.1}", elo);
+    }
+    return format!("{}inf", if elo > 0.0 {"+"} else {"-"});
+}
+
+// plays the match, first and second swap colors every game and play each opening once with
+// each color, the start position is used without openings
+pub fn run_match(first: &MatchEngine, second: &MatchEngine, start: Game, time_control: Option<TimeControl>, settings: &MatchSettings) -> Result<MatchScore, String> {
+    let openings = match &settings.openings {
+        Some(path) => load_openings(path)?,
+        None => vec![start],
+    };
+    let mut out = OpenOptions::new().create(true).append(true).open(&settings.out)
+        .map_err(|err| format!("couldn't open {}: {}", settings.out.display(), err))?;
+
+    let mut score = MatchScore::default();
+    let mut names = (format!("first"), format!("second"));
+    for round in 0..settings.games {
+        let opening = &openings[(round / 2) as usize % openings.len()];
+        let first_is_white = round % 2 == 0;
+        let first_player = spawn_engine(first, &settings.tables)?;
+        let second_player = spawn_engine(second, &settings.tables)?;
+        names = (first_player.name(), second_player.name());
+        let (white, black) = if first_is_white {(first_player, second_player)} else {(second_player, first_player)};
+        let (white_name, black_name) = (white.name(), black.name());
+
+        let mut runner = GameRunner::new(opening.clone(), Clock::new(time_control), white, black);
+        runner.adjudication = settings.adjudication.clone().map(|adjudication| Adjudication {tables: settings.tables.clone(), ..adjudication});
+        let result = runner.run();
+
+        let mut tags = vec![
+            ("Event", format!("rustChess match")),
+            ("Round", (round + 1).to_string()),
+            ("White", white_name.clone()),
+            ("Black", black_name.clone()),
+            ("Termination", termination_tag(&result, runner.adjudicated).to_string()),
+        ];
+        if let Some(time_control) = time_control {
+            tags.push(("TimeControl", format!("{}+{}", time_control.initial.as_secs(), time_control.increment.as_secs())));
+        }
+        writeln!(out, "{}", game_to_pgn_with_tags(&runner.game, &tags))
+            .map_err(|err| format!("couldn't write {}: {}", settings.out.display(), err))?;
+
+        match result.winner() {
+            None => score.draws += 1,
+            Some(winner) if (winner == White) == first_is_white => score.wins += 1,
+            Some(_) => score.losses += 1,
+        }
+        println!("game {}/{}: {} - {} {} ({}{}), +{} ={} -{}",
+            round + 1, settings.games, white_name, black_name, result.pgn_result(), result,
+            if runner.adjudicated {", adjudicated"} else {""}, score.wins, score.draws, score.losses);
+
+        if let Some((elo0, elo1)) = settings.sprt {
+            if sprt_verdict(score.sprt_llr(elo0, elo1)).is_some() {
+                println!("SPRT decided after {} games", score.games());
+                break;
+            }
+        }
+    }
+
+    print_report(&names.0, &names.1, &score, settings.sprt.unwrap_or(DEFAULT_SPRT));
+    return Ok(score);
+}
+
+pub fn print_report(first_name: &str, second_name: &str, score: &MatchScore, sprt: (f64, f64)) {
+    println!();
+    println!("Score of {} vs {}: {} - {} - {} [{:.3}] {}",
+        first_name, second_name, score.wins, score.losses, score.draws,
+        if score.games() > 0 {score.score()} else {0.0}, score.games());
+    match score.elo() {
+        Some((elo, margin)) if elo.is_finite() => println!("Elo difference: {} +/- {}", format_elo(elo), format_elo(margin).trim_start_matches('+')),
+        Some((elo, _)) => println!("Elo difference: {}", format_elo(elo)),
+        None => {}
+    }
+
+    let llr = score.sprt_llr(sprt.0, sprt.1);
+    let verdict = match sprt_verdict(llr) {
+        Some(true) => "H1 accepted",
+        Some(false) => "H0 accepted",
+        None => "inconclusive",
+    };
+    println!("SPRT: llr {:.2} ({:.2}, {:.2}) [{}, {}] {}", llr,
+        (SPRT_BETA / (1.0 - SPRT_ALPHA)).ln(), ((1.0 - SPRT_BETA) / SPRT_ALPHA).ln(),
+        sprt.0, sprt.1, verdict);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixty_percent_is_about_seventy_elo() {
+        let score = MatchScore {wins: 50, draws: 20, losses: 30};
+        let (elo, margin) = score.elo().unwrap();
+        assert!((elo - 70.4).abs() < 0.1);
+        assert!(margin > 0.0 && margin < elo);
+
+        let even = MatchScore {wins: 30, draws: 40, losses: 30};
+        assert!(even.elo().unwrap().0.abs() < 1e-9);
+    }
+
+    #[test]
+    fn llr_of_a_known_score() {
+        // score 0.7 with a per game variance of 0.16 against H0 = 0 and H1 = 10 Elo
+        let score = MatchScore {wins: 60, draws: 20, losses: 20};
+        assert!((score.sprt_llr(0.0, 10.0) - 1.7337).abs() < 1e-4);
+        assert_eq!(sprt_verdict(score.sprt_llr(0.0, 10.0)), None);
+        assert_eq!(sprt_verdict(score.sprt_llr(0.0, 50.0)), Some(true));
+        assert_eq!(sprt_verdict(MatchScore {wins: 20, draws: 20, losses: 60}.sprt_llr(0.0, 50.0)), Some(false));
+    }
+
+    #[test]
+    fn no_games_and_clean_sweeps() {
+        let none = MatchScore::default();
+        assert_eq!(none.elo(), None);
+        assert_eq!(none.sprt_llr(0.0, 10.0), 0.0);
+        assert_eq!(sprt_verdict(none.sprt_llr(0.0, 10.0)), None);
+
+        let sweep = MatchScore {wins: 10, draws: 0, losses: 0};
+        assert_eq!(sweep.elo(), Some((f64::INFINITY, f64::INFINITY)));
+        let swept = MatchScore {wins: 0, draws: 0, losses: 10};
+        assert_eq!(swept.elo(), Some((f64::NEG_INFINITY, f64::INFINITY)));
+        // every game alike leaves no variance to go on
+        assert_eq!(sweep.sprt_llr(0.0, 10.0), 0.0);
+        assert_eq!(sprt_verdict(sweep.sprt_llr(0.0, 10.0)), None);
+    }
+}
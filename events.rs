@@ -0,0 +1,164 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::position::*;
+use crate::game::Game;
+use crate::game::GameResult;
+
+// things that happen in a game, for code that wants to follow one without driving it: loggers,
+// UIs, statistics. Every event comes with the game as it is right after the event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    MovePlayed(Move),
+    Capture(Move, Piece), // the piece taken, for en passant the pawn beside the destination
+    Check(Move),
+    Castling(Move),
+    Promotion(Move, PieceType),
+    DrawOffered(Color),
+    GameEnded(GameResult),
+}
+
+pub trait GameObserver {
+    fn on_event(&mut self, event: &GameEvent, game: &Game);
+}
+
+// plain closures can subscribe too
+impl<F: FnMut(&GameEvent, &Game)> GameObserver for F {
+    fn on_event(&mut self, event: &GameEvent, game: &Game) {
+        self(event, game);
+    }
+}
+
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<Box<dyn GameObserver>>,
+}
+
+impl Observers {
+    pub fn add(&mut self, observer: impl GameObserver + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn notify(&mut self, event: &GameEvent, game: &Game) {
+        for observer in self.observers.iter_mut() {
+            observer.on_event(event, game);
+        }
+    }
+
+    // the events for the last move in the game's history: MovePlayed first, then capture,
+    // castling, promotion and check as they apply
+    pub fn move_played(&mut self, game: &Game) {
+        for event in move_events(game) {
+            self.notify(&event, game);
+        }
+    }
+}
+
+pub fn move_events(game: &Game) -> Vec<GameEvent> {
+    let entry = match game.move_history.last() {
+        Some(entry) => entry,
+        None => return vec![],
+    };
+    let played = &entry.moveEntry;
+    let mut events = vec![GameEvent::MovePlayed(played.clone())];
+
+    if entry.isCaptureMove {
+        // en passant takes the pawn on the mover's starting rank, beside the destination
+        let captured = if played.special_move == Some(SpecialMoveType::EnPassant) {
+            entry.boardState[played.from.rank][played.to.file]
+        } else {
+            entry.boardState[played.to.rank][played.to.file]
+        };
+        events.push(GameEvent::Capture(played.clone(), captured));
+    }
+    if matches!(played.special_move, Some(SpecialMoveType::CastleLong) | Some(SpecialMoveType::CastleShort)) {
+        events.push(GameEvent::Castling(played.clone()));
+    }
+    if let Some(piece) = played.promotion {
+        events.push(GameEvent::Promotion(played.clone(), piece));
+    }
+    if game.is_in_check() {
+        events.push(GameEvent::Check(played.clone()));
+    }
+
+    return events;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceType::*;
+    use crate::piece::Color::*;
+    use crate::fen::parse_fen;
+    use crate::uci::parse_uci_move;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // plays the move and returns it with the events it raised
+    fn play(game: &mut Game, uci_move: &str) -> (Move, Vec<GameEvent>) {
+        let legal_move = parse_uci_move(game, uci_move).unwrap();
+        game.play_move(legal_move.clone());
+        return (legal_move, move_events(game));
+    }
+
+    #[test]
+    fn captures_report_the_piece_taken() {
+        let mut game = Game::new();
+        assert_eq!(move_events(&game), vec![]);
+        let (e4, events) = play(&mut game, "e2e4");
+        assert_eq!(events, vec![GameEvent::MovePlayed(e4)]);
+        play(&mut game, "d7d5");
+
+        let (exd5, events) = play(&mut game, "e4d5");
+        assert_eq!(events, vec![
+            GameEvent::MovePlayed(exd5.clone()),
+            GameEvent::Capture(exd5, Piece {piece: Pawn, color: Black}),
+        ]);
+
+        // the pawn taken en passant stood on c5, not on c6 where the capturing pawn lands
+        play(&mut game, "c7c5");
+        let (dxc6, events) = play(&mut game, "d5c6");
+        assert_eq!(dxc6.special_move, Some(SpecialMoveType::EnPassant));
+        assert_eq!(events, vec![
+            GameEvent::MovePlayed(dxc6.clone()),
+            GameEvent::Capture(dxc6, Piece {piece: Pawn, color: Black}),
+        ]);
+    }
+
+    #[test]
+    fn castling_and_promotion_come_before_check() {
+        let mut game = Game::from_start(parse_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap());
+        let (castle, events) = play(&mut game, "e1g1");
+        assert_eq!(events, vec![
+            GameEvent::MovePlayed(castle.clone()),
+            GameEvent::Castling(castle.clone()),
+            GameEvent::Check(castle),
+        ]);
+
+        let mut game = Game::from_start(parse_fen("3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+        let (promotion, events) = play(&mut game, "e7d8q");
+        assert_eq!(events, vec![
+            GameEvent::MovePlayed(promotion.clone()),
+            GameEvent::Capture(promotion.clone(), Piece {piece: Rook, color: Black}),
+            GameEvent::Promotion(promotion.clone(), Queen),
+            GameEvent::Check(promotion),
+        ]);
+    }
+
+    #[test]
+    fn observers_get_the_events_in_order() {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let mut observers = Observers::default();
+        let log = seen.clone();
+        observers.add(move |event: &GameEvent, _game: &Game| log.borrow_mut().push(event.clone()));
+
+        let mut game = Game::from_start(parse_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap());
+        let (castle, events) = play(&mut game, "e1g1");
+        observers.move_played(&game);
+        observers.notify(&GameEvent::DrawOffered(White), &game);
+        assert_eq!(events.len(), 3);
+        assert_eq!(seen.borrow()[..3], events[..]);
+        assert_eq!(seen.borrow()[3], GameEvent::DrawOffered(White));
+        assert_eq!(seen.borrow()[0], GameEvent::MovePlayed(castle));
+    }
+}
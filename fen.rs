@@ -0,0 +1,202 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::PieceType::*;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::position::*;
+use crate::game::Game;
+use crate::game::StartPosition;
+use crate::validate::validate_position;
+
+pub fn piece_to_fen_char(piece: Piece) -> char {
+    let c = match piece.piece {
+        King => 'k',
+        Queen => 'q',
+        Bishop => 'b',
+        Knight => 'n',
+        Rook => 'r',
+        Pawn => 'p',
+        PieceType::Null => return ' ',
+    };
+
+    if piece.color == White {
+        return c.to_ascii_uppercase();
+    }
+    return c;
+}
+
+pub fn fen_char_to_piece(c: char) -> Result<Piece, ()> {
+    let piece = match c.to_ascii_lowercase() {
+        'k' => King,
+        'q' => Queen,
+        'b' => Bishop,
+        'n' => Knight,
+        'r' => Rook,
+        'p' => Pawn,
+        _ => return Err(()),
+    };
+    let color = if c.is_ascii_uppercase() {White} else {Black};
+
+    Ok(Piece {piece, color})
+}
+
+pub fn parse_fen(fen: &str) -> Result<StartPosition, String> {
+    let start = parse_fen_unchecked(fen)?;
+    validate_position(&start)?;
+
+    Ok(start)
+}
+
+// the fields of a FEN read as they are, without asking whether the position could come up in
+// a game, for the position editor which only validates when it's done
+pub fn parse_fen_unchecked(fen: &str) -> Result<StartPosition, String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 || fields.len() > 6 {
+        return Err(format!("a FEN needs 4 to 6 fields, got {}", fields.len()));
+    }
+
+    let board = parse_board_fen(fields[0])?;
+
+    let turn = match fields[1] {
+        "w" => White,
+        "b" => Black,
+        other => return Err(format!("side to move must be w or b, got '{}'", other)),
+    };
+
+    let castling_rights = parse_castling(fields[2])?;
+
+    let en_passant = if fields[3] == "-" {
+        None
+    } else {
+        Some(parse_square(fields[3]).map_err(|_| format!("bad en passant square '{}'", fields[3]))?)
+    };
+
+    let halfmove_clock = match fields.get(4) {
+        Some(v) => v.parse::<u32>().map_err(|_| format!("bad halfmove clock '{}'", v))?,
+        None => 0,
+    };
+    let fullmove_number = match fields.get(5) {
+        Some(v) => v.parse::<u32>().map_err(|_| format!("bad fullmove number '{}'", v))?.max(1),
+        None => 1,
+    };
+
+    let start = StartPosition {
+        board,
+        turn,
+        castling_rights,
+        en_passant,
+        halfmove_clock,
+        fullmove_number,
+    };
+
+    Ok(start)
+}
+
+// "KQkq", "Kq" or "-" as in a FEN
+pub fn parse_castling(text: &str) -> Result<CastlingRights, String> {
+    let mut castling_rights = NO_CASTLING;
+    if text == "-" {
+        return Ok(castling_rights);
+    }
+    for c in text.chars() {
+        match c {
+            'K' => castling_rights.white_short = true,
+            'Q' => castling_rights.white_long = true,
+            'k' => castling_rights.black_short = true,
+            'q' => castling_rights.black_long = true,
+            _ => return Err(format!("unknown castling right '{}'", c)),
+        }
+    }
+    return Ok(castling_rights);
+}
+
+// the piece placement field of a FEN, eg "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
+pub fn parse_board_fen(placement: &str) -> Result<Board, String> {
+    let empty_piece = Piece {piece: PieceType::Null, color: Color::Null};
+    let mut board: Board = [[empty_piece; 8]; 8];
+
+    let rows: Vec<&str> = placement.split('/').collect();
+    if rows.len() != 8 {
+        return Err(format!("the board needs 8 ranks, got {}", rows.len()));
+    }
+    // FEN lists the ranks from the 8th down to the 1st
+    for (i, row) in rows.iter().enumerate() {
+        let rank = 7 - i;
+        let mut file = 0;
+        for c in row.chars() {
+            if let Some(empty_squares) = c.to_digit(10) {
+                file += empty_squares as usize;
+            } else {
+                let piece = fen_char_to_piece(c).map_err(|_| format!("unknown piece '{}'", c))?;
+                if file >= 8 {
+                    return Err(format!("rank {} has more than 8 squares", rank + 1));
+                }
+                board[rank][file] = piece;
+                file += 1;
+            }
+        }
+        if file != 8 {
+            return Err(format!("rank {} doesn't have 8 squares", rank + 1));
+        }
+    }
+
+    return Ok(board);
+}
+
+pub fn board_to_fen(board: &Board) -> String {
+    let mut res = String::new();
+
+    for rank in (0..8).rev() {
+        let mut empty_squares = 0;
+        for file in 0..8 {
+            let piece = board[rank][file];
+            if piece.piece == PieceType::Null {
+                empty_squares += 1;
+                continue;
+            }
+            if empty_squares > 0 {
+                res.push_str(&empty_squares.to_string());
+                empty_squares = 0;
+            }
+            res.push(piece_to_fen_char(piece));
+        }
+        if empty_squares > 0 {
+            res.push_str(&empty_squares.to_string());
+        }
+        if rank > 0 {
+            res.push('/');
+        }
+    }
+
+    return res;
+}
+
+pub fn castling_rights_to_fen(castling_rights: &CastlingRights) -> String {
+    let mut res = String::new();
+    if castling_rights.white_short { res.push('K'); }
+    if castling_rights.white_long { res.push('Q'); }
+    if castling_rights.black_short { res.push('k'); }
+    if castling_rights.black_long { res.push('q'); }
+    if res.is_empty() {
+        res.push('-');
+    }
+
+    return res;
+}
+
+pub fn game_to_fen(game: &Game) -> String {
+    let en_passant = match game.en_passant_square() {
+        Some(square) => square_name(&square),
+        None => "-".to_string(),
+    };
+
+    return format!("{} {} {} {} {} {}",
+        board_to_fen(&game.board),
+        if game.turn == White {"w"} else {"b"},
+        castling_rights_to_fen(&game.castling_rights()),
+        en_passant,
+        game.halfmove_clock(),
+        game.fullmove_number(),
+    );
+}
@@ -0,0 +1,1095 @@
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::game::Game;
+use crate::game::GameResult;
+use crate::game::evaluate_timeout;
+use crate::pgn::*;
+use crate::clock::Clock;
+use crate::clock::TimeControl;
+use crate::render::*;
+use crate::uci::parse_uci_move;
+use crate::random::Random;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+// A server in the style of FICS for playing over telnet (or nc). People log in with a handle,
+// find opponents with seek/play or match/accept and play rated or unrated games with a clock,
+// others can watch and everyone can talk. One thread owns all the state, the connections only
+// hand it their lines. Ratings are Elo, kept in a text file with one
+// "handle rating wins losses draws" line per player.
+
+const INITIAL_RATING: i32 = 1500;
+const RATING_K: f64 = 32.0;
+const MAX_HANDLE_LENGTH: usize = 17;
+const DEFAULT_TERMS: Terms = Terms {
+    time_control: TimeControl {initial: Duration::from_secs(5 * 60), increment: Duration::ZERO},
+    rated: true,
+    color: None,
+};
+// how often the clocks are looked at when nobody says anything
+const TICK: Duration = Duration::from_millis(100);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const PROMPT: &str = "fics% ";
+
+const FICS_HELP: &str = "\
+finding a game
+  seek [min [inc]] [rated|unrated] [white|black]   offer a game to anybody, 5 0 rated by default
+  sought                  the open seeks
+  play <n>                take seek n
+  unseek                  take your seeks back
+  match <handle> [min [inc]] [rated|unrated] [white|black]
+                          challenge somebody
+  accept [handle]         accept a challenge
+  decline [handle]        turn a challenge down
+playing
+  e4, Nf3, e2e4 ...       moves, in SAN or coordinates
+  draw                    offer a draw, or accept your opponent's offer
+  resign                  give up
+  refresh                 show the board again
+watching
+  games                   the games being played
+  observe <n>             watch game n
+  unobserve [n]           stop watching
+talking
+  say <text>              to your opponent
+  tell <handle> <text>    to one person
+  kibitz <text>           to the players and watchers of your game
+  shout <text>            to everybody
+other
+  who                     who is logged in
+  finger [handle]         rating and record
+  quit                    leave";
+
+enum Event {
+    Connected(usize, TcpStream),
+    Line(usize, String),
+    Disconnected(usize),
+}
+
+// what a seek or a challenge offers, the color is the one the offering player wants
+#[derive(Debug, Clone, Copy)]
+struct Terms {
+    time_control: TimeControl,
+    rated: bool,
+    color: Option<Color>,
+}
+
+struct Seek {
+    number: usize,
+    by: usize,
+    terms: Terms,
+}
+
+struct Challenge {
+    from: usize,
+    to: usize,
+    terms: Terms,
+}
+
+// a connection, with a handle once it has logged in
+struct User {
+    stream: TcpStream,
+    handle: Option<String>,
+    game: Option<usize>,
+    observing: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    rating: i32,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+struct OnlineGame {
+    players: [usize; 2], // White, Black
+    handles: [String; 2],
+    game: Game,
+    clock: Clock,
+    rated: bool,
+    observers: Vec<usize>,
+    draw_offer: Option<Color>,
+}
+
+struct Server {
+    users: HashMap<usize, User>,
+    records: HashMap<String, Record>, // by lower case handle
+    ratings_path: PathBuf,
+    seeks: Vec<Seek>,
+    next_seek: usize,
+    challenges: Vec<Challenge>,
+    games: BTreeMap<usize, OnlineGame>,
+    next_game: usize,
+    random: Random,
+    outbox: HashMap<usize, String>, // sent with a prompt once the current event is handled
+    closing: Vec<usize>,
+}
+
+// ~/.rustchess-ratings
+pub fn ratings_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    return PathBuf::from(home).join(".rustchess-ratings");
+}
+
+pub fn run_fics(port: u16, ratings_path: PathBuf) -> Result<(), String> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|err| format!("couldn't listen on port {}: {}", port, err))?;
+    let port = listener.local_addr().map_err(|err| err.to_string())?.port();
+    let records = load_records(&ratings_path)?;
+    println!("chess server running, connect with: telnet localhost {}", port);
+
+    let (sender, events) = channel();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let reader = match stream.try_clone() {
+                Ok(reader) => reader,
+                Err(_) => continue,
+            };
+            let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+            if sender.send(Event::Connected(id, stream)).is_err() {
+                return;
+            }
+            spawn_line_reader(id, reader, sender.clone());
+        }
+    });
+
+    let mut server = Server::new(records, ratings_path);
+
+    loop {
+        match events.recv_timeout(TICK) {
+            Ok(Event::Connected(id, stream)) => server.connected(id, stream),
+            Ok(Event::Line(id, line)) => server.line(id, &line),
+            Ok(Event::Disconnected(id)) => server.disconnected(id),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Err(format!("stopped accepting connections")),
+        }
+        server.check_flags();
+        server.flush();
+    }
+}
+
+fn spawn_line_reader(id: usize, stream: TcpStream, events: Sender<Event>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut bytes = vec![];
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            // telnet clients may mix in option negotiation, only the text is kept
+            let line: String = String::from_utf8_lossy(&bytes).chars()
+                .filter(|c| !c.is_control() && *c != char::REPLACEMENT_CHARACTER)
+                .collect();
+            if events.send(Event::Line(id, line.trim().to_string())).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(Event::Disconnected(id));
+    });
+}
+
+fn load_records(path: &PathBuf) -> Result<HashMap<String, Record>, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(format!("couldn't read {}: {}", path.display(), err)),
+    };
+
+    let mut records = HashMap::new();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let numbers: Vec<i64> = fields.iter().skip(1).filter_map(|field| field.parse().ok()).collect();
+        if fields.len() != 5 || numbers.len() != 4 {
+            return Err(format!("{}: bad ratings line \"{}\"", path.display(), line));
+        }
+        records.insert(fields[0].to_lowercase(), Record {
+            rating: numbers[0] as i32,
+            wins: numbers[1] as u32,
+            losses: numbers[2] as u32,
+            draws: numbers[3] as u32,
+        });
+    }
+
+    return Ok(records);
+}
+
+fn save_records(path: &PathBuf, records: &HashMap<String, Record>) -> Result<(), String> {
+    let mut handles: Vec<&String> = records.keys().collect();
+    handles.sort();
+    let mut contents = String::new();
+    for handle in handles {
+        let record = records[handle];
+        contents.push_str(&format!("{} {} {} {} {}\n", handle, record.rating, record.wins, record.losses, record.draws));
+    }
+
+    // written next to the old file first so a crash never leaves half a file behind
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, contents).map_err(|err| err.to_string())?;
+    return std::fs::rename(&temporary, path).map_err(|err| err.to_string());
+}
+
+fn opposite(color: Color) -> Color {
+    if color == White {Black} else {White}
+}
+
+fn color_index(color: Color) -> usize {
+    if color == White {0} else {1}
+}
+
+fn color_name(color: Color) -> &'static str {
+    if color == White {"white"} else {"black"}
+}
+
+fn is_valid_handle(handle: &str) -> bool {
+    return (2..=MAX_HANDLE_LENGTH).contains(&handle.len()) && handle.chars().all(|c| c.is_ascii_alphanumeric());
+}
+
+// "5 0 rated", "2.5 12 unrated white"
+fn terms_text(terms: &Terms) -> String {
+    let mut text = format!("{} {} {}",
+        terms.time_control.initial.as_secs_f64() / 60.0,
+        terms.time_control.increment.as_secs(),
+        if terms.rated {"rated"} else {"unrated"});
+    if let Some(color) = terms.color {
+        text.push(' ');
+        text.push_str(color_name(color));
+    }
+
+    return text;
+}
+
+// minutes and increment as the first two numbers, defaults for whatever isn't given
+fn parse_terms(words: &[&str]) -> Result<Terms, String> {
+    let mut terms = DEFAULT_TERMS;
+    let mut numbers = 0;
+    for word in words {
+        match word.to_lowercase().as_str() {
+            "rated" | "r" => terms.rated = true,
+            "unrated" | "u" => terms.rated = false,
+            "white" | "w" => terms.color = Some(White),
+            "black" | "b" => terms.color = Some(Black),
+            number => {
+                let invalid = || format!("I don't understand \"{}\", try help", word);
+                match numbers {
+                    0 => {
+                        let minutes: f64 = number.parse().map_err(|_| invalid())?;
+                        if !(minutes > 0.0 && minutes <= 600.0) {
+                            return Err(format!("the time should be between 0 and 600 minutes"));
+                        }
+                        terms.time_control.initial = Duration::from_secs_f64(minutes * 60.0);
+                    }
+                    1 => {
+                        let increment: u64 = number.parse().map_err(|_| invalid())?;
+                        terms.time_control.increment = Duration::from_secs(increment.min(600));
+                    }
+                    _ => return Err(invalid()),
+                }
+                numbers += 1;
+            }
+        }
+    }
+
+    return Ok(terms);
+}
+
+// the new rating after scoring `score` (1, 0.5 or 0) against `opponent`
+fn elo(rating: i32, opponent: i32, score: f64) -> i32 {
+    let expected = 1.0 / (1.0 + 10f64.powf((opponent - rating) as f64 / 400.0));
+    return rating + (RATING_K * (score - expected)).round() as i32;
+}
+
+impl Server {
+    fn new(records: HashMap<String, Record>, ratings_path: PathBuf) -> Server {
+        return Server {
+            users: HashMap::new(),
+            records,
+            ratings_path,
+            seeks: vec![],
+            next_seek: 1,
+            challenges: vec![],
+            games: BTreeMap::new(),
+            next_game: 1,
+            random: Random::from_time(),
+            outbox: HashMap::new(),
+            closing: vec![],
+        };
+    }
+
+    fn send(&mut self, id: usize, text: &str) {
+        let out = self.outbox.entry(id).or_default();
+        out.push_str(text);
+        out.push('\n');
+    }
+
+    fn flush(&mut self) {
+        for (id, text) in std::mem::take(&mut self.outbox) {
+            if let Some(user) = self.users.get(&id) {
+                let mut text = text.replace('\n', "\r\n");
+                if user.handle.is_some() && !self.closing.contains(&id) {
+                    text.push_str(PROMPT);
+                }
+                // a failed write shows up as a disconnect on the reading side
+                let _ = (&user.stream).write_all(text.as_bytes());
+            }
+        }
+        for id in std::mem::take(&mut self.closing) {
+            if let Some(user) = self.users.get(&id) {
+                let _ = user.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    fn handle(&self, id: usize) -> String {
+        return self.users.get(&id).and_then(|user| user.handle.clone()).unwrap_or_default();
+    }
+
+    fn find_user(&self, handle: &str) -> Option<usize> {
+        return self.users.iter()
+            .find(|(_, user)| user.handle.as_ref().is_some_and(|name| name.eq_ignore_ascii_case(handle)))
+            .map(|(id, _)| *id);
+    }
+
+    fn record(&self, handle: &str) -> Record {
+        return self.records.get(&handle.to_lowercase()).copied()
+            .unwrap_or(Record {rating: INITIAL_RATING, wins: 0, losses: 0, draws: 0});
+    }
+
+    fn rated_name(&self, id: usize) -> String {
+        let handle = self.handle(id);
+        return format!("{} ({})", handle, self.record(&handle).rating);
+    }
+
+    fn logged_in(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.users.iter().filter(|(_, user)| user.handle.is_some()).map(|(id, _)| *id).collect();
+        ids.sort();
+        return ids;
+    }
+
+    fn connected(&mut self, id: usize, stream: TcpStream) {
+        self.users.insert(id, User {stream, handle: None, game: None, observing: vec![]});
+        self.send(id, "Welcome to the rustChess server.\n\nPick any handle of letters and digits to log in.\n");
+        self.outbox.entry(id).or_default().push_str("login: ");
+    }
+
+    fn disconnected(&mut self, id: usize) {
+        let user = match self.users.remove(&id) {
+            Some(user) => user,
+            None => return,
+        };
+        let handle = match user.handle {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        if let Some(number) = user.game {
+            let color = if self.games[&number].players[0] == id {White} else {Black};
+            self.games.get_mut(&number).unwrap().game.resign(color);
+            self.end_game(number, &format!("{} forfeits by disconnection", handle));
+        }
+        for online in self.games.values_mut() {
+            online.observers.retain(|observer| *observer != id);
+        }
+        self.seeks.retain(|seek| seek.by != id);
+        self.challenges.retain(|challenge| challenge.from != id && challenge.to != id);
+        for other in self.logged_in() {
+            self.send(other, &format!("Notification: {} has departed.", handle));
+        }
+    }
+
+    fn line(&mut self, id: usize, line: &str) {
+        let logged_in = match self.users.get(&id) {
+            Some(user) => user.handle.is_some(),
+            None => return,
+        };
+        if !logged_in {
+            self.login(id, line);
+            return;
+        }
+        if line.is_empty() {
+            self.outbox.entry(id).or_default();
+            return;
+        }
+
+        let (command, rest) = line.split_once(' ').map(|(command, rest)| (command, rest.trim())).unwrap_or((line, ""));
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        match command.to_lowercase().as_str() {
+            "help" | "?" => self.send(id, FICS_HELP),
+            "who" => self.who(id),
+            "finger" => self.finger(id, words.first().copied()),
+            "seek" => self.seek(id, &words),
+            "sought" => self.sought(id),
+            "unseek" => {
+                self.seeks.retain(|seek| seek.by != id);
+                self.send(id, "Your seeks have been removed.");
+            }
+            "play" => self.play(id, words.first().copied()),
+            "match" => self.challenge(id, &words),
+            "accept" => self.accept(id, words.first().copied()),
+            "decline" => self.decline(id, words.first().copied()),
+            "draw" => self.draw(id),
+            "resign" => self.resign(id),
+            "refresh" | "board" => self.refresh(id),
+            "games" => self.list_games(id),
+            "observe" => self.observe(id, words.first().copied()),
+            "unobserve" => self.unobserve(id, words.first().copied()),
+            "say" => self.say(id, rest),
+            "tell" => self.tell(id, rest),
+            "kibitz" => self.kibitz(id, rest),
+            "shout" => self.shout(id, rest),
+            "quit" | "exit" | "logout" => {
+                self.send(id, "Thank you for playing.");
+                self.closing.push(id);
+            }
+            _ => {
+                if self.users[&id].game.is_some() {
+                    self.move_line(id, line);
+                } else {
+                    self.send(id, &format!("{}: Command not found, try help.", command));
+                }
+            }
+        }
+    }
+
+    fn login(&mut self, id: usize, handle: &str) {
+        if !is_valid_handle(handle) {
+            if !handle.is_empty() {
+                self.send(id, &format!("Sorry, handles are 2 to {} letters and digits.", MAX_HANDLE_LENGTH));
+            }
+            self.outbox.entry(id).or_default().push_str("login: ");
+            return;
+        }
+        if self.find_user(handle).is_some() {
+            self.send(id, &format!("{} is already logged in.", handle));
+            self.outbox.entry(id).or_default().push_str("login: ");
+            return;
+        }
+
+        for other in self.logged_in() {
+            self.send(other, &format!("Notification: {} has arrived.", handle));
+        }
+        self.users.get_mut(&id).unwrap().handle = Some(handle.to_string());
+        let record = self.record(handle);
+        self.send(id, &format!("**** Starting session as {} ({}) ****\nType help for the commands.", handle, record.rating));
+    }
+
+    fn who(&mut self, id: usize) {
+        let mut lines = vec![];
+        for other in self.logged_in() {
+            let user = &self.users[&other];
+            let status = if let Some(number) = user.game {
+                format!("playing game {}", number)
+            } else if self.seeks.iter().any(|seek| seek.by == other) {
+                format!("seeking")
+            } else if let Some(number) = user.observing.first() {
+                format!("observing game {}", number)
+            } else {
+                format!("available")
+            };
+            lines.push(format!("{:>5} {:<18}{}", self.record(user.handle.as_ref().unwrap()).rating, user.handle.as_ref().unwrap(), status));
+        }
+        lines.push(format!("\n{} players logged in.", lines.len()));
+        self.send(id, &lines.join("\n"));
+    }
+
+    fn finger(&mut self, id: usize, handle: Option<&str>) {
+        let handle = match handle {
+            Some(handle) => match self.find_user(handle) {
+                Some(other) => self.handle(other),
+                None if self.records.contains_key(&handle.to_lowercase()) => handle.to_lowercase(),
+                None => return self.send(id, &format!("There is no player called {}.", handle)),
+            },
+            None => self.handle(id),
+        };
+        let record = self.record(&handle);
+        let online = if self.find_user(&handle).is_some() {"on line"} else {"off line"};
+        self.send(id, &format!("Finger of {} ({}):\n  rating {}, {} wins, {} losses, {} draws in rated games",
+            handle, online, record.rating, record.wins, record.losses, record.draws));
+    }
+
+    fn seek(&mut self, id: usize, words: &[&str]) {
+        if self.users[&id].game.is_some() {
+            return self.send(id, "You can't seek while you are playing.");
+        }
+        let terms = match parse_terms(words) {
+            Ok(terms) => terms,
+            Err(err) => return self.send(id, &err),
+        };
+
+        let number = self.next_seek;
+        self.next_seek += 1;
+        self.seeks.push(Seek {number, by: id, terms});
+        self.send(id, &format!("Your seek has been posted with index {}.", number));
+        let announcement = format!("{} seeking {} (\"play {}\" to respond)", self.rated_name(id), terms_text(&terms), number);
+        for other in self.logged_in() {
+            if other != id && self.users[&other].game.is_none() {
+                self.send(other, &announcement);
+            }
+        }
+    }
+
+    fn sought(&mut self, id: usize) {
+        let mut lines = vec![];
+        for seek in self.seeks.iter() {
+            lines.push(format!("{:>3} {} {}", seek.number, self.rated_name(seek.by), terms_text(&seek.terms)));
+        }
+        lines.push(format!("{} ads displayed.", self.seeks.len()));
+        self.send(id, &lines.join("\n"));
+    }
+
+    fn play(&mut self, id: usize, number: Option<&str>) {
+        let seek = match number.and_then(|number| number.parse::<usize>().ok()) {
+            Some(number) => self.seeks.iter().position(|seek| seek.number == number),
+            None => return self.send(id, "Usage: play <seek number>"),
+        };
+        let seek = match seek {
+            Some(seek) => seek,
+            None => return self.send(id, "That seek is not available."),
+        };
+        if self.seeks[seek].by == id {
+            return self.send(id, "You can't play your own seek.");
+        }
+        if self.users[&id].game.is_some() {
+            return self.send(id, "You are already playing a game.");
+        }
+        let seek = self.seeks.remove(seek);
+        self.start_game(seek.by, id, seek.terms);
+    }
+
+    fn challenge(&mut self, id: usize, words: &[&str]) {
+        let opponent = match words.first() {
+            Some(handle) => self.find_user(handle),
+            None => return self.send(id, "Usage: match <handle> [minutes [increment]] [rated|unrated] [white|black]"),
+        };
+        let opponent = match opponent {
+            Some(opponent) if opponent == id => return self.send(id, "You can't match yourself."),
+            Some(opponent) => opponent,
+            None => return self.send(id, &format!("{} is not logged in.", words[0])),
+        };
+        if self.users[&id].game.is_some() {
+            return self.send(id, "You are already playing a game.");
+        }
+        if self.users[&opponent].game.is_some() {
+            return self.send(id, &format!("{} is playing a game.", self.handle(opponent)));
+        }
+        let terms = match parse_terms(&words[1..]) {
+            Ok(terms) => terms,
+            Err(err) => return self.send(id, &err),
+        };
+
+        self.challenges.retain(|challenge| !(challenge.from == id && challenge.to == opponent));
+        self.challenges.push(Challenge {from: id, to: opponent, terms});
+        let description = format!("{} {} {}", self.rated_name(id), self.rated_name(opponent), terms_text(&terms));
+        self.send(id, &format!("Issuing: {}.", description));
+        self.send(opponent, &format!("Challenge: {}.\nYou can \"accept\" or \"decline\" it.", description));
+    }
+
+    // the challenge to `id`, from `handle` if given, or the only one there is
+    fn find_challenge(&mut self, id: usize, handle: Option<&str>) -> Option<usize> {
+        let offers: Vec<usize> = (0..self.challenges.len())
+            .filter(|index| self.challenges[*index].to == id)
+            .filter(|index| handle.is_none_or(|handle| self.handle(self.challenges[*index].from).eq_ignore_ascii_case(handle)))
+            .collect();
+        match offers.len() {
+            0 => {
+                self.send(id, "There are no offers for you to respond to.");
+                return None;
+            }
+            1 => return Some(offers[0]),
+            _ => {
+                self.send(id, "You have more than one offer, say whose with the handle.");
+                return None;
+            }
+        }
+    }
+
+    fn accept(&mut self, id: usize, handle: Option<&str>) {
+        if let Some(index) = self.find_challenge(id, handle) {
+            if self.users[&id].game.is_some() {
+                return self.send(id, "You are already playing a game.");
+            }
+            let challenge = self.challenges.remove(index);
+            self.start_game(challenge.from, id, challenge.terms);
+        }
+    }
+
+    fn decline(&mut self, id: usize, handle: Option<&str>) {
+        if let Some(index) = self.find_challenge(id, handle) {
+            let challenge = self.challenges.remove(index);
+            let handle = self.handle(id);
+            self.send(challenge.from, &format!("{} declines your challenge.", handle));
+            self.send(id, "You decline the challenge.");
+        }
+    }
+
+    fn start_game(&mut self, offered_by: usize, accepted_by: usize, terms: Terms) {
+        let offered_color = terms.color.unwrap_or(if self.random.below(2) == 0 {White} else {Black});
+        let players = if offered_color == White {[offered_by, accepted_by]} else {[accepted_by, offered_by]};
+        self.seeks.retain(|seek| !players.contains(&seek.by));
+        self.challenges.retain(|challenge| !players.contains(&challenge.from) && !players.contains(&challenge.to));
+
+        let number = self.next_game;
+        self.next_game += 1;
+        let description = format!("{} vs. {}, {}", self.rated_name(players[0]), self.rated_name(players[1]), terms_text(&Terms {color: None, ..terms}));
+        // White's time runs from the start, nobody can stall a game by never moving
+        let mut clock = Clock::new(Some(terms.time_control));
+        clock.start(White);
+        self.games.insert(number, OnlineGame {
+            players,
+            handles: [self.handle(players[0]), self.handle(players[1])],
+            game: Game::new(),
+            clock,
+            rated: terms.rated,
+            observers: vec![],
+            draw_offer: None,
+        });
+        for player in players {
+            self.users.get_mut(&player).unwrap().game = Some(number);
+            self.send(player, &format!("{{Game {}: {}}} Creating", number, description));
+            self.show_board(number, player);
+        }
+    }
+
+    // everyone following a game, players first
+    fn audience(&self, number: usize) -> Vec<usize> {
+        let online = &self.games[&number];
+        let mut ids = online.players.to_vec();
+        ids.extend(online.observers.iter());
+        return ids;
+    }
+
+    fn show_board(&mut self, number: usize, viewer: usize) {
+        let online = &self.games[&number];
+        let options = RenderOptions {
+            perspective: if online.players[1] == viewer {Perspective::Black} else {Perspective::White},
+            ascii: true,
+            ..RenderOptions::default()
+        };
+        let last_move = match history_to_san(&online.game).last() {
+            Some(san) => format!(", last move {}", san),
+            None => String::new(),
+        };
+        let text = format!("\nGame {}: {} ({}) vs. {} ({})\n{}\n{} to move{}{}",
+            number,
+            online.handles[0], online.clock.display(White),
+            online.handles[1], online.clock.display(Black),
+            render_board(&online.game, &options, None, None),
+            if online.game.turn == White {"White"} else {"Black"},
+            last_move,
+            if online.game.is_in_check() {", check"} else {""});
+        self.send(viewer, &text);
+    }
+
+    fn show_board_to_all(&mut self, number: usize) {
+        for viewer in self.audience(number) {
+            self.show_board(number, viewer);
+        }
+    }
+
+    fn playing(&mut self, id: usize) -> Option<(usize, Color)> {
+        let number = match self.users[&id].game {
+            Some(number) => number,
+            None => {
+                self.send(id, "You are not playing a game.");
+                return None;
+            }
+        };
+        let color = if self.games[&number].players[0] == id {White} else {Black};
+
+        return Some((number, color));
+    }
+
+    fn move_line(&mut self, id: usize, text: &str) {
+        let (number, color) = match self.playing(id) {
+            Some(playing) => playing,
+            None => return,
+        };
+        let online = self.games.get_mut(&number).unwrap();
+        if online.game.turn != color {
+            return self.send(id, "It is not your move.");
+        }
+        let legal_move = match parse_uci_move(&online.game, text).or_else(|_| parse_san(&online.game, text)) {
+            Ok(legal_move) => legal_move,
+            Err(_) => return self.send(id, &format!("Illegal move ({}).", text)),
+        };
+
+        online.game.play_move(legal_move);
+        online.clock.press(color);
+        online.draw_offer = None;
+        self.show_board_to_all(number);
+        if let Some(result) = self.games[&number].game.result() {
+            let description = result_description(&result, &self.games[&number].handles);
+            self.end_game(number, &description);
+        }
+    }
+
+    fn draw(&mut self, id: usize) {
+        let (number, color) = match self.playing(id) {
+            Some(playing) => playing,
+            None => return,
+        };
+        let online = self.games.get_mut(&number).unwrap();
+        if online.draw_offer == Some(opposite(color)) {
+            online.game.agree_draw();
+            self.end_game(number, "Game drawn by mutual agreement");
+            return;
+        }
+
+        online.draw_offer = Some(color);
+        let opponent = online.players[color_index(opposite(color))];
+        let handle = self.handle(id);
+        self.send(opponent, &format!("{} offers you a draw, type draw to accept.", handle));
+        self.send(id, "Draw request sent.");
+    }
+
+    fn resign(&mut self, id: usize) {
+        if let Some((number, color)) = self.playing(id) {
+            self.games.get_mut(&number).unwrap().game.resign(color);
+            let handle = self.handle(id);
+            self.end_game(number, &format!("{} resigns", handle));
+        }
+    }
+
+    fn check_flags(&mut self) {
+        let flagged: Vec<(usize, Color)> = self.games.iter()
+            .filter_map(|(number, online)| online.clock.flagged().map(|color| (*number, color)))
+            .collect();
+        for (number, color) in flagged {
+            let online = self.games.get_mut(&number).unwrap();
+            online.game.ended = Some(evaluate_timeout(&online.game.board, color));
+            let description = match online.game.ended {
+                Some(GameResult::Timeout(_)) => format!("{} forfeits on time", online.handles[color_index(color)]),
+                _ => format!("{} ran out of time and {} has no material to mate",
+                    online.handles[color_index(color)], online.handles[color_index(opposite(color))]),
+            };
+            self.end_game(number, &description);
+        }
+    }
+
+    // the game has a result by now, tells everyone, rates it and clears it away
+    fn end_game(&mut self, number: usize, description: &str) {
+        let audience = self.audience(number);
+        let mut online = self.games.remove(&number).unwrap();
+        online.clock.stop();
+        let result = online.game.result().unwrap();
+        let announcement = format!("{{Game {} ({} vs. {}) {}}} {}", number, online.handles[0], online.handles[1], description, result.pgn_result());
+        for id in audience.iter() {
+            self.send(*id, &announcement);
+        }
+
+        if online.rated {
+            let handles = [online.handles[0].to_lowercase(), online.handles[1].to_lowercase()];
+            let before = [self.record(&handles[0]), self.record(&handles[1])];
+            let white_score = match result.winner() {
+                Some(White) => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            for side in 0..2 {
+                let score = if side == 0 {white_score} else {1.0 - white_score};
+                let mut record = before[side];
+                record.rating = elo(before[side].rating, before[1 - side].rating, score);
+                if score == 1.0 {
+                    record.wins += 1;
+                } else if score == 0.0 {
+                    record.losses += 1;
+                } else {
+                    record.draws += 1;
+                }
+                self.records.insert(handles[side].clone(), record);
+                self.send(online.players[side], &format!("{} rating adjustment: {} --> {}", online.handles[side], before[side].rating, record.rating));
+            }
+            if let Err(err) = save_records(&self.ratings_path, &self.records) {
+                eprintln!("couldn't save the ratings to {}: {}", self.ratings_path.display(), err);
+            }
+        }
+
+        for id in audience {
+            if let Some(user) = self.users.get_mut(&id) {
+                if user.game == Some(number) {
+                    user.game = None;
+                }
+                user.observing.retain(|observed| *observed != number);
+            }
+        }
+    }
+
+    fn refresh(&mut self, id: usize) {
+        let user = &self.users[&id];
+        match user.game.or(user.observing.first().copied()) {
+            Some(number) => self.show_board(number, id),
+            None => self.send(id, "You are neither playing nor observing a game."),
+        }
+    }
+
+    fn list_games(&mut self, id: usize) {
+        let mut lines = vec![];
+        for (number, online) in self.games.iter() {
+            lines.push(format!("{:>3} {:>5} {:<18}{:>5} {:<18}[{}] {} - {} {} to move, move {}",
+                number,
+                self.record(&online.handles[0]).rating, online.handles[0],
+                self.record(&online.handles[1]).rating, online.handles[1],
+                if online.rated {"rated"} else {"unrated"},
+                online.clock.display(White), online.clock.display(Black),
+                if online.game.turn == White {"white"} else {"black"},
+                online.game.fullmove_number()));
+        }
+        lines.push(format!("{} games displayed.", self.games.len()));
+        self.send(id, &lines.join("\n"));
+    }
+
+    fn observe(&mut self, id: usize, number: Option<&str>) {
+        let number = match number.and_then(|number| number.parse::<usize>().ok()) {
+            Some(number) if self.games.contains_key(&number) => number,
+            Some(_) => return self.send(id, "There is no such game."),
+            None => return self.send(id, "Usage: observe <game number>"),
+        };
+        if self.users[&id].game == Some(number) {
+            return self.send(id, "You are playing that game.");
+        }
+        if !self.users[&id].observing.contains(&number) {
+            self.users.get_mut(&id).unwrap().observing.push(number);
+            self.games.get_mut(&number).unwrap().observers.push(id);
+        }
+        self.send(id, &format!("You are now observing game {}.", number));
+        self.show_board(number, id);
+    }
+
+    fn unobserve(&mut self, id: usize, number: Option<&str>) {
+        let numbers: Vec<usize> = match number {
+            Some(number) => number.parse().ok().into_iter().collect(),
+            None => self.users[&id].observing.clone(),
+        };
+        if numbers.is_empty() || !numbers.iter().all(|number| self.users[&id].observing.contains(number)) {
+            return self.send(id, "You are not observing that game.");
+        }
+        for number in numbers {
+            self.users.get_mut(&id).unwrap().observing.retain(|observed| *observed != number);
+            if let Some(online) = self.games.get_mut(&number) {
+                online.observers.retain(|observer| *observer != id);
+            }
+            self.send(id, &format!("Removing game {} from observation list.", number));
+        }
+    }
+
+    fn say(&mut self, id: usize, text: &str) {
+        if let Some((number, color)) = self.playing(id) {
+            let opponent = self.games[&number].players[color_index(opposite(color))];
+            let handle = self.handle(id);
+            self.send(opponent, &format!("{} says: {}", handle, text));
+            self.send(id, &format!("(told {})", self.handle(opponent)));
+        }
+    }
+
+    fn tell(&mut self, id: usize, rest: &str) {
+        let (handle, text) = rest.split_once(' ').unwrap_or((rest, ""));
+        match self.find_user(handle) {
+            Some(other) if !text.trim().is_empty() => {
+                let from = self.handle(id);
+                self.send(other, &format!("{} tells you: {}", from, text.trim()));
+                self.send(id, &format!("(told {})", self.handle(other)));
+            }
+            Some(_) => self.send(id, "Usage: tell <handle> <text>"),
+            None => self.send(id, &format!("{} is not logged in.", handle)),
+        }
+    }
+
+    fn kibitz(&mut self, id: usize, text: &str) {
+        let user = &self.users[&id];
+        let number = match user.game.or(user.observing.first().copied()) {
+            Some(number) => number,
+            None => return self.send(id, "You are neither playing nor observing a game."),
+        };
+        let message = format!("{} kibitzes: {}", self.rated_name(id), text);
+        for other in self.audience(number) {
+            self.send(other, &message);
+        }
+    }
+
+    fn shout(&mut self, id: usize, text: &str) {
+        let message = format!("{} shouts: {}", self.handle(id), text);
+        for other in self.logged_in() {
+            self.send(other, &message);
+        }
+    }
+}
+
+fn result_description(result: &GameResult, handles: &[String; 2]) -> String {
+    let loser = |winner: &Color| &handles[color_index(opposite(*winner))];
+    match result {
+        GameResult::Checkmate(winner) => format!("{} checkmated", loser(winner)),
+        GameResult::Resignation(winner) => format!("{} resigns", loser(winner)),
+        GameResult::Timeout(winner) => format!("{} forfeits on time", loser(winner)),
+        GameResult::Stalemate => format!("Game drawn by stalemate"),
+        GameResult::FiftyMoveRule => format!("Game drawn by the 50 move rule"),
+        GameResult::ThreefoldRepetition => format!("Game drawn by repetition"),
+        GameResult::InsufficientMaterial | GameResult::DeadPosition => format!("Neither player has mating material"),
+        GameResult::TimeoutVsInsufficientMaterial => format!("Game drawn, a flag fell but no mate was possible"),
+        GameResult::DrawAgreed => format!("Game drawn by mutual agreement"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a server with no connections yet that keeps its ratings in a fresh file
+    fn server(name: &str) -> Server {
+        let path = std::env::temp_dir().join(format!("rustchess-fics-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        return Server::new(HashMap::new(), path);
+    }
+
+    // connects `id` over localhost, the returned client end has to stay open
+    fn connect(server: &mut Server, id: usize) -> TcpStream {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        server.connected(id, stream);
+        return client;
+    }
+
+    // what has been said to `id` since the last call
+    fn output(server: &mut Server, id: usize) -> String {
+        return server.outbox.remove(&id).unwrap_or_default();
+    }
+
+    fn log_in(server: &mut Server, id: usize, handle: &str) -> TcpStream {
+        let client = connect(server, id);
+        server.line(id, handle);
+        assert!(output(server, id).contains(&format!("**** Starting session as {} (", handle)));
+        return client;
+    }
+
+    #[test]
+    fn handles_are_checked_at_login() {
+        let mut server = server("login");
+        let _alice = log_in(&mut server, 0, "alice");
+
+        let _other = connect(&mut server, 1);
+        server.line(1, "ALICE");
+        assert!(output(&mut server, 1).ends_with("ALICE is already logged in.\nlogin: "));
+        server.line(1, "a-b");
+        assert!(output(&mut server, 1).contains("Sorry, handles are 2 to 17 letters and digits."));
+        assert_eq!(server.users[&1].handle, None);
+
+        server.line(1, "bob");
+        assert!(output(&mut server, 1).contains("**** Starting session as bob (1500) ****"));
+        assert_eq!(output(&mut server, 0), "Notification: bob has arrived.\n");
+    }
+
+    #[test]
+    fn a_seek_played_to_a_resignation_is_rated() {
+        let mut server = server("seek");
+        let _alice = log_in(&mut server, 0, "alice");
+        let _bob = log_in(&mut server, 1, "bob");
+
+        server.line(0, "seek 5 0 rated white");
+        assert!(output(&mut server, 0).contains("Your seek has been posted with index 1."));
+        assert!(output(&mut server, 1).contains("alice (1500) seeking 5 0 rated white (\"play 1\" to respond)"));
+
+        server.line(1, "play 1");
+        assert!(server.seeks.is_empty());
+        assert_eq!(server.games[&1].players, [0, 1]);
+        assert!(output(&mut server, 0).contains("{Game 1: alice (1500) vs. bob (1500), 5 0 rated} Creating"));
+        output(&mut server, 1);
+
+        server.line(1, "e5");
+        assert!(output(&mut server, 1).contains("It is not your move."));
+        server.line(0, "e4");
+        server.line(1, "e7e5");
+        assert_eq!(server.games[&1].game.move_history.len(), 2);
+        output(&mut server, 0);
+        output(&mut server, 1);
+
+        server.line(1, "resign");
+        let told = output(&mut server, 0);
+        assert!(told.contains("{Game 1 (alice vs. bob) bob resigns} 1-0"));
+        assert!(told.contains("alice rating adjustment: 1500 --> 1516"));
+        assert!(output(&mut server, 1).contains("bob rating adjustment: 1500 --> 1484"));
+        assert!(server.games.is_empty());
+        assert_eq!(server.users[&0].game, None);
+
+        // the ratings file reads back as the same records
+        let records = load_records(&server.ratings_path).unwrap();
+        assert_eq!((records["alice"].rating, records["alice"].wins, records["alice"].losses), (1516, 1, 0));
+        assert_eq!((records["bob"].rating, records["bob"].wins, records["bob"].losses), (1484, 0, 1));
+        let _ = std::fs::remove_file(&server.ratings_path);
+    }
+
+    #[test]
+    fn challenges_can_be_declined_or_accepted() {
+        let mut server = server("match");
+        let _alice = log_in(&mut server, 0, "alice");
+        let _bob = log_in(&mut server, 1, "bob");
+
+        server.line(0, "match bob 3 2 unrated black");
+        assert!(output(&mut server, 0).contains("Issuing: alice (1500) bob (1500) 3 2 unrated black."));
+        assert!(output(&mut server, 1).contains("Challenge: alice (1500) bob (1500) 3 2 unrated black."));
+        server.line(1, "decline");
+        assert!(output(&mut server, 0).contains("bob declines your challenge."));
+        assert!(server.challenges.is_empty());
+        output(&mut server, 1);
+
+        server.line(1, "accept");
+        assert!(output(&mut server, 1).contains("There are no offers for you to respond to."));
+
+        server.line(0, "match bob 3 2 unrated black");
+        server.line(1, "accept alice");
+        assert_eq!(server.games[&1].players, [1, 0]);
+        assert!(!server.games[&1].rated);
+        assert_eq!(server.users[&0].game, Some(1));
+    }
+
+    #[test]
+    fn leaving_in_the_middle_of_a_game_forfeits_it() {
+        let mut server = server("forfeit");
+        let _alice = log_in(&mut server, 0, "alice");
+        let bob = log_in(&mut server, 1, "bob");
+        server.line(0, "match bob unrated white");
+        server.line(1, "accept");
+        output(&mut server, 0);
+
+        drop(bob);
+        server.disconnected(1);
+        let told = output(&mut server, 0);
+        assert!(told.contains("{Game 1 (alice vs. bob) bob forfeits by disconnection} 1-0"));
+        assert!(told.contains("Notification: bob has departed."));
+        assert!(server.games.is_empty());
+        assert_eq!(server.users[&0].game, None);
+        assert!(!server.users.contains_key(&1));
+    }
+
+    #[test]
+    fn ratings_file_lines_are_checked() {
+        let path = std::env::temp_dir().join(format!("rustchess-fics-records-{}", std::process::id()));
+        assert!(load_records(&path).unwrap().is_empty());
+
+        std::fs::write(&path, "Alice 1620 3 1 2\n\nbob 1380 0 4 0\n").unwrap();
+        let records = load_records(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records["alice"].rating, records["alice"].wins, records["alice"].losses, records["alice"].draws), (1620, 3, 1, 2));
+
+        std::fs::write(&path, "alice 1620 3 1\n").unwrap();
+        assert!(load_records(&path).unwrap_err().contains("bad ratings line \"alice 1620 3 1\""));
+        std::fs::write(&path, "alice 1620 3 1 x\n").unwrap();
+        assert!(load_records(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,654 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::board::setup_board;
+use crate::position::*;
+use crate::legal_moves::*;
+use std::collections::HashMap;
+use std::fmt;
+
+// everything a FEN describes, the position a game is started from
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartPosition {
+    pub board: Board,
+    pub turn: Color,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+impl StartPosition {
+    pub fn classical() -> StartPosition {
+        let empty_piece = Piece {piece: PieceType::Null, color: Color::Null};
+        let mut board: Board = [[empty_piece; 8]; 8];
+        setup_board(&mut board);
+
+        return StartPosition {
+            board,
+            turn: White,
+            castling_rights: CastlingRights {white_long: true, white_short: true, black_long: true, black_short: true},
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameResult {
+    Checkmate(Color), // the color is always the winner
+    Resignation(Color),
+    Timeout(Color),
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+    DeadPosition,
+    TimeoutVsInsufficientMaterial,
+    DrawAgreed,
+}
+
+impl GameResult {
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            GameResult::Checkmate(color) | GameResult::Resignation(color) | GameResult::Timeout(color) => Some(*color),
+            _ => None,
+        }
+    }
+
+    pub fn pgn_result(&self) -> &'static str {
+        match self.winner() {
+            Some(White) => "1-0",
+            Some(_) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+
+    // a short fixed name for the way the game ended, for output read by programs
+    pub fn termination(&self) -> &'static str {
+        match self {
+            GameResult::Checkmate(_) => "checkmate",
+            GameResult::Resignation(_) => "resignation",
+            GameResult::Timeout(_) => "timeout",
+            GameResult::Stalemate => "stalemate",
+            GameResult::FiftyMoveRule => "fifty_move_rule",
+            GameResult::ThreefoldRepetition => "threefold_repetition",
+            GameResult::InsufficientMaterial => "insufficient_material",
+            GameResult::DeadPosition => "dead_position",
+            GameResult::TimeoutVsInsufficientMaterial => "timeout_vs_insufficient_material",
+            GameResult::DrawAgreed => "agreement",
+        }
+    }
+
+    // the reverse of termination()
+    pub fn from_termination(termination: &str, winner: Option<Color>) -> Option<GameResult> {
+        match (termination, winner) {
+            ("checkmate", Some(winner)) => Some(GameResult::Checkmate(winner)),
+            ("resignation", Some(winner)) => Some(GameResult::Resignation(winner)),
+            ("timeout", Some(winner)) => Some(GameResult::Timeout(winner)),
+            ("stalemate", None) => Some(GameResult::Stalemate),
+            ("fifty_move_rule", None) => Some(GameResult::FiftyMoveRule),
+            ("threefold_repetition", None) => Some(GameResult::ThreefoldRepetition),
+            ("insufficient_material", None) => Some(GameResult::InsufficientMaterial),
+            ("dead_position", None) => Some(GameResult::DeadPosition),
+            ("timeout_vs_insufficient_material", None) => Some(GameResult::TimeoutVsInsufficientMaterial),
+            ("agreement", None) => Some(GameResult::DrawAgreed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::Checkmate(winner) => write!(f, "{:?} wins", winner),
+            GameResult::Resignation(winner) => write!(f, "{:?} wins by resignation", winner),
+            GameResult::Timeout(winner) => write!(f, "{:?} wins on time", winner),
+            GameResult::Stalemate => write!(f, "draw by stalemate"),
+            GameResult::FiftyMoveRule => write!(f, "draw by 50 move rule"),
+            GameResult::ThreefoldRepetition => write!(f, "draw by 3 fold repetition"),
+            GameResult::InsufficientMaterial => write!(f, "draw by insufficient material"),
+            GameResult::DeadPosition => write!(f, "draw by dead position"),
+            GameResult::TimeoutVsInsufficientMaterial => write!(f, "draw by timeout vs insufficient material"),
+            GameResult::DrawAgreed => write!(f, "draw by agreement"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Game {
+    pub board: Board,
+    pub turn: Color,
+    pub move_history: Vec<MoveHistoryEntry>,
+    pub three_fold_repetition_map: HashMap<String, i32>, // (position, occurances_count)
+    pub start: StartPosition,
+    // results that can't be read off the board, like a resignation
+    pub ended: Option<GameResult>,
+    // moves taken back with undo, most recent last, along with the board they led to
+    redo_stack: Vec<(MoveHistoryEntry, Board)>,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        return Game::new();
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        return Game::from_start(StartPosition::classical());
+    }
+
+    pub fn from_start(start: StartPosition) -> Game {
+        let mut game = Game {
+            board: start.board,
+            turn: start.turn,
+            move_history: Vec::new(),
+            three_fold_repetition_map: HashMap::new(),
+            start,
+            ended: None,
+            redo_stack: Vec::new(),
+        };
+        game.record_position();
+
+        return game;
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        let mut castling_rights = self.start.castling_rights;
+        for hmove in self.move_history.iter() {
+            update_castling_rights(&mut castling_rights, &hmove.moveEntry);
+        }
+
+        return castling_rights;
+    }
+
+    pub fn en_passant_square(&self) -> Option<Square> {
+        match self.move_history.last() {
+            Some(last_entry) => en_passant_target(&last_entry.moveEntry),
+            None => self.start.en_passant.clone(),
+        }
+    }
+
+    // plies since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u32 {
+        let mut clock = 0;
+        for hmove in self.move_history.iter().rev() {
+            if hmove.isCaptureMove || hmove.moveEntry.piece.piece == PieceType::Pawn {
+                return clock;
+            }
+            clock += 1;
+        }
+
+        return clock + self.start.halfmove_clock;
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        let plies = self.move_history.len() as u32 + if self.start.turn == Black {1} else {0};
+        return self.start.fullmove_number + plies / 2;
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        return find_all_legal_moves(&self.board, self.turn, &self.castling_rights(), &self.en_passant_square());
+    }
+
+    pub fn is_in_check(&self) -> bool {
+        return is_in_check(&self.board, self.turn);
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        if self.ended.is_some() {
+            return self.ended.clone();
+        }
+
+        let legal_moves = self.legal_moves();
+        return evaluate_win_draw(self.turn, &self.board, &legal_moves, self.halfmove_clock(), &self.three_fold_repetition_map);
+    }
+
+    pub fn resign(&mut self, color: Color) {
+        let winner = if color == White {Black} else {White};
+        self.ended = Some(GameResult::Resignation(winner));
+    }
+
+    pub fn agree_draw(&mut self) {
+        self.ended = Some(GameResult::DrawAgreed);
+    }
+
+    // finds the legal move going from one square to another, a missing promotion picks a queen
+    pub fn find_legal_move(&self, from: &Square, to: &Square, promotion: Option<PieceType>) -> Option<Move> {
+        return self.legal_moves().into_iter().find(|legal_move| {
+            legal_move.from == *from && legal_move.to == *to
+                && (promotion.is_none() || legal_move.promotion == promotion)
+        });
+    }
+
+    // plays a move taken from legal_moves()
+    pub fn play_move(&mut self, legal_move: Move) {
+        self.redo_stack.clear();
+
+        self.move_history.push(MoveHistoryEntry {
+            moveEntry: legal_move.clone(),
+            boardState: self.board.clone(),
+            isCaptureMove: self.board[legal_move.to.rank][legal_move.to.file].piece != PieceType::Null
+                || legal_move.special_move == Some(SpecialMoveType::EnPassant),
+        });
+
+        play_move(&mut self.board, legal_move);
+
+        self.turn = if self.turn == White {Black} else {White};
+        self.record_position();
+    }
+
+    // takes back the last move, returns false if there is nothing to take back
+    pub fn undo(&mut self) -> bool {
+        if self.move_history.is_empty() {
+            return false;
+        }
+
+        // forget the position we are leaving so it doesn't count towards a repetition, before
+        // the history loses the move its en passant square comes from
+        let current_position_str = self.position_str();
+        let last_entry = self.move_history.pop().unwrap();
+        if let Some(count) = self.three_fold_repetition_map.get_mut(&current_position_str) {
+            *count -= 1;
+            if *count <= 0 {
+                self.three_fold_repetition_map.remove(&current_position_str);
+            }
+        }
+
+        self.redo_stack.push((last_entry.clone(), self.board.clone()));
+        self.board = last_entry.boardState;
+        // castling and en passant rights are derived from move_history, so popping the entry restores them
+        self.turn = if self.turn == White {Black} else {White};
+        self.ended = None;
+
+        return true;
+    }
+
+    // replays the last undone move, returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let (entry, board_after) = match self.redo_stack.pop() {
+            Some(v) => v,
+            None => return false,
+        };
+
+        self.move_history.push(entry);
+        self.board = board_after;
+        self.turn = if self.turn == White {Black} else {White};
+        self.record_position();
+
+        return true;
+    }
+
+    // the game position is serialized by printing board state and all legal moves
+    // TODO: optimize this board state
+    fn position_str(&self) -> String {
+        let mut current_position_str = String::new();
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                current_position_str.push_str(&format!("{:?}", self.board[rank][file]));
+            }
+        }
+
+        for legal_move in self.legal_moves().iter() {
+            current_position_str.push_str(&format!("{:?}", legal_move));
+        }
+
+        return current_position_str;
+    }
+
+    fn record_position(&mut self) {
+        let current_position_str = self.position_str();
+        match self.three_fold_repetition_map.get(&current_position_str) {
+            Some(count) => { self.three_fold_repetition_map.insert(current_position_str, count + 1); }
+            None => { self.three_fold_repetition_map.insert(current_position_str, 1); }
+        }
+    }
+}
+
+pub fn play_move(board: &mut Board, input_move: Move) {
+    board[input_move.to.rank][input_move.to.file] = board[input_move.from.rank][input_move.from.file];
+    board[input_move.from.rank][input_move.from.file] = Piece {piece: PieceType::Null, color: Color::Null};
+    
+    if input_move.special_move == Some(SpecialMoveType::EnPassant) {
+        if input_move.piece.color == Color::White {
+            let captured_pawn_square = move_down(input_move.to.clone()).unwrap();
+            board[captured_pawn_square.rank][captured_pawn_square.file] = Piece {piece: PieceType::Null, color: Color::Null};
+        } else if input_move.piece.color == Color::Black {
+            let captured_pawn_square = move_up(input_move.to.clone()).unwrap();
+            board[captured_pawn_square.rank][captured_pawn_square.file] = Piece {piece: PieceType::Null, color: Color::Null};
+        }
+    } else if input_move.special_move == Some(SpecialMoveType::CastleLong) {
+        if input_move.piece.color == Color::White {
+            board[0][0] = Piece {piece: PieceType::Null, color: Color::Null};
+            board[0][3] = Piece {piece: PieceType::Rook, color: Color::White};
+        } else if input_move.piece.color == Color::Black {
+            board[7][0] = Piece {piece: PieceType::Null, color: Color::Null};
+            board[7][3] = Piece {piece: PieceType::Rook, color: Color::Black};
+        }
+    } else if input_move.special_move == Some(SpecialMoveType::CastleShort) {
+        if input_move.piece.color == Color::White {
+            board[0][7] = Piece {piece: PieceType::Null, color: Color::Null};
+            board[0][5] = Piece {piece: PieceType::Rook, color: Color::White};
+        } else if input_move.piece.color == Color::Black {
+            board[7][7] = Piece {piece: PieceType::Null, color: Color::Null};
+            board[7][5] = Piece {piece: PieceType::Rook, color: Color::Black};
+        }
+    } else if input_move.special_move == Some(SpecialMoveType::Promote) {
+        board[input_move.to.rank][input_move.to.file].piece = input_move.promotion.unwrap_or(PieceType::Queen);
+    }
+}
+
+fn evaluate_win_draw(
+    turn: Color, 
+    board: &Board, 
+    legal_moves: &Vec<Move>, 
+    halfmove_clock: u32,
+    three_fold_repetition_map: &HashMap<String, i32>,
+) -> Option<GameResult> {
+    if is_50_move_draw(halfmove_clock) {
+        return Some(GameResult::FiftyMoveRule);
+    } else if is_3_fold_repetition_draw(three_fold_repetition_map) {
+        return Some(GameResult::ThreefoldRepetition);
+    } else if is_insuffiecient_material_draw(board) {
+        return Some(GameResult::InsufficientMaterial);
+    } else if is_dead_position(board) {
+        return Some(GameResult::DeadPosition);
+    }
+
+    let opponent_turn = if turn == White {Black} else {White};
+    let opponent_can_capture_king = is_in_check(board, turn);
+
+    if legal_moves.is_empty() && opponent_can_capture_king {
+        return Some(GameResult::Checkmate(opponent_turn));
+    } else if legal_moves.is_empty() && !opponent_can_capture_king {
+        return Some(GameResult::Stalemate);
+    }
+
+    return None;
+}
+
+fn is_50_move_draw(halfmove_clock: u32) -> bool {
+    // since a move is defined as white turn then black turn, 2*50 plies need to be considered
+    return halfmove_clock >= 100;
+}
+
+fn is_insuffiecient_material_draw(board: &Board) -> bool {
+    // neither side can deliver mate by any sequence of legal moves
+    return has_insufficient_mating_material(board, White) && has_insufficient_mating_material(board, Black);
+}
+
+// true when `color` can't checkmate even with the opponent's help, this is the rule used
+// for timeout rulings (a flag fall against a side that can't mate is a draw)
+fn has_insufficient_mating_material(board: &Board, color: Color) -> bool {
+    let mut own_knights = 0;
+    let mut own_bishops = 0;
+    let mut own_pieces = 0;
+    // opponent pieces that could block the king's escape squares for a knight mate. Queens
+    // don't count, one next to the king can always take a knight giving check
+    let mut opponent_blockers = 0;
+    let mut bishops_on_light_squares = false;
+    let mut bishops_on_dark_squares = false;
+    let mut any_knights_or_pawns = false;
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let piece = board[rank][file];
+            if piece.piece == PieceType::Null {
+                continue;
+            }
+
+            if piece.piece == PieceType::Bishop {
+                // a1 is a dark square
+                if (rank + file) % 2 == 0 {
+                    bishops_on_dark_squares = true;
+                } else {
+                    bishops_on_light_squares = true;
+                }
+            }
+            if piece.piece == PieceType::Knight || piece.piece == PieceType::Pawn {
+                any_knights_or_pawns = true;
+            }
+
+            if piece.color == color {
+                own_pieces += 1;
+                match piece.piece {
+                    PieceType::Pawn | PieceType::Rook | PieceType::Queen => return false,
+                    PieceType::Knight => own_knights += 1,
+                    PieceType::Bishop => own_bishops += 1,
+                    _ => {}
+                }
+            } else if piece.piece != PieceType::King && piece.piece != PieceType::Queen {
+                opponent_blockers += 1;
+            }
+        }
+    }
+
+    if own_knights > 0 {
+        // a lone knight can only mate a king that is boxed in by its own pieces
+        return own_pieces <= 2 && opponent_blockers == 0;
+    }
+
+    if own_bishops > 0 {
+        // bishops (of either side) all on one square color can never attack the other color,
+        // and without knights or pawns nothing can be forced onto it to block the king in. Rooks
+        // and queens next to the cornered king can always step into the check or take the bishop
+        let bishops_on_both_colors = bishops_on_light_squares && bishops_on_dark_squares;
+        return !bishops_on_both_colors && !any_knights_or_pawns;
+    }
+
+    return true;
+}
+
+// a position where no sequence of legal moves can lead to mate, beyond plain lack of material.
+// this detects only kings and pawns remaining, with every pawn rammed head on against an enemy
+// pawn and neither king able to walk to an enemy pawn it could capture
+fn is_dead_position(board: &Board) -> bool {
+    let mut attacked_by_white_pawns = [[false; 8]; 8];
+    let mut attacked_by_black_pawns = [[false; 8]; 8];
+    let mut kings: Vec<(Square, Color)> = Vec::new();
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let piece = board[rank][file];
+            let square = Square {file, rank};
+            match piece.piece {
+                PieceType::Null => {}
+                PieceType::King => kings.push((square, piece.color)),
+                PieceType::Pawn => {
+                    let opponent = if piece.color == White {Black} else {White};
+                    let (forward, captures) = if piece.color == White {
+                        (move_up(square.clone()), [move_up_left(square.clone()), move_up_right(square.clone())])
+                    } else {
+                        (move_down(square.clone()), [move_down_left(square.clone()), move_down_right(square.clone())])
+                    };
+
+                    // the pawn has to be blocked by an enemy pawn, which is blocked by it in return
+                    match forward {
+                        Ok(to) if board[to.rank][to.file] == Piece {piece: PieceType::Pawn, color: opponent} => {}
+                        _ => return false,
+                    }
+
+                    for capture in captures.into_iter().flatten() {
+                        if board[capture.rank][capture.file] == (Piece {piece: PieceType::Pawn, color: opponent}) {
+                            return false;
+                        }
+                        if piece.color == White {
+                            attacked_by_white_pawns[capture.rank][capture.file] = true;
+                        } else {
+                            attacked_by_black_pawns[capture.rank][capture.file] = true;
+                        }
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    // flood fill every square each king can ever reach, if it touches an undefended enemy pawn
+    // that pawn can be captured and the structure opens up
+    for (king_square, color) in kings {
+        let enemy_attacks = if color == White {&attacked_by_black_pawns} else {&attacked_by_white_pawns};
+        let mut visited = [[false; 8]; 8];
+        let mut to_visit = vec![king_square.clone()];
+        visited[king_square.rank][king_square.file] = true;
+
+        while let Some(square) = to_visit.pop() {
+            let neighbours = [move_up, move_down, move_left, move_right,
+                move_up_right, move_up_left, move_down_right, move_down_left];
+            for neighbour_fn in neighbours {
+                let Ok(next) = neighbour_fn(square.clone()) else { continue };
+                if visited[next.rank][next.file] {
+                    continue;
+                }
+                visited[next.rank][next.file] = true;
+
+                let piece = board[next.rank][next.file];
+                if piece.piece == PieceType::Pawn && piece.color == color {
+                    continue;
+                }
+                if enemy_attacks[next.rank][next.file] {
+                    continue;
+                }
+                if piece.piece == PieceType::Pawn {
+                    // an enemy pawn no other enemy pawn defends
+                    return false;
+                }
+                to_visit.push(next);
+            }
+        }
+    }
+
+    return true;
+}
+
+// a flag fall only loses if the opponent still has a way to mate
+pub fn evaluate_timeout(board: &Board, flagged: Color) -> GameResult {
+    let opponent = if flagged == White {Black} else {White};
+    if has_insufficient_mating_material(board, opponent) {
+        return GameResult::TimeoutVsInsufficientMaterial;
+    }
+
+    return GameResult::Timeout(opponent);
+}
+
+fn is_3_fold_repetition_draw(three_fold_repetition_map: &HashMap<String, i32>) -> bool {
+    for occurances in three_fold_repetition_map.values() {
+        if *occurances >= 3 {
+            return true;
+        }
+    }
+
+    return false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+    use crate::uci::parse_uci_move;
+
+    fn board(fen: &str) -> Board {
+        return parse_fen(fen).unwrap().board;
+    }
+
+    #[test]
+    fn knight_mates_only_with_a_blocker_other_than_a_queen() {
+        let mate = Game::from_start(parse_fen("6rk/5N2/7K/8/8/8/8/8 b - - 0 1").unwrap());
+        assert_eq!(mate.result(), Some(GameResult::Checkmate(White)));
+        assert!(!has_insufficient_mating_material(&board("6rk/8/8/8/8/8/8/K1N5 w - - 0 1"), White));
+        assert_eq!(evaluate_timeout(&board("6rk/8/8/8/8/8/8/K1N5 w - - 0 1"), Black), GameResult::Timeout(White));
+
+        assert!(has_insufficient_mating_material(&board("6qk/8/8/8/8/8/8/K1N5 w - - 0 1"), White));
+        assert_eq!(evaluate_timeout(&board("6qk/8/8/8/8/8/8/K1N5 w - - 0 1"), Black), GameResult::TimeoutVsInsufficientMaterial);
+    }
+
+    #[test]
+    fn bishop_mates_only_with_a_knight_pawn_or_other_colored_bishop() {
+        assert!(has_insufficient_mating_material(&board("kr6/8/8/8/8/8/8/K3B3 w - - 0 1"), White));
+        assert!(has_insufficient_mating_material(&board("kq6/8/8/8/8/8/8/K3B3 w - - 0 1"), White));
+        assert!(!has_insufficient_mating_material(&board("kn6/8/8/8/8/8/8/K3B3 w - - 0 1"), White));
+        assert!(!has_insufficient_mating_material(&board("k7/p7/8/8/8/8/8/K3B3 w - - 0 1"), White));
+
+        // c8 and f1 are both light, c8 and c1 aren't
+        assert!(is_insuffiecient_material_draw(&board("k1b5/8/8/8/8/8/8/K4B2 w - - 0 1")));
+        assert!(!is_insuffiecient_material_draw(&board("k1b5/8/8/8/8/8/8/K1B5 w - - 0 1")));
+    }
+
+    type Snapshot = (Board, Color, CastlingRights, Option<Square>, HashMap<String, i32>);
+
+    fn snapshot(game: &Game) -> Snapshot {
+        return (game.board, game.turn, game.castling_rights(), game.en_passant_square(), game.three_fold_repetition_map.clone());
+    }
+
+    fn play(game: &mut Game, uci_move: &str) {
+        let legal_move = parse_uci_move(game, uci_move).unwrap();
+        game.play_move(legal_move);
+    }
+
+    // the move taken back and played again leaves exactly the positions around it
+    fn undo_and_redo(fen: &str, uci_move: &str) {
+        let mut game = Game::from_start(parse_fen(fen).unwrap());
+        let before = snapshot(&game);
+        play(&mut game, uci_move);
+        let after = snapshot(&game);
+        assert_ne!(before, after);
+
+        assert!(game.undo());
+        assert_eq!(snapshot(&game), before);
+        assert!(game.move_history.is_empty());
+        assert!(!game.undo());
+
+        assert!(game.redo());
+        assert_eq!(snapshot(&game), after);
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn undo_and_redo_castling() {
+        undo_and_redo("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1");
+        undo_and_redo("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8");
+    }
+
+    #[test]
+    fn undo_and_redo_en_passant() {
+        undo_and_redo("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6");
+        // and the double step that allows it, which takes the en passant square back with it
+        undo_and_redo("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", "e2e4");
+    }
+
+    #[test]
+    fn undo_and_redo_promotion() {
+        undo_and_redo("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1", "e7e8n");
+        undo_and_redo("3r4/4P1k1/8/8/8/8/8/4K3 w - - 0 1", "e7d8q");
+    }
+
+    #[test]
+    fn repetition_counts_follow_undo() {
+        let mut game = Game::new();
+        for uci_move in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"] {
+            play(&mut game, uci_move);
+        }
+        assert_eq!(game.result(), Some(GameResult::ThreefoldRepetition));
+        assert!(game.undo());
+        assert_eq!(game.result(), None);
+        assert!(game.redo());
+        assert_eq!(game.result(), Some(GameResult::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn a_new_move_clears_the_redo_stack() {
+        let mut game = Game::new();
+        play(&mut game, "e2e4");
+        assert!(game.undo());
+        play(&mut game, "d2d4");
+        assert!(!game.redo());
+        assert_eq!(game.move_history.len(), 1);
+        assert_eq!(move_to_uci(&game.move_history[0].moveEntry), "d2d4");
+    }
+}
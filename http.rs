@@ -0,0 +1,151 @@
+use crate::json::Json;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+
+const MAX_BODY: usize = 1 << 20;
+const MAX_HEADERS: usize = 100;
+
+// just enough HTTP/1.1 for a local JSON API, one request per connection
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>, // names lower cased
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        return self.headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.as_str());
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        return self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    }
+
+    // the path split at '/', without empty pieces
+    pub fn segments(&self) -> Vec<&str> {
+        return self.path.split('/').filter(|segment| !segment.is_empty()).collect();
+    }
+}
+
+// None when the client closed the connection without sending anything
+pub fn read_request(reader: &mut BufReader<TcpStream>) -> Result<Option<Request>, String> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).map_err(|err| err.to_string())? == 0 {
+        return Ok(None);
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or("empty request line")?.to_string();
+    let target = parts.next().ok_or("request line without a path")?;
+
+    let (path, query_string) = target.split_once('?').unwrap_or((target, ""));
+    let query = query_string.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+            return Err(format!("connection closed in the headers"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() >= MAX_HEADERS {
+            return Err(format!("too many headers"));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let mut request = Request {method, path: percent_decode(path), query, headers, body: vec![]};
+    let length: usize = match request.header("content-length") {
+        Some(length) => length.parse().map_err(|_| format!("bad content-length {}", length))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(format!("body too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).map_err(|err| err.to_string())?;
+
+    return Ok(Some(request));
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut res = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => res.push(b' '),
+            b'%' if i + 2 < bytes.len() && text.is_char_boundary(i + 3) && u8::from_str_radix(&text[i + 1..i + 3], 16).is_ok() => {
+                res.push(u8::from_str_radix(&text[i + 1..i + 3], 16).unwrap());
+                i += 2;
+            }
+            b => res.push(b),
+        }
+        i += 1;
+    }
+
+    return String::from_utf8_lossy(&res).to_string();
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: &Json) -> Response {
+        Response {status, content_type: "application/json", body: body.to_string()}
+    }
+
+    pub fn text(status: u16, content_type: &'static str, body: String) -> Response {
+        Response {status, content_type, body}
+    }
+
+    pub fn error(status: u16, message: &str) -> Response {
+        return Response::json(status, &Json::object(vec![("error", Json::string(message))]));
+    }
+
+    pub fn empty(status: u16) -> Response {
+        Response {status, content_type: "text/plain", body: String::new()}
+    }
+
+    // browsers may call from any origin, it is a local server
+    pub fn write_to(&self, mut stream: &TcpStream) -> std::io::Result<()> {
+        write!(stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\
+             Access-Control-Allow-Methods: GET, POST, DELETE, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n\
+             Connection: close\r\n\r\n{}",
+            self.status, reason_phrase(self.status), self.content_type, self.body.len(), self.body)?;
+        return stream.flush();
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
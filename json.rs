@@ -0,0 +1,287 @@
+use std::fmt;
+
+// just enough JSON for the machine interfaces, objects keep their keys in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    // builds an object from (key, value) pairs
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        return Json::Object(fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect());
+    }
+
+    pub fn string(text: &str) -> Json {
+        return Json::String(text.to_string());
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => {
+                if value.fract() == 0.0 && value.abs() < 1e15 {
+                    write!(f, "{}", *value as i64)
+                } else {
+                    write!(f, "{}", value)
+                }
+            }
+            Json::String(text) => write_json_string(f, text),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// arrays and objects nested deeper than this are refused, the parser recurses for each level
+// and input from the network could otherwise run it out of stack
+const MAX_DEPTH: usize = 64;
+
+pub fn parse_json(text: &str) -> Result<Json, String> {
+    let mut parser = Parser {chars: text.chars().collect(), pos: 0, depth: 0};
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.chars.len() {
+        return Err(format!("unexpected text after the value at {}", parser.pos));
+    }
+
+    return Ok(value);
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize, // arrays and objects open around the current position
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.pos).copied();
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(format!("expected '{}' at {}", expected, self.pos));
+        }
+        self.pos += 1;
+
+        return Ok(());
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+
+        return Ok(value);
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => self.nested(Parser::array),
+            Some('{') => self.nested(Parser::object),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("unexpected '{}' at {}", c, self.pos)),
+            None => Err(format!("unexpected end of input")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Parser) -> Result<Json, String>) -> Result<Json, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("nested deeper than {} levels at {}", MAX_DEPTH, self.pos));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        return value;
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+
+        return text.parse().map(Json::Number).map_err(|_| format!("invalid number {}", text));
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut res = String::new();
+        loop {
+            let c = self.peek().ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(res),
+                '\\' => {
+                    let escaped = self.peek().ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match escaped {
+                        '"' | '\\' | '/' => res.push(escaped),
+                        'b' => res.push('\u{8}'),
+                        'f' => res.push('\u{c}'),
+                        'n' => res.push('\n'),
+                        'r' => res.push('\r'),
+                        't' => res.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            // characters outside the basic plane come as a surrogate pair
+                            if (0xd800..0xdc00).contains(&code) && self.chars.get(self.pos..self.pos + 2) == Some(&['\\', 'u']) {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            res.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        _ => return Err(format!("invalid escape \\{} at {}", escaped, self.pos)),
+                    }
+                }
+                c => res.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.get(self.pos..self.pos + 4).ok_or("truncated \\u escape")?.iter().collect();
+        self.pos += 4;
+
+        return u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid \\u escape {}", digits));
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(format!("expected ',' or ']' at {}", self.pos)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            fields.push((name, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(format!("expected ',' or '}}' at {}", self.pos)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse_json(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse_json(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(parse_json(&"[".repeat(200000)).is_err());
+        assert!(parse_json(&"{\"a\":".repeat(200000)).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::fen::*;
+use crate::pgn::*;
+use crate::uci::parse_uci_move;
+use crate::json::*;
+use std::io::Write;
+
+// one JSON request per line on stdin, one JSON response per line on stdout:
+//   {"cmd": "new"}                          optionally with "fen"
+//   {"cmd": "move", "move": "e2e4"}         UCI or SAN
+//   {"cmd": "legal_moves", "square": "e2"}
+//   {"cmd": "undo"}
+//   {"cmd": "state"}
+// an "id" in the request is echoed back in the response
+pub fn run_json_api(mut game: Game) {
+    loop {
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        println!("{}", respond_to_line(&mut game, &line));
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// the response to one line of input, which may not even be JSON
+pub fn respond_to_line(game: &mut Game, line: &str) -> Json {
+    return match parse_json(line) {
+        Ok(request) => handle_request(game, &request),
+        Err(err) => error_response(None, &format!("invalid JSON: {}", err)),
+    };
+}
+
+pub fn handle_request(game: &mut Game, request: &Json) -> Json {
+    let id = request.get("id").cloned();
+    let field = |name: &str| request.get(name).and_then(|value| value.as_str());
+
+    let mut extra: Vec<(&str, Json)> = vec![];
+    match field("cmd") {
+        Some("new") => match field("fen") {
+            Some(fen) => match parse_fen(fen) {
+                Ok(start) => *game = Game::from_start(start),
+                Err(err) => return error_response(id, &format!("invalid FEN: {}", err)),
+            },
+            None => *game = Game::new(),
+        },
+        Some("move") => {
+            let text = match field("move") {
+                Some(text) => text,
+                None => return error_response(id, "move needs a \"move\" field"),
+            };
+            if let Some(result) = game.result() {
+                return error_response(id, &format!("the game is over: {}", result));
+            }
+            match parse_uci_move(game, text).or_else(|_| parse_san(game, text)) {
+                Ok(legal_move) => game.play_move(legal_move),
+                Err(_) => return error_response(id, &format!("illegal move {}", text)),
+            }
+        }
+        Some("legal_moves") => {
+            let square = match field("square").map(parse_square) {
+                Some(Ok(square)) => square,
+                _ => return error_response(id, "legal_moves needs a \"square\" field like \"e2\""),
+            };
+            let moves = game.legal_moves().iter()
+                .filter(|legal_move| legal_move.from == square)
+                .map(|legal_move| Json::String(move_to_uci(legal_move)))
+                .collect();
+            extra.push(("moves", Json::Array(moves)));
+        }
+        Some("undo") => {
+            if !game.undo() {
+                return error_response(id, "nothing to undo");
+            }
+        }
+        Some("state") => {}
+        Some(other) => return error_response(id, &format!("unknown cmd {}", other)),
+        None => return error_response(id, "request needs a \"cmd\" field"),
+    }
+
+    let mut fields = vec![("ok", Json::Bool(true))];
+    if let Some(id) = id {
+        fields.push(("id", id));
+    }
+    fields.extend(extra);
+    fields.push(("state", game_state(game)));
+
+    return Json::object(fields);
+}
+
+fn error_response(id: Option<Json>, message: &str) -> Json {
+    let mut fields = vec![("ok", Json::Bool(false))];
+    if let Some(id) = id {
+        fields.push(("id", id));
+    }
+    fields.push(("error", Json::string(message)));
+
+    return Json::object(fields);
+}
+
+pub fn color_name(color: Color) -> &'static str {
+    if color == White {"white"} else {"black"}
+}
+
+// everything a client needs to draw the game, the board is eight strings from rank 8 down
+// to rank 1 with FEN letters for pieces and '.' for empty squares
+pub fn game_state(game: &Game) -> Json {
+    let mut ranks = vec![];
+    for rank in (0..8).rev() {
+        let row: String = game.board[rank].iter()
+            .map(|piece| if piece.piece == PieceType::Null {'.'} else {piece_to_fen_char(*piece)})
+            .collect();
+        ranks.push(Json::String(row));
+    }
+
+    let legal_moves = match game.result() {
+        Some(_) => vec![],
+        None => game.legal_moves().iter().map(|legal_move| Json::String(move_to_uci(legal_move))).collect(),
+    };
+
+    let sans = history_to_san(game);
+    let last_move = match (game.move_history.last(), sans.last()) {
+        (Some(entry), Some(san)) => Json::object(vec![
+            ("uci", Json::String(move_to_uci(&entry.moveEntry))),
+            ("san", Json::string(san)),
+        ]),
+        _ => Json::Null,
+    };
+
+    return Json::object(vec![
+        ("fen", Json::String(game_to_fen(game))),
+        ("board", Json::Array(ranks)),
+        ("turn", Json::string(color_name(game.turn))),
+        ("legal_moves", Json::Array(legal_moves)),
+        ("last_move", last_move),
+        ("check", Json::Bool(game.is_in_check())),
+        ("result", result_json(game)),
+    ]);
+}
+
+// null while the game goes on
+pub fn result_json(game: &Game) -> Json {
+    return match game.result() {
+        Some(result) => Json::object(vec![
+            ("result", Json::string(result.pgn_result())),
+            ("termination", Json::string(result.termination())),
+            ("winner", match result.winner() {
+                Some(winner) => Json::string(color_name(winner)),
+                None => Json::Null,
+            }),
+        ]),
+        None => Json::Null,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(response: &Json) -> &str {
+        assert_eq!(response.get("ok"), Some(&Json::Bool(false)));
+        return response.get("error").and_then(|error| error.as_str()).unwrap();
+    }
+
+    #[test]
+    fn a_legal_move_is_played() {
+        let mut game = Game::new();
+        let response = respond_to_line(&mut game, r#"{"cmd": "move", "move": "e2e4", "id": 7}"#);
+        assert_eq!(response.get("ok"), Some(&Json::Bool(true)));
+        assert_eq!(response.get("id"), Some(&Json::Number(7.0)));
+
+        let state = response.get("state").unwrap();
+        assert_eq!(state.get("turn"), Some(&Json::string("black")));
+        assert_eq!(state.get("last_move").and_then(|last| last.get("san")), Some(&Json::string("e4")));
+        assert_eq!(state.get("fen"), Some(&Json::string("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")));
+        assert_eq!(game.move_history.len(), 1);
+
+        // SAN works as well
+        let response = respond_to_line(&mut game, r#"{"cmd": "move", "move": "Nf6"}"#);
+        assert_eq!(response.get("ok"), Some(&Json::Bool(true)));
+        assert_eq!(game.move_history.len(), 2);
+    }
+
+    #[test]
+    fn an_illegal_move_is_refused() {
+        let mut game = Game::new();
+        let response = respond_to_line(&mut game, r#"{"cmd": "move", "move": "e2e5", "id": "a"}"#);
+        assert_eq!(error_of(&response), "illegal move e2e5");
+        assert_eq!(response.get("id"), Some(&Json::string("a")));
+        assert!(game.move_history.is_empty());
+    }
+
+    #[test]
+    fn an_unknown_command_is_refused() {
+        let mut game = Game::new();
+        assert_eq!(error_of(&respond_to_line(&mut game, r#"{"cmd": "fly"}"#)), "unknown cmd fly");
+        assert_eq!(error_of(&respond_to_line(&mut game, r#"{"move": "e2e4"}"#)), "request needs a \"cmd\" field");
+    }
+
+    #[test]
+    fn malformed_json_is_refused() {
+        let mut game = Game::new();
+        let response = respond_to_line(&mut game, r#"{"cmd": "move", "move": "#);
+        assert!(error_of(&response).starts_with("invalid JSON: "));
+        assert_eq!(response.get("id"), None);
+        assert!(game.move_history.is_empty());
+    }
+}
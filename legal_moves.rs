@@ -6,15 +6,20 @@ use crate::piece::Color;
 use crate::board::Board;
 use crate::position::*;
 
-pub fn find_all_legal_moves(board: &Board, turn: Color, move_history: &Vec<MoveHistoryEntry>) -> Vec<Move> {
+pub fn find_all_legal_moves(
+    board: &Board,
+    turn: Color,
+    castling_rights: &CastlingRights,
+    en_passant: &Option<Square>,
+) -> Vec<Move> {
     let mut res: Vec<Move> = vec![];
 
     res.extend(find_basic_legal_moves(board, turn));
     
-    res.extend(find_en_passant_moves(board, turn, move_history));
-    res.extend(find_castling_moves(board, turn, move_history));
+    res.extend(find_en_passant_moves(board, turn, en_passant));
+    res.extend(find_castling_moves(board, turn, castling_rights));
 
-    remove_moves_leading_to_check(&mut res, &board, turn);
+    remove_moves_leading_to_check(&mut res, board, turn);
 
     return res;
 }
@@ -87,6 +92,7 @@ fn find_knight_legal_moves(board: &Board, position: Square, color: Color) -> Vec
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: None,
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color != color{
                 res.push(move_under_consideration);
@@ -111,6 +117,7 @@ fn find_king_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: None,
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color != color {
                 res.push(move_under_consideration);
@@ -131,6 +138,7 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: if to_pos.rank == 7 {Some(SpecialMoveType::Promote)} else {None},
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color == Color::Null {
                 res.push(move_under_consideration);
@@ -142,9 +150,10 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: Some(SpecialMoveType::PawnLongMove),
+                promotion: None,
             };
             if position.rank == 1 {
-                if board[to_pos.rank][to_pos.file].color == Color::Null {
+                if board[to_pos.rank][to_pos.file].color == Color::Null && board[2][position.file].color == Color::Null {
                     res.push(move_under_consideration);
                 }
             }
@@ -155,6 +164,7 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: if to_pos.rank == 7 {Some(SpecialMoveType::Promote)} else {None},
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color == Color::Black {
                 res.push(move_under_consideration);
@@ -166,6 +176,7 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: if to_pos.rank == 7 {Some(SpecialMoveType::Promote)} else {None},
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color == Color::Black {
                 res.push(move_under_consideration);
@@ -178,6 +189,7 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: if to_pos.rank == 0 {Some(SpecialMoveType::Promote)} else {None},
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color == Color::Null {
                 res.push(move_under_consideration);
@@ -189,9 +201,10 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: Some(SpecialMoveType::PawnLongMove),
+                promotion: None,
             };
             if position.rank == 6 {
-                if board[to_pos.rank][to_pos.file].color == Color::Null {
+                if board[to_pos.rank][to_pos.file].color == Color::Null && board[5][position.file].color == Color::Null {
                     res.push(move_under_consideration);
                 }
             }
@@ -202,6 +215,7 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: if to_pos.rank == 0 {Some(SpecialMoveType::Promote)} else {None},
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color == Color::White {
                 res.push(move_under_consideration);
@@ -213,14 +227,27 @@ fn find_pawn_legal_moves(board: &Board, position: Square, color: Color) -> Vec<M
                 to: to_pos.clone(),
                 piece: board[position.rank][position.file].clone(),
                 special_move: if to_pos.rank == 0 {Some(SpecialMoveType::Promote)} else {None},
+                promotion: None,
             };
             if board[to_pos.rank][to_pos.file].color == Color::White {
                 res.push(move_under_consideration);
             }
         }
     }
+
+    // a promoting move is listed once for every piece the pawn can turn into
+    let mut expanded_res: Vec<Move> = vec![];
+    for pawn_move in res {
+        if pawn_move.special_move == Some(SpecialMoveType::Promote) {
+            for promotion in [Queen, Rook, Bishop, Knight] {
+                expanded_res.push(Move {promotion: Some(promotion), ..pawn_move.clone()});
+            }
+        } else {
+            expanded_res.push(pawn_move);
+        }
+    }
     
-    return res;
+    return expanded_res;
 }
 
 fn find_moves_in_direction(
@@ -238,6 +265,7 @@ fn find_moves_in_direction(
             to: to_pos.clone(),
             piece: board[position.rank][position.file].clone(),
             special_move: None,
+            promotion: None,
         };
         if board[to_pos.rank][to_pos.file].color == Color::Null {
             res.push(move_under_consideration);
@@ -258,9 +286,15 @@ pub fn remove_moves_leading_to_check(legal_moves: &mut Vec<Move>, board: &Board,
     legal_moves.retain(|legal_move| {
         let mut fake_board = board.clone();
 
-        // play the move
-        fake_board[legal_move.to.rank][legal_move.to.file] = fake_board[legal_move.from.rank][legal_move.from.file];
-        fake_board[legal_move.from.rank][legal_move.from.file] = Piece {piece: PieceType::Null, color: Color::Null};
+        // play the move, a move to its own square (used by the castling checks) leaves the king where it is
+        if legal_move.from != legal_move.to {
+            fake_board[legal_move.to.rank][legal_move.to.file] = fake_board[legal_move.from.rank][legal_move.from.file];
+            fake_board[legal_move.from.rank][legal_move.from.file] = Piece {piece: PieceType::Null, color: Color::Null};
+        }
+        // taking en passant also takes the pawn off the square beside the capturing pawn
+        if legal_move.special_move == Some(SpecialMoveType::EnPassant) {
+            fake_board[legal_move.from.rank][legal_move.to.file] = Piece {piece: PieceType::Null, color: Color::Null};
+        }
         
         // find all legal moves for the opponent 
         let fake_turn = if turn == White {Black} else {White};
@@ -289,145 +323,155 @@ pub fn remove_moves_leading_to_check(legal_moves: &mut Vec<Move>, board: &Board,
 
 }
 
-pub fn find_en_passant_moves(board: &Board, turn: Color, move_history: &Vec<MoveHistoryEntry>) -> Vec<Move> {
+pub fn find_en_passant_moves(board: &Board, turn: Color, en_passant: &Option<Square>) -> Vec<Move> {
     let mut res = vec![];
-    let last_played_move_opt = move_history.last();
 
-    if last_played_move_opt == None {
-        return res;
+    let target = match en_passant {
+        Some(square) => square.clone(),
+        None => return res,
+    };
+
+    // the pawn that just moved two squares sits right behind the target square, and the
+    // pawns that can take it sit diagonally behind the target square
+    let (captured_pawn_square, possible_positions) = if turn == Color::White {
+        (move_down(target.clone()), [move_down_left(target.clone()), move_down_right(target.clone())])
+    } else {
+        (move_up(target.clone()), [move_up_left(target.clone()), move_up_right(target.clone())])
+    };
+
+    let opponent = if turn == White {Black} else {White};
+    match captured_pawn_square {
+        Ok(pos) if board[pos.rank][pos.file] == (Piece {piece: PieceType::Pawn, color: opponent}) => {}
+        _ => return res,
     }
 
-    let last_played_move = &last_played_move_opt.unwrap().moveEntry;
-
-    // last_played_move being PawnLongMove also implies that the color is opposite to turn
-    // and that the piece is a pawn
-    if last_played_move.special_move == Some(SpecialMoveType::PawnLongMove) {
-        if turn == Color::White {
-            // posible positions for a pawn to be able to take the last moved pawn enpassant
-            let possible_position_1 = move_right(last_played_move.to.clone());
-            let possible_position_2 = move_left(last_played_move.to.clone());
-
-            // Some assumptions are valid here hence the unwraps
-            // since the last move is a PawnLongMove, we know that the square behind it is free
-            // we also know that it won't cross the board boundaries
-
-            if let Ok(pos) = possible_position_1 {
-                if board[pos.rank][pos.file].piece == PieceType::Pawn && board[pos.rank][pos.file].color == Color::White {
-                    res.push(Move {
-                        from: pos.clone(),
-                        to: move_up_left(pos.clone()).unwrap(),
-                        piece: board[pos.rank][pos.file].clone(),
-                        special_move: Some(SpecialMoveType::EnPassant),
-                    });
-                }
-            }
+    for pos in possible_positions.into_iter().flatten() {
+        if board[pos.rank][pos.file].piece == PieceType::Pawn && board[pos.rank][pos.file].color == turn {
+            res.push(Move {
+                from: pos.clone(),
+                to: target.clone(),
+                piece: board[pos.rank][pos.file].clone(),
+                special_move: Some(SpecialMoveType::EnPassant),
+                promotion: None,
+            });
+        }
+    }
 
-            if let Ok(pos) = possible_position_2 {
-                if board[pos.rank][pos.file].piece == PieceType::Pawn && board[pos.rank][pos.file].color == Color::White {
-                    res.push(Move {
-                        from: pos.clone(),
-                        to: move_up_right(pos.clone()).unwrap(),
-                        piece: board[pos.rank][pos.file].clone(),
-                        special_move: Some(SpecialMoveType::EnPassant),
-                    });
-                }
-            }
-        } else if turn == Color::Black {
-            // posible positions for a pawn to be able to take the last moved pawn enpassant
-            let possible_position_1 = move_right(last_played_move.to.clone());
-            let possible_position_2 = move_left(last_played_move.to.clone());
-
-            // Some assumptions are valid here hence the unwraps
-            // since the last move is a PawnLongMove, we know that the square behind it is free
-            // we also know that it won't cross the board boundaries
-
-            if let Ok(pos) = possible_position_1 {
-                if board[pos.rank][pos.file].piece == PieceType::Pawn && board[pos.rank][pos.file].color == Color::Black {
-                    res.push(Move {
-                        from: pos.clone(),
-                        to: move_down_left(pos.clone()).unwrap(),
-                        piece: board[pos.rank][pos.file].clone(),
-                        special_move: Some(SpecialMoveType::EnPassant),
-                    });
-                }
-            }
+    res
+}
 
-            if let Ok(pos) = possible_position_2 {
-                if board[pos.rank][pos.file].piece == PieceType::Pawn && board[pos.rank][pos.file].color == Color::Black {
-                    res.push(Move {
-                        from: pos.clone(),
-                        to: move_down_right(pos.clone()).unwrap(),
-                        piece: board[pos.rank][pos.file].clone(),
-                        special_move: Some(SpecialMoveType::EnPassant),
-                    });
-                }
+// the square a pawn can be taken on en passant right after this move, if any
+pub fn en_passant_target(played_move: &Move) -> Option<Square> {
+    if played_move.special_move != Some(SpecialMoveType::PawnLongMove) {
+        return None;
+    }
+
+    if played_move.piece.color == Color::White {
+        return move_up(played_move.from.clone()).ok();
+    } else {
+        return move_down(played_move.from.clone()).ok();
+    }
+}
+
+// a king move gives up both castling rights of its side, anything leaving or landing on
+// a rook's home square gives up castling on that side
+pub fn update_castling_rights(castling_rights: &mut CastlingRights, played_move: &Move) {
+    if played_move.piece.piece == PieceType::King {
+        if played_move.piece.color == Color::White {
+            castling_rights.white_long = false;
+            castling_rights.white_short = false;
+        } else if played_move.piece.color == Color::Black {
+            castling_rights.black_long = false;
+            castling_rights.black_short = false;
+        }
+    }
+
+    for square in [&played_move.from, &played_move.to] {
+        match (square.rank, square.file) {
+            (0, 0) => castling_rights.white_long = false,
+            (0, 7) => castling_rights.white_short = false,
+            (7, 0) => castling_rights.black_long = false,
+            (7, 7) => castling_rights.black_short = false,
+            _ => {}
+        }
+    }
+}
+
+pub fn is_in_check(board: &Board, color: Color) -> bool {
+    let opponent = if color == White {Black} else {White};
+
+    // locate the king
+    let mut king_position: Square = Square {rank: 8, file: 8};
+    for i in 0..8 {
+        for j in 0..8 {
+            let piece = board[i][j];
+            if piece.piece == PieceType::King && piece.color == color {
+                king_position = Square {rank: i, file: j};
             }
         }
     }
 
-    res
+    // check if any opponent move can take the king
+    for opponent_move in find_basic_legal_moves(board, opponent) {
+        if opponent_move.to == king_position {
+            return true;
+        }
+    }
+
+    return false;
 }
 
-pub fn find_castling_moves(board: &Board, turn: Color, move_history: &Vec<MoveHistoryEntry>) -> Vec<Move> {
+pub fn find_castling_moves(board: &Board, turn: Color, castling_rights: &CastlingRights) -> Vec<Move> {
     let mut res = vec![];
 
-    if white_can_castle_long(board, move_history) && turn == Color::White {
+    if white_can_castle_long(board, castling_rights) && turn == Color::White {
         res.push(Move {
             from: Square{file: 4, rank: 0},
             to: Square{file: 2, rank: 0},
             piece: Piece {piece: PieceType::King, color: Color::White},
             special_move: Some(SpecialMoveType::CastleLong),
+            promotion: None,
         })
     }
 
-    if white_can_castle_short(board, move_history) && turn == Color::White {
+    if white_can_castle_short(board, castling_rights) && turn == Color::White {
         res.push(Move {
             from: Square{file: 4, rank: 0},
             to: Square{file: 6, rank: 0},
             piece: Piece {piece: PieceType::King, color: Color::White},
             special_move: Some(SpecialMoveType::CastleShort),
+            promotion: None,
         })
     }
 
-    if black_can_castle_long(board, move_history) && turn == Color::Black {
+    if black_can_castle_long(board, castling_rights) && turn == Color::Black {
         res.push(Move {
             from: Square{file: 4, rank: 7},
             to: Square{file: 2, rank: 7},
             piece: Piece {piece: PieceType::King, color: Color::Black},
             special_move: Some(SpecialMoveType::CastleLong),
+            promotion: None,
         })
     }
 
-    if black_can_castle_short(board, move_history) && turn == Color::Black {
+    if black_can_castle_short(board, castling_rights) && turn == Color::Black {
         res.push(Move {
             from: Square{file: 4, rank: 7},
             to: Square{file: 6, rank: 7},
             piece: Piece {piece: PieceType::King, color: Color::Black},
             special_move: Some(SpecialMoveType::CastleShort),
+            promotion: None,
         })
     }
 
     return res;
 }
 
-pub fn white_can_castle_long(board: &Board, move_history: &Vec<MoveHistoryEntry>) -> bool {
-    let mut king_rook_never_moved: bool = true;
-    for hmove in move_history {
-        if hmove.moveEntry.piece.piece == PieceType::King && hmove.moveEntry.piece.color == Color::White {
-            king_rook_never_moved = false;
-            break;
-        }
-        if hmove.moveEntry.piece.piece == PieceType::Rook && hmove.moveEntry.piece.color == Color::White {
-            if hmove.moveEntry.from.file == 0 && hmove.moveEntry.from.rank == 0 {
-                king_rook_never_moved = false;
-                break;
-            }
-        }
-    }
-    // extra check for non-standard initial positions
-    if board[4][0].piece != PieceType::King && board[4][0].color != Color::White {
-        king_rook_never_moved = false;
-    }
+pub fn white_can_castle_long(board: &Board, castling_rights: &CastlingRights) -> bool {
+    // the right alone isn't enough for hand made positions, the king and rook have to be home
+    let castling_right_kept = castling_rights.white_long
+        && board[0][4] == Piece {piece: PieceType::King, color: Color::White}
+        && board[0][0] == Piece {piece: PieceType::Rook, color: Color::White};
 
     let no_pieces_block_castle = board[0][1].piece == PieceType::Null 
         && board[0][2].piece == PieceType::Null
@@ -438,41 +482,31 @@ pub fn white_can_castle_long(board: &Board, move_history: &Vec<MoveHistoryEntry>
         to: Square{file: 4, rank: 0},
         piece: Piece {piece: PieceType::King, color: Color::White},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 0},
         to: Square{file: 3, rank: 0},
         piece: Piece {piece: PieceType::King, color: Color::White},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 0},
         to: Square{file: 2, rank: 0},
         piece: Piece {piece: PieceType::King, color: Color::White},
         special_move: None,
+        promotion: None,
     }];
     remove_moves_leading_to_check(&mut hypothetical_king_moves, board, Color::White);
     let no_check_in_king_path = hypothetical_king_moves.len() == 3;
 
-    return king_rook_never_moved && no_pieces_block_castle && no_check_in_king_path;
+    return castling_right_kept && no_pieces_block_castle && no_check_in_king_path;
 }
 
-pub fn white_can_castle_short(board: &Board, move_history: &Vec<MoveHistoryEntry>) -> bool {
-    let mut king_rook_never_moved: bool = true;
-    for hmove in move_history {
-        if hmove.moveEntry.piece.piece == PieceType::King && hmove.moveEntry.piece.color == Color::White {
-            king_rook_never_moved = false;
-            break;
-        }
-        if hmove.moveEntry.piece.piece == PieceType::Rook && hmove.moveEntry.piece.color == Color::White {
-            if hmove.moveEntry.from.file == 7 && hmove.moveEntry.from.rank == 0 {
-                king_rook_never_moved = false;
-                break;
-            }
-        }
-    }
-    // extra check for non-standard initial positions
-    if board[4][0].piece != PieceType::King && board[4][0].color != Color::White {
-        king_rook_never_moved = false;
-    }
+pub fn white_can_castle_short(board: &Board, castling_rights: &CastlingRights) -> bool {
+    // the right alone isn't enough for hand made positions, the king and rook have to be home
+    let castling_right_kept = castling_rights.white_short
+        && board[0][4] == Piece {piece: PieceType::King, color: Color::White}
+        && board[0][7] == Piece {piece: PieceType::Rook, color: Color::White};
 
     let no_pieces_block_castle = board[0][5].piece == PieceType::Null 
         && board[0][6].piece == PieceType::Null;
@@ -482,41 +516,31 @@ pub fn white_can_castle_short(board: &Board, move_history: &Vec<MoveHistoryEntry
         to: Square{file: 4, rank: 0},
         piece: Piece {piece: PieceType::King, color: Color::White},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 0},
         to: Square{file: 5, rank: 0},
         piece: Piece {piece: PieceType::King, color: Color::White},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 0},
         to: Square{file: 6, rank: 0},
         piece: Piece {piece: PieceType::King, color: Color::White},
         special_move: None,
+        promotion: None,
     }];
     remove_moves_leading_to_check(&mut hypothetical_king_moves, board, Color::White);
     let no_check_in_king_path = hypothetical_king_moves.len() == 3;
 
-    return king_rook_never_moved && no_pieces_block_castle && no_check_in_king_path;
+    return castling_right_kept && no_pieces_block_castle && no_check_in_king_path;
 }
 
-pub fn black_can_castle_long(board: &Board, move_history: &Vec<MoveHistoryEntry>) -> bool {
-    let mut king_rook_never_moved: bool = true;
-    for hmove in move_history {
-        if hmove.moveEntry.piece.piece == PieceType::King && hmove.moveEntry.piece.color == Color::Black {
-            king_rook_never_moved = false;
-            break;
-        }
-        if hmove.moveEntry.piece.piece == PieceType::Rook && hmove.moveEntry.piece.color == Color::Black {
-            if hmove.moveEntry.from.file == 0 && hmove.moveEntry.from.rank == 7 {
-                king_rook_never_moved = false;
-                break;
-            }
-        }
-    }
-    // extra check for non-standard initial positions
-    if board[4][7].piece != PieceType::King && board[4][7].color != Color::Black {
-        king_rook_never_moved = false;
-    }
+pub fn black_can_castle_long(board: &Board, castling_rights: &CastlingRights) -> bool {
+    // the right alone isn't enough for hand made positions, the king and rook have to be home
+    let castling_right_kept = castling_rights.black_long
+        && board[7][4] == Piece {piece: PieceType::King, color: Color::Black}
+        && board[7][0] == Piece {piece: PieceType::Rook, color: Color::Black};
 
     let no_pieces_block_castle = board[7][1].piece == PieceType::Null 
         && board[7][2].piece == PieceType::Null
@@ -527,41 +551,31 @@ pub fn black_can_castle_long(board: &Board, move_history: &Vec<MoveHistoryEntry>
         to: Square{file: 4, rank: 7},
         piece: Piece {piece: PieceType::King, color: Color::Black},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 7},
         to: Square{file: 3, rank: 7},
         piece: Piece {piece: PieceType::King, color: Color::Black},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 7},
         to: Square{file: 2, rank: 7},
         piece: Piece {piece: PieceType::King, color: Color::Black},
         special_move: None,
+        promotion: None,
     }];
     remove_moves_leading_to_check(&mut hypothetical_king_moves, board, Color::Black);
     let no_check_in_king_path = hypothetical_king_moves.len() == 3;
 
-    return king_rook_never_moved && no_pieces_block_castle && no_check_in_king_path;
+    return castling_right_kept && no_pieces_block_castle && no_check_in_king_path;
 }
 
-pub fn black_can_castle_short(board: &Board, move_history: &Vec<MoveHistoryEntry>) -> bool {
-    let mut king_rook_never_moved: bool = true;
-    for hmove in move_history {
-        if hmove.moveEntry.piece.piece == PieceType::King && hmove.moveEntry.piece.color == Color::Black {
-            king_rook_never_moved = false;
-            break;
-        }
-        if hmove.moveEntry.piece.piece == PieceType::Rook && hmove.moveEntry.piece.color == Color::Black {
-            if hmove.moveEntry.from.file == 7 && hmove.moveEntry.from.rank == 0 {
-                king_rook_never_moved = false;
-                break;
-            }
-        }
-    }
-    // extra check for non-standard initial positions
-    if board[4][7].piece != PieceType::King && board[4][7].color != Color::Black {
-        king_rook_never_moved = false;
-    }
+pub fn black_can_castle_short(board: &Board, castling_rights: &CastlingRights) -> bool {
+    // the right alone isn't enough for hand made positions, the king and rook have to be home
+    let castling_right_kept = castling_rights.black_short
+        && board[7][4] == Piece {piece: PieceType::King, color: Color::Black}
+        && board[7][7] == Piece {piece: PieceType::Rook, color: Color::Black};
 
     let no_pieces_block_castle = board[7][5].piece == PieceType::Null 
         && board[7][6].piece == PieceType::Null;
@@ -571,19 +585,22 @@ pub fn black_can_castle_short(board: &Board, move_history: &Vec<MoveHistoryEntry
         to: Square{file: 4, rank: 7},
         piece: Piece {piece: PieceType::King, color: Color::Black},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 7},
         to: Square{file: 5, rank: 7},
         piece: Piece {piece: PieceType::King, color: Color::Black},
         special_move: None,
+        promotion: None,
     }, Move {
         from: Square{file: 4, rank: 7},
         to: Square{file: 6, rank: 7},
         piece: Piece {piece: PieceType::King, color: Color::Black},
         special_move: None,
+        promotion: None,
     }];
     remove_moves_leading_to_check(&mut hypothetical_king_moves, board, Color::Black);
     let no_check_in_king_path = hypothetical_king_moves.len() == 3;
 
-    return king_rook_never_moved && no_pieces_block_castle && no_check_in_king_path;
+    return castling_right_kept && no_pieces_block_castle && no_check_in_king_path;
 }
\ No newline at end of file
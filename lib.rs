@@ -0,0 +1,45 @@
+// the rules, formats, engine and front ends, shared by the chess and chess-server binaries
+
+// the code base keeps explicit returns, index loops over the board, the original
+// camelCase history fields and Result<_, ()> for bad squares, so those lints are silenced
+// crate wide
+#![allow(non_snake_case)]
+#![allow(clippy::needless_return, clippy::needless_range_loop, clippy::clone_on_copy)]
+#![allow(clippy::collapsible_if, clippy::ptr_arg, clippy::useless_format, clippy::result_unit_err)]
+
+pub mod position;
+pub mod piece;
+pub mod board;
+pub mod legal_moves;
+pub mod game;
+pub mod fen;
+pub mod validate;
+pub mod editor;
+pub mod pgn;
+pub mod engine;
+pub mod book;
+pub mod syzygy;
+pub mod endgame;
+pub mod solver;
+pub mod render;
+pub mod clock;
+pub mod tui;
+pub mod random;
+pub mod players;
+pub mod perft;
+pub mod uci;
+pub mod uci_engine;
+pub mod cli;
+pub mod batch;
+pub mod json;
+pub mod json_api;
+pub mod savegame;
+pub mod network;
+pub mod fics;
+pub mod runner;
+pub mod events;
+pub mod engine_match;
+pub mod http;
+pub mod ws;
+#[cfg(feature = "serde")]
+pub mod serialize;
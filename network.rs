@@ -0,0 +1,594 @@
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::game::GameResult;
+use crate::fen::game_to_fen;
+use crate::pgn::parse_san;
+use crate::render::*;
+use crate::uci::*;
+use crate::random::Random;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+// Two player games over TCP. The host owns the game and checks every move, the player who
+// joins only says what they want to do. One message per line:
+//
+//   joining player -> host              host -> joining player
+//   HELLO [token]   join or rejoin      WELCOME <color> <token>
+//   MOVE <uci>                          POSITION fen <fen> moves <uci> ...
+//   RESIGN                              DRAW_OFFERED <color>
+//   DRAW            offer or accept     DRAW_DECLINED <color>
+//   DECLINE         turn an offer down  RESULT <result> <termination> [winner]
+//                                       ERROR <message>
+//                                       BYE   the host left
+//
+// a player who loses the connection says HELLO again with the token from WELCOME and carries on
+
+pub const DEFAULT_PORT: u16 = 7777;
+const RECONNECT_ATTEMPTS: u32 = 30;
+
+const NETWORK_HELP: &str = "\
+moves are entered as coordinates (e2e4) or in SAN (Nf3)
+  board     show the board again
+  draw      offer a draw, or accept the one on the table
+  decline   turn a draw offer down
+  resign    give up the game
+  quit      leave";
+
+enum Event {
+    Local(String), // a line typed at this end
+    LocalClosed,
+    Connected(usize, TcpStream),
+    Remote(usize, String),
+    Disconnected(usize),
+}
+
+fn spawn_stdin_reader(events: Sender<Event>) {
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if events.send(Event::Local(line)).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = events.send(Event::LocalClosed);
+    });
+}
+
+fn spawn_socket_reader(id: usize, stream: TcpStream, events: Sender<Event>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if events.send(Event::Remote(id, line.trim().to_string())).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = events.send(Event::Disconnected(id));
+    });
+}
+
+fn send_line(mut stream: &TcpStream, line: &str) {
+    // a failed write shows up as a disconnect on the reading side
+    let _ = writeln!(stream, "{}", line);
+}
+
+fn color_name(color: Color) -> &'static str {
+    if color == White {"white"} else {"black"}
+}
+
+fn opposite(color: Color) -> Color {
+    if color == White {Black} else {White}
+}
+
+// the whole game as a POSITION message, the moves let the other side rebuild the history
+pub fn position_message(game: &Game) -> String {
+    let start = Game::from_start(game.start.clone());
+    let mut message = format!("POSITION fen {} moves", game_to_fen(&start));
+    for entry in game.move_history.iter() {
+        message.push(' ');
+        message.push_str(&move_to_uci(&entry.moveEntry));
+    }
+
+    return message;
+}
+
+pub fn result_message(result: &GameResult) -> String {
+    match result.winner() {
+        Some(winner) => format!("RESULT {} {} {}", result.pgn_result(), result.termination(), color_name(winner)),
+        None => format!("RESULT {} {}", result.pgn_result(), result.termination()),
+    }
+}
+
+// turns what the player typed into a protocol command, checking moves against their copy of the game
+fn local_command(game: &Game, input: &str) -> Result<String, String> {
+    match input {
+        "resign" => Ok("RESIGN".to_string()),
+        "draw" => Ok("DRAW".to_string()),
+        "decline" => Ok("DECLINE".to_string()),
+        _ => match parse_uci_move(game, input).or_else(|_| parse_san(game, input)) {
+            Ok(legal_move) => Ok(format!("MOVE {}", move_to_uci(&legal_move))),
+            Err(_) => Err(format!("illegal move {}, type help for the commands", input)),
+        },
+    }
+}
+
+struct Host {
+    game: Game,
+    color: Color,
+    token: String,
+    connections: HashMap<usize, TcpStream>,
+    peer: Option<usize>,
+    joined: bool, // somebody took the other seat, only their token gets back in
+    draw_offer: Option<Color>,
+    render_options: RenderOptions,
+}
+
+pub fn run_host(game: Game, port: u16, color: Color, render_options: RenderOptions) -> Result<(), String> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|err| format!("couldn't listen on port {}: {}", port, err))?;
+    let port = listener.local_addr().map_err(|err| err.to_string())?.port();
+    println!("hosting as {:?}, waiting for an opponent on port {}", color, port);
+
+    let (sender, events) = channel();
+    spawn_stdin_reader(sender.clone());
+    return host_game(listener, game, color, render_options, sender, events);
+}
+
+// the host's end of the game, what the host types comes in as Local events
+fn host_game(listener: TcpListener, game: Game, color: Color, render_options: RenderOptions, sender: Sender<Event>, events: Receiver<Event>) -> Result<(), String> {
+    let accept_events = sender;
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let reader = match stream.try_clone() {
+                Ok(reader) => reader,
+                Err(_) => continue,
+            };
+            // announce the connection before anything it says can arrive
+            if accept_events.send(Event::Connected(id, stream)).is_err() {
+                return;
+            }
+            spawn_socket_reader(id, reader, accept_events.clone());
+        }
+    });
+
+    let mut host = Host {
+        game,
+        color,
+        token: format!("{:016x}", Random::from_time().next_u64()),
+        connections: HashMap::new(),
+        peer: None,
+        joined: false,
+        draw_offer: None,
+        render_options,
+    };
+    host.show();
+
+    while let Ok(event) = events.recv() {
+        match event {
+            Event::Connected(id, stream) => {
+                host.connections.insert(id, stream);
+            }
+            Event::Remote(id, line) => host.remote_line(id, &line),
+            Event::Disconnected(id) => {
+                host.connections.remove(&id);
+                if host.peer == Some(id) {
+                    host.peer = None;
+                    println!("your opponent disconnected, waiting for them to come back");
+                }
+            }
+            Event::Local(line) => {
+                if !host.local_line(line.trim()) {
+                    break;
+                }
+            }
+            Event::LocalClosed => break,
+        }
+
+        if let Some(result) = host.game.result() {
+            host.send(&result_message(&result));
+            println!("{}", result);
+            println!("Game Over");
+            return Ok(());
+        }
+    }
+
+    host.send("BYE");
+    return Ok(());
+}
+
+impl Host {
+    fn send(&self, line: &str) {
+        if let Some(stream) = self.peer.and_then(|peer| self.connections.get(&peer)) {
+            send_line(stream, line);
+        }
+    }
+
+    fn show(&self) {
+        println!("{}", render_board(&self.game, &self.render_options, None, None));
+        if self.game.result().is_none() {
+            if self.game.turn == self.color {
+                println!("your move");
+            } else {
+                println!("waiting for your opponent");
+            }
+        }
+    }
+
+    fn remote_line(&mut self, id: usize, line: &str) {
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+
+        if command == "HELLO" {
+            let stream = match self.connections.get(&id) {
+                Some(stream) => stream,
+                None => return,
+            };
+            if self.peer.is_some() || (self.joined && argument != self.token) {
+                send_line(stream, "ERROR the game is full");
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+
+            let opponent = opposite(self.color);
+            send_line(stream, &format!("WELCOME {} {}", color_name(opponent), self.token));
+            send_line(stream, &position_message(&self.game));
+            if self.draw_offer == Some(self.color) {
+                send_line(stream, &format!("DRAW_OFFERED {}", color_name(self.color)));
+            }
+            println!("{}", if self.joined {"your opponent is back"} else {"your opponent joined"});
+            self.peer = Some(id);
+            self.joined = true;
+            return;
+        }
+
+        if self.peer != Some(id) {
+            if let Some(stream) = self.connections.get(&id) {
+                send_line(stream, "ERROR say HELLO first");
+            }
+            return;
+        }
+        if let Err(err) = self.apply(opposite(self.color), line) {
+            self.send(&format!("ERROR {}", err));
+        }
+    }
+
+    // false when the host wants to leave
+    fn local_line(&mut self, input: &str) -> bool {
+        match input {
+            "" => {}
+            "quit" | "exit" => return false,
+            "help" => println!("{}", NETWORK_HELP),
+            "board" => self.show(),
+            _ => {
+                let applied = local_command(&self.game, input).and_then(|command| self.apply(self.color, &command));
+                if let Err(err) = applied {
+                    println!("{}", err);
+                }
+            }
+        }
+
+        return true;
+    }
+
+    // carries out a protocol command from either side, the one place moves are checked
+    fn apply(&mut self, color: Color, command_line: &str) -> Result<(), String> {
+        let (command, argument) = command_line.split_once(' ').unwrap_or((command_line, ""));
+        if self.game.result().is_some() {
+            return Err(format!("the game is over"));
+        }
+
+        match command {
+            "MOVE" => {
+                if self.game.turn != color {
+                    return Err(format!("it isn't your turn"));
+                }
+                // looked up among find_all_legal_moves for the current position
+                let legal_move = parse_uci_move(&self.game, argument)?;
+                self.game.play_move(legal_move);
+                self.draw_offer = None;
+                self.send(&position_message(&self.game));
+                self.show();
+            }
+            "RESIGN" => self.game.resign(color),
+            "DRAW" => match self.draw_offer {
+                Some(offered_by) if offered_by != color => self.game.agree_draw(),
+                Some(_) => return Err(format!("you already offered a draw")),
+                None => {
+                    self.draw_offer = Some(color);
+                    self.send(&format!("DRAW_OFFERED {}", color_name(color)));
+                    if color == self.color {
+                        println!("draw offered");
+                    } else {
+                        println!("your opponent offers a draw, type draw to accept or decline to turn it down");
+                    }
+                }
+            },
+            "DECLINE" => match self.draw_offer {
+                Some(offered_by) if offered_by != color => {
+                    self.draw_offer = None;
+                    self.send(&format!("DRAW_DECLINED {}", color_name(color)));
+                    if color == self.color {
+                        println!("draw declined");
+                    } else {
+                        println!("your opponent declined the draw");
+                    }
+                }
+                _ => return Err(format!("there is no draw offer to decline")),
+            },
+            _ => return Err(format!("unknown command {}", command)),
+        }
+
+        return Ok(());
+    }
+}
+
+struct Guest {
+    address: String,
+    game: Game,
+    color: Option<Color>,
+    token: Option<String>,
+    stream: Option<TcpStream>,
+    connection_id: usize,
+    render_options: RenderOptions,
+    flip: bool, // the player asked to see the board from the other side
+}
+
+pub fn run_join(address: &str, render_options: RenderOptions, flip: bool) -> Result<(), String> {
+    let address = if address.contains(':') {address.to_string()} else {format!("{}:{}", address, DEFAULT_PORT)};
+    let (sender, events) = channel();
+
+    let mut guest = Guest {
+        address,
+        game: Game::new(),
+        color: None,
+        token: None,
+        stream: None,
+        connection_id: 0,
+        render_options,
+        flip,
+    };
+    guest.connect(&sender)?;
+    println!("connected to {}", guest.address);
+    spawn_stdin_reader(sender.clone());
+
+    while let Ok(event) = events.recv() {
+        let carry_on = match event {
+            Event::Remote(id, line) if id == guest.connection_id => guest.remote_line(&line),
+            Event::Disconnected(id) if id == guest.connection_id => {
+                guest.stream = None;
+                println!("lost the connection, trying to get back in");
+                guest.reconnect(&sender)?;
+                println!("reconnected");
+                true
+            }
+            Event::Local(line) => guest.local_line(line.trim()),
+            Event::LocalClosed => false,
+            _ => true, // left over from an earlier connection
+        };
+        if !carry_on {
+            break;
+        }
+    }
+
+    return Ok(());
+}
+
+impl Guest {
+    fn connect(&mut self, events: &Sender<Event>) -> Result<(), String> {
+        let stream = TcpStream::connect(&self.address).map_err(|err| format!("couldn't connect to {}: {}", self.address, err))?;
+        let reader = stream.try_clone().map_err(|err| err.to_string())?;
+
+        self.connection_id += 1;
+        spawn_socket_reader(self.connection_id, reader, events.clone());
+        match &self.token {
+            Some(token) => send_line(&stream, &format!("HELLO {}", token)),
+            None => send_line(&stream, "HELLO"),
+        }
+        self.stream = Some(stream);
+
+        return Ok(());
+    }
+
+    fn reconnect(&mut self, events: &Sender<Event>) -> Result<(), String> {
+        for _ in 0..RECONNECT_ATTEMPTS {
+            thread::sleep(Duration::from_secs(1));
+            if self.connect(events).is_ok() {
+                return Ok(());
+            }
+        }
+
+        return Err(format!("couldn't get back to {}", self.address));
+    }
+
+    fn show(&self) {
+        println!("{}", render_board(&self.game, &self.render_options, None, None));
+        if self.game.result().is_none() && self.color.is_some() {
+            if Some(self.game.turn) == self.color {
+                println!("your move");
+            } else {
+                println!("waiting for your opponent");
+            }
+        }
+    }
+
+    // false once the game is over or the host left
+    fn remote_line(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let color_word = |i: usize| match words.get(i) {
+            Some(&"white") => Some(White),
+            Some(&"black") => Some(Black),
+            _ => None,
+        };
+
+        match words.first() {
+            Some(&"WELCOME") => {
+                let color = color_word(1).unwrap_or(White);
+                if self.color.is_none() {
+                    println!("you play {:?}", color);
+                }
+                self.color = Some(color);
+                self.token = words.get(2).map(|token| token.to_string());
+                let own_side = if color == White {Perspective::White} else {Perspective::Black};
+                let other_side = if color == White {Perspective::Black} else {Perspective::White};
+                self.render_options.perspective = if self.flip {other_side} else {own_side};
+            }
+            Some(&"POSITION") => match parse_position(&words[1..]) {
+                Ok(game) => {
+                    self.game = game;
+                    self.show();
+                }
+                Err(err) => println!("the host sent a position we can't read: {}", err),
+            },
+            Some(&"DRAW_OFFERED") => {
+                if color_word(1) == self.color {
+                    println!("draw offered");
+                } else {
+                    println!("your opponent offers a draw, type draw to accept or decline to turn it down");
+                }
+            }
+            Some(&"DRAW_DECLINED") => {
+                if color_word(1) == self.color {
+                    println!("draw declined");
+                } else {
+                    println!("your opponent declined the draw");
+                }
+            }
+            Some(&"RESULT") => {
+                let termination = words.get(2).copied().unwrap_or("");
+                self.game.ended = GameResult::from_termination(termination, color_word(3));
+                match &self.game.ended {
+                    Some(result) => println!("{}", result),
+                    None => println!("{}", words.get(1).copied().unwrap_or("")),
+                }
+                println!("Game Over");
+                return false;
+            }
+            Some(&"ERROR") => println!("{}", line.trim_start_matches("ERROR").trim()),
+            Some(&"BYE") => {
+                println!("the host left the game");
+                return false;
+            }
+            _ => {}
+        }
+
+        return true;
+    }
+
+    // false when the player wants to leave
+    fn local_line(&mut self, input: &str) -> bool {
+        match input {
+            "" => {}
+            "quit" | "exit" => return false,
+            "help" => println!("{}", NETWORK_HELP),
+            "board" => self.show(),
+            _ => match local_command(&self.game, input) {
+                Ok(command) => match &self.stream {
+                    Some(stream) => send_line(stream, &command),
+                    None => println!("not connected"),
+                },
+                Err(err) => println!("{}", err),
+            },
+        }
+
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Client {
+        stream: TcpStream,
+        reader: BufReader<TcpStream>,
+    }
+
+    impl Client {
+        fn connect(port: u16) -> Client {
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            return Client {stream, reader};
+        }
+
+        fn send(&self, line: &str) {
+            send_line(&self.stream, line);
+        }
+
+        fn receive(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            return line.trim().to_string();
+        }
+    }
+
+    #[test]
+    fn two_players_over_localhost() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, events) = channel();
+        let host_input = sender.clone();
+        let host = thread::spawn(move || host_game(listener, Game::new(), White, RenderOptions::default(), sender, events));
+        let start = "POSITION fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves";
+
+        let mut black = Client::connect(port);
+        black.send("HELLO");
+        let welcome = black.receive();
+        let token = welcome.strip_prefix("WELCOME black ").unwrap().to_string();
+        assert_eq!(black.receive(), start);
+
+        host_input.send(Event::Local("e4".to_string())).unwrap();
+        assert_eq!(black.receive(), format!("{} e2e4", start));
+        black.send("MOVE e2e3");
+        assert!(black.receive().starts_with("ERROR"));
+        black.send("MOVE e7e5");
+        assert_eq!(black.receive(), format!("{} e2e4 e7e5", start));
+
+        let mut stranger = Client::connect(port);
+        stranger.send("HELLO");
+        assert_eq!(stranger.receive(), "ERROR the game is full");
+
+        black.send("DRAW");
+        assert_eq!(black.receive(), "DRAW_OFFERED black");
+        drop(black);
+
+        // the host may not have seen the disconnect yet
+        let mut back = Client::connect(port);
+        for _ in 0..100 {
+            back.send(&format!("HELLO {}", token));
+            let answer = back.receive();
+            if answer != "ERROR the game is full" {
+                assert_eq!(answer, format!("WELCOME black {}", token));
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+            back = Client::connect(port);
+        }
+        assert_eq!(back.receive(), format!("{} e2e4 e7e5", start));
+
+        host_input.send(Event::Local("draw".to_string())).unwrap();
+        assert_eq!(back.receive(), "RESULT 1/2-1/2 agreement");
+        assert!(host.join().unwrap().is_ok());
+    }
+}
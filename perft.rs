@@ -0,0 +1,92 @@
+use crate::piece::Color::*;
+use crate::piece::Color;
+use crate::board::Board;
+use crate::position::*;
+use crate::legal_moves::*;
+use crate::game::Game;
+use crate::game::play_move;
+
+// counts the leaf nodes of the legal move tree, the standard way to check a move generator
+pub fn perft(board: &Board, turn: Color, castling_rights: &CastlingRights, en_passant: &Option<Square>, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let legal_moves = find_all_legal_moves(board, turn, castling_rights, en_passant);
+    if depth == 1 {
+        return legal_moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for legal_move in legal_moves {
+        let (next_board, next_castling_rights, next_en_passant) = play_on_copy(board, castling_rights, &legal_move);
+        let opponent = if turn == White {Black} else {White};
+        nodes += perft(&next_board, opponent, &next_castling_rights, &next_en_passant, depth - 1);
+    }
+
+    return nodes;
+}
+
+// node counts below every legal move of the game's current position
+pub fn perft_divide(game: &Game, depth: u32) -> Vec<(Move, u64)> {
+    let mut res = vec![];
+    if depth == 0 {
+        return res;
+    }
+
+    let castling_rights = game.castling_rights();
+    let opponent = if game.turn == White {Black} else {White};
+    for legal_move in game.legal_moves() {
+        let (next_board, next_castling_rights, next_en_passant) = play_on_copy(&game.board, &castling_rights, &legal_move);
+        let nodes = perft(&next_board, opponent, &next_castling_rights, &next_en_passant, depth - 1);
+        res.push((legal_move, nodes));
+    }
+
+    return res;
+}
+
+fn play_on_copy(board: &Board, castling_rights: &CastlingRights, legal_move: &Move) -> (Board, CastlingRights, Option<Square>) {
+    let mut next_board = board.clone();
+    play_move(&mut next_board, legal_move.clone());
+
+    let mut next_castling_rights = *castling_rights;
+    update_castling_rights(&mut next_castling_rights, legal_move);
+
+    return (next_board, next_castling_rights, en_passant_target(legal_move));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+
+    // the published node counts for a position, from depth 1 on
+    fn check(fen: &str, expected: &[u64]) {
+        let start = parse_fen(fen).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            let depth = depth as u32 + 1;
+            assert_eq!(perft(&start.board, start.turn, &start.castling_rights, &start.en_passant, depth), *nodes, "{} depth {}", fen, depth);
+        }
+
+        let game = Game::from_start(start);
+        let depth = expected.len() as u32;
+        let divided: u64 = perft_divide(&game, depth).iter().map(|(_, nodes)| nodes).sum();
+        assert_eq!(divided, expected[expected.len() - 1]);
+    }
+
+    #[test]
+    fn start_position() {
+        check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        check("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]);
+    }
+
+    // en passant captures that would leave the king in check along the rank
+    #[test]
+    fn rook_and_pawn_endgame() {
+        check("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238]);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::piece::PieceType;
+use crate::piece::PieceType::*;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::legal_moves::*;
+use crate::game::Game;
+use crate::game::GameResult;
+use crate::game::StartPosition;
+use crate::game::play_move;
+use crate::fen::parse_fen;
+use crate::fen::game_to_fen;
+
+fn piece_letter(piece: PieceType) -> &'static str {
+    match piece {
+        King => "K",
+        Queen => "Q",
+        Bishop => "B",
+        Knight => "N",
+        Rook => "R",
+        _ => "",
+    }
+}
+
+fn letter_to_piece(c: char) -> Option<PieceType> {
+    match c {
+        'K' => Some(King),
+        'Q' => Some(Queen),
+        'B' => Some(Bishop),
+        'N' => Some(Knight),
+        'R' => Some(Rook),
+        _ => None,
+    }
+}
+
+// standard algebraic notation of a legal move in the game's current position, eg "Nbd7" or "exd8=Q+"
+pub fn move_to_san(game: &Game, played_move: &Move) -> String {
+    let mut res = String::new();
+
+    if played_move.special_move == Some(SpecialMoveType::CastleShort) {
+        res.push_str("O-O");
+    } else if played_move.special_move == Some(SpecialMoveType::CastleLong) {
+        res.push_str("O-O-O");
+    } else {
+        let is_capture = game.board[played_move.to.rank][played_move.to.file].piece != PieceType::Null
+            || played_move.special_move == Some(SpecialMoveType::EnPassant);
+
+        if played_move.piece.piece == Pawn {
+            if is_capture {
+                res.push_str(&square_name(&played_move.from)[..1]);
+            }
+        } else {
+            res.push_str(piece_letter(played_move.piece.piece));
+
+            // other pieces of the same kind that can reach the same square
+            let mut same_file = false;
+            let mut same_rank = false;
+            let mut ambiguous = false;
+            for legal_move in game.legal_moves() {
+                if legal_move.piece == played_move.piece && legal_move.to == played_move.to && legal_move.from != played_move.from {
+                    ambiguous = true;
+                    same_file |= legal_move.from.file == played_move.from.file;
+                    same_rank |= legal_move.from.rank == played_move.from.rank;
+                }
+            }
+
+            let from_name = square_name(&played_move.from);
+            if ambiguous && !same_file {
+                res.push_str(&from_name[..1]);
+            } else if ambiguous && !same_rank {
+                res.push_str(&from_name[1..]);
+            } else if ambiguous {
+                res.push_str(&from_name);
+            }
+        }
+
+        if is_capture {
+            res.push('x');
+        }
+        res.push_str(&square_name(&played_move.to));
+
+        if let Some(promotion) = played_move.promotion {
+            res.push('=');
+            res.push_str(piece_letter(promotion));
+        }
+    }
+
+    // look one move ahead for check and mate
+    let mut board_after = game.board;
+    play_move(&mut board_after, played_move.clone());
+    let mut castling_rights = game.castling_rights();
+    update_castling_rights(&mut castling_rights, played_move);
+    let opponent = if game.turn == White {Black} else {White};
+
+    if is_in_check(&board_after, opponent) {
+        if find_all_legal_moves(&board_after, opponent, &castling_rights, &en_passant_target(played_move)).is_empty() {
+            res.push('#');
+        } else {
+            res.push('+');
+        }
+    }
+
+    return res;
+}
+
+pub fn parse_san(game: &Game, san: &str) -> Result<Move, String> {
+    let cleaned = san.trim_end_matches(['+', '#', '!', '?']);
+
+    if cleaned == "O-O" || cleaned == "0-0" || cleaned == "O-O-O" || cleaned == "0-0-0" {
+        let special_move = if cleaned.len() == 3 {SpecialMoveType::CastleShort} else {SpecialMoveType::CastleLong};
+        for legal_move in game.legal_moves() {
+            if legal_move.special_move == Some(special_move.clone()) {
+                return Ok(legal_move);
+            }
+        }
+        return Err(format!("{} is not legal here", san));
+    }
+
+    let mut chars: Vec<char> = cleaned.chars().collect();
+
+    let piece = match chars.first().and_then(|c| letter_to_piece(*c)) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => Pawn,
+    };
+
+    // promotion is written "e8=Q", some programs leave out the '='
+    let mut promotion = None;
+    if let Some(last) = chars.last() {
+        if let Some(promoted_piece) = letter_to_piece(*last) {
+            promotion = Some(promoted_piece);
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    if chars.len() < 2 {
+        return Err(format!("can't read move '{}'", san));
+    }
+    let destination: String = chars[chars.len() - 2..].iter().collect();
+    let to = parse_square(&destination).map_err(|_| format!("can't read move '{}'", san))?;
+
+    // whatever is left between the piece and the destination narrows down where it came from
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in chars[..chars.len() - 2].iter() {
+        match c {
+            'a'..='h' => from_file = Some(*c as usize - 'a' as usize),
+            '1'..='8' => from_rank = Some(*c as usize - '1' as usize),
+            'x' | '-' | ':' => {}
+            _ => return Err(format!("can't read move '{}'", san)),
+        }
+    }
+
+    let mut candidates: Vec<Move> = vec![];
+    for legal_move in game.legal_moves() {
+        if legal_move.piece.piece == piece
+            && legal_move.to == to
+            && legal_move.promotion == promotion
+            && from_file.is_none_or(|file| legal_move.from.file == file)
+            && from_rank.is_none_or(|rank| legal_move.from.rank == rank) {
+            candidates.push(legal_move);
+        }
+    }
+
+    match candidates.len() {
+        1 => Ok(candidates.remove(0)),
+        0 => Err(format!("{} is not legal here", san)),
+        _ => Err(format!("{} is ambiguous", san)),
+    }
+}
+
+// SAN of every move played so far
+pub fn history_to_san(game: &Game) -> Vec<String> {
+    // replay the game from its start to get every move in its own position
+    let mut replay = Game::from_start(game.start.clone());
+    let mut res: Vec<String> = vec![];
+    for hmove in game.move_history.iter() {
+        res.push(move_to_san(&replay, &hmove.moveEntry));
+        replay.play_move(hmove.moveEntry.clone());
+    }
+
+    return res;
+}
+
+pub fn game_to_pgn(game: &Game) -> String {
+    return game_to_pgn_with_tags(game, &[]);
+}
+
+// the seven tag roster apart from Result, and what each says when unknown
+const ROSTER: [(&str, &str); 6] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+];
+
+// game_to_pgn with some tags filled in, tags outside the roster come after it, Result is
+// always the game's own
+pub fn game_to_pgn_with_tags(game: &Game, tags: &[(&str, String)]) -> String {
+    let result = match game.result() {
+        Some(result) => result.pgn_result(),
+        None => "*",
+    };
+    let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut res = String::new();
+    for (name, unknown) in ROSTER {
+        let value = tags.iter().find(|(tag, _)| *tag == name).map(|(_, value)| escape(value));
+        res.push_str(&format!("[{} \"{}\"]\n", name, value.unwrap_or(unknown.to_string())));
+    }
+    res.push_str(&format!("[Result \"{}\"]\n", result));
+    for (name, value) in tags {
+        if *name != "Result" && !ROSTER.iter().any(|(tag, _)| tag == name) {
+            res.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+    }
+    if game.start != StartPosition::classical() {
+        let start_game = Game::from_start(game.start.clone());
+        res.push_str("[SetUp \"1\"]\n");
+        res.push_str(&format!("[FEN \"{}\"]\n", game_to_fen(&start_game)));
+    }
+    res.push('\n');
+
+    let mut tokens: Vec<String> = vec![];
+    let mut turn = game.start.turn;
+    let mut fullmove_number = game.start.fullmove_number;
+    for san in history_to_san(game) {
+        if turn == White {
+            tokens.push(format!("{}.", fullmove_number));
+        } else if tokens.is_empty() {
+            tokens.push(format!("{}...", fullmove_number));
+        }
+        tokens.push(san);
+
+        if turn == Black {
+            fullmove_number += 1;
+        }
+        turn = if turn == White {Black} else {White};
+    }
+    tokens.push(result.to_string());
+
+    // keep lines under 80 characters
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > 79 {
+            res.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            res.push(' ');
+            line_len += 1;
+        }
+        line_len += token.len();
+        res.push_str(&token);
+    }
+    res.push('\n');
+
+    return res;
+}
+
+// every game in a PGN file, a tag after movetext starts the next one
+pub fn parse_pgn_games(pgn: &str) -> Result<Vec<Game>, String> {
+    let mut chunks: Vec<String> = vec![];
+    let mut chunk = String::new();
+    let mut in_movetext = false;
+    for line in pgn.lines() {
+        let is_tag = line.trim().starts_with('[');
+        if is_tag && in_movetext {
+            chunks.push(std::mem::take(&mut chunk));
+            in_movetext = false;
+        }
+        if !is_tag && !line.trim().is_empty() {
+            in_movetext = true;
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    if !chunk.trim().is_empty() {
+        chunks.push(chunk);
+    }
+
+    let mut games = vec![];
+    for (index, chunk) in chunks.iter().enumerate() {
+        games.push(parse_pgn(chunk).map_err(|err| format!("game {}: {}", index + 1, err))?);
+    }
+
+    return Ok(games);
+}
+
+// reads the first game of a PGN file, comments, variations and annotation glyphs are skipped
+pub fn parse_pgn(pgn: &str) -> Result<Game, String> {
+    let mut start = StartPosition::classical();
+    let mut movetext = String::new();
+
+    for line in pgn.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            let tag = &line[1..line.len() - 1];
+            if let Some((name, value)) = tag.split_once(' ') {
+                if name == "FEN" {
+                    start = parse_fen(value.trim().trim_matches('"'))?;
+                }
+            }
+            continue;
+        }
+        if line.starts_with('%') {
+            continue;
+        }
+        movetext.push_str(line);
+        movetext.push('\n');
+    }
+
+    let mut game = Game::from_start(start);
+    let mut comment_depth = 0; // inside {...}
+    let mut variation_depth = 0; // inside (...)
+    let mut token = String::new();
+    let mut in_line_comment = false;
+
+    let mut tokens: Vec<String> = vec![];
+    for c in movetext.chars() {
+        if in_line_comment {
+            if c == '\n' {
+                in_line_comment = false;
+            }
+            continue;
+        }
+        match c {
+            '{' => comment_depth += 1,
+            '}' => comment_depth -= 1,
+            _ if comment_depth > 0 => {}
+            ';' => in_line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            c if c.is_whitespace() => {
+                if !token.is_empty() {
+                    tokens.push(token.clone());
+                    token.clear();
+                }
+            }
+            _ => token.push(c),
+        }
+        if (c == '{' || c == '(' || c == ';') && !token.is_empty() {
+            tokens.push(token.clone());
+            token.clear();
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    for token in tokens {
+        match token.as_str() {
+            "1-0" | "0-1" => {
+                // a decisive result without mate on the board means somebody resigned
+                if game.result().is_none() {
+                    game.ended = Some(GameResult::Resignation(if token == "1-0" {White} else {Black}));
+                }
+                break;
+            }
+            "1/2-1/2" => {
+                // and a draw the rules don't call was agreed on
+                if game.result().is_none() {
+                    game.agree_draw();
+                }
+                break;
+            }
+            "*" => break,
+            _ => {}
+        }
+
+        // move numbers can be glued to the move, eg "1.e4"
+        let san = match token.rfind('.') {
+            Some(i) => &token[i + 1..],
+            None => token.as_str(),
+        };
+        if san.is_empty() || san.starts_with('$') {
+            continue;
+        }
+
+        let played_move = parse_san(&game, san)
+            .map_err(|err| format!("move {}: {}", game.fullmove_number(), err))?;
+        game.play_move(played_move);
+    }
+
+    Ok(game)
+}
@@ -1,10 +1,12 @@
 #[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     pub piece: PieceType,
     pub color: Color,
 }
 
 #[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     King,
     Queen,
@@ -16,6 +18,7 @@ pub enum PieceType {
 }
 
 #[derive(Eq, Hash, PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Black,
     White,
@@ -0,0 +1,118 @@
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::engine;
+use crate::book::BookPick;
+use crate::book::OpeningBook;
+use crate::random::Random;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerKind {
+    Human,
+    Engine(u32), // difficulty level
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Players {
+    pub white: PlayerKind,
+    pub black: PlayerKind,
+}
+
+impl Players {
+    pub fn humans() -> Players {
+        Players {white: PlayerKind::Human, black: PlayerKind::Human}
+    }
+
+    pub fn get(&self, color: Color) -> PlayerKind {
+        if color == White {self.white} else {self.black}
+    }
+
+    pub fn is_engine(&self, color: Color) -> bool {
+        return self.get(color) != PlayerKind::Human;
+    }
+
+    // the engine's reply for the side to move, from the book while it has one, None when a
+    // human is to move
+    pub fn engine_move(&self, game: &Game, book: Option<&OpeningBook>) -> Option<Move> {
+        match self.get(game.turn) {
+            PlayerKind::Engine(level) => {
+                let book_move = book.and_then(|book| book.choose(game, BookPick::Weighted, &mut Random::from_time()));
+                book_move.or_else(|| engine::search(game, &engine::limits_for_level(level)).best_move)
+            }
+            PlayerKind::Human => None,
+        }
+    }
+
+    // takes back moves until a human is to move again, so against the computer a full
+    // move pair goes, returns false if there was nothing to take back
+    pub fn undo(&self, game: &mut Game) -> bool {
+        if !game.undo() {
+            return false;
+        }
+        while self.is_engine(game.turn) && !self.is_engine(if game.turn == White {Black} else {White}) {
+            if !game.undo() {
+                break;
+            }
+        }
+
+        return true;
+    }
+
+    // the reverse of undo
+    pub fn redo(&self, game: &mut Game) -> bool {
+        if !game.redo() {
+            return false;
+        }
+        while self.is_engine(game.turn) && !self.is_engine(if game.turn == White {Black} else {White}) {
+            if !game.redo() {
+                break;
+            }
+        }
+
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::parse_uci_move;
+
+    #[test]
+    fn undo_against_the_engine_takes_back_both_plies() {
+        let players = Players {white: PlayerKind::Human, black: PlayerKind::Engine(1)};
+        let mut game = Game::new();
+        assert!(!players.undo(&mut game));
+
+        let start = game.board;
+        let human_move = parse_uci_move(&game, "e2e4").unwrap();
+        game.play_move(human_move);
+        let reply = players.engine_move(&game, None).unwrap();
+        game.play_move(reply);
+        let after_reply = game.board;
+
+        assert!(players.undo(&mut game));
+        assert_eq!(game.board, start);
+        assert_eq!(game.turn, White);
+        assert!(game.move_history.is_empty());
+
+        assert!(players.redo(&mut game));
+        assert_eq!(game.board, after_reply);
+        assert_eq!(game.turn, White);
+        assert_eq!(game.move_history.len(), 2);
+    }
+
+    #[test]
+    fn undo_between_humans_takes_back_one_ply() {
+        let mut game = Game::new();
+        let human_move = parse_uci_move(&game, "e2e4").unwrap();
+        game.play_move(human_move);
+        let reply = parse_uci_move(&game, "e7e5").unwrap();
+        game.play_move(reply);
+
+        assert!(Players::humans().undo(&mut game));
+        assert_eq!(game.turn, Black);
+        assert_eq!(game.move_history.len(), 1);
+    }
+}
@@ -1,7 +1,10 @@
-use crate::Piece;
-use crate::Board;
+use crate::piece::Piece;
+use crate::board::Board;
+use crate::piece::PieceType;
 
-#[derive(Debug, Clone, PartialEq)]
+// with the serde feature squares, moves and history entries are written in a compact form,
+// see serialize.rs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Square {
     pub file: usize,       //vertical
     pub rank: usize,       //horizontal
@@ -13,6 +16,7 @@ pub struct Move {
     pub to: Square,
     pub piece: Piece,
     pub special_move: Option<SpecialMoveType>,
+    pub promotion: Option<PieceType>, // the piece a promoting pawn turns into
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,7 +26,20 @@ pub struct MoveHistoryEntry {
     pub isCaptureMove: bool,
 }
 
+// which castling moves are still allowed, losing a right is permanent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CastlingRights {
+    pub white_long: bool,
+    pub white_short: bool,
+    pub black_long: bool,
+    pub black_short: bool,
+}
+
+pub const NO_CASTLING: CastlingRights = CastlingRights {white_long: false, white_short: false, black_long: false, black_short: false};
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecialMoveType {
     PawnLongMove, // eg e2->e4, moving two squares at the start
     EnPassant,
@@ -43,7 +60,7 @@ pub fn move_right(current_square: Square) -> Result<Square, ()> {
 }
 
 pub fn move_left(current_square: Square) -> Result<Square, ()> {
-    if current_square.file <= 0 {
+    if current_square.file == 0 {
         return Err(());
     }
 
@@ -54,7 +71,7 @@ pub fn move_left(current_square: Square) -> Result<Square, ()> {
 }
 
 pub fn move_down(current_square: Square) -> Result<Square, ()> {
-    if current_square.rank <= 0 {
+    if current_square.rank == 0 {
         return Err(());
     }
 
@@ -132,4 +149,35 @@ pub fn move_down_down_right(current_square: Square) -> Result<Square, ()> {
 
 pub fn move_down_right_right(current_square: Square) -> Result<Square, ()> {
     move_down(move_right(move_right(current_square)?)?)
-}
\ No newline at end of file
+}
+// algebraic name of a square, eg "e4"
+pub fn square_name(square: &Square) -> String {
+    let file_char = (b'a' + square.file as u8) as char;
+    return format!("{}{}", file_char, square.rank + 1);
+}
+
+pub fn parse_square(name: &str) -> Result<Square, ()> {
+    let mut chars = name.chars();
+    let file_char = chars.next().ok_or(())?.to_ascii_lowercase();
+    let rank_char = chars.next().ok_or(())?;
+    if chars.next().is_some() || !('a'..='h').contains(&file_char) || !('1'..='8').contains(&rank_char) {
+        return Err(());
+    }
+
+    Ok(Square {
+        file: (file_char as u8 - b'a') as usize,
+        rank: (rank_char as u8 - b'1') as usize,
+    })
+}
+
+// long algebraic notation as used by UCI, eg "e2e4" or "e7e8q"
+pub fn move_to_uci(uci_move: &Move) -> String {
+    let promotion = match uci_move.promotion {
+        Some(PieceType::Queen) => "q",
+        Some(PieceType::Rook) => "r",
+        Some(PieceType::Bishop) => "b",
+        Some(PieceType::Knight) => "n",
+        _ => "",
+    };
+    return format!("{}{}{}", square_name(&uci_move.from), square_name(&uci_move.to), promotion);
+}
@@ -0,0 +1,43 @@
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+// small xorshift generator, good enough for picking colors and adding noise to the engine
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn from_seed(seed: u64) -> Random {
+        // xorshift gets stuck on zero
+        Random {state: if seed == 0 {0x9e3779b97f4a7c15} else {seed}}
+    }
+
+    pub fn from_time() -> Random {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        return Random::from_seed(nanos ^ 0x2545f4914f6cdd1d);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        return self.state;
+    }
+
+    // uniform in 0..bound
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return 0;
+        }
+        return self.next_u64() % bound;
+    }
+
+    // uniform in -amplitude..=amplitude
+    pub fn spread(&mut self, amplitude: i32) -> i32 {
+        if amplitude <= 0 {
+            return 0;
+        }
+        return self.below(2 * amplitude as u64 + 1) as i32 - amplitude;
+    }
+}
@@ -0,0 +1,186 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::PieceType::*;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::fen::piece_to_fen_char;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Perspective {
+    White,
+    Black,
+    SideToMove, // turns the board around every move
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub perspective: Perspective,
+    pub ascii: bool,  // letters instead of chess glyphs
+    pub colors: bool, // ANSI colored squares
+    pub highlight_last_move: bool,
+    pub highlight_check: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            perspective: Perspective::White,
+            ascii: false,
+            colors: false,
+            highlight_last_move: true,
+            highlight_check: true,
+        }
+    }
+}
+
+impl RenderOptions {
+    // true when rank 1 should be drawn at the top
+    pub fn is_flipped(&self, turn: Color) -> bool {
+        match self.perspective {
+            Perspective::White => false,
+            Perspective::Black => true,
+            Perspective::SideToMove => turn == Black,
+        }
+    }
+}
+
+// 256 color palette backgrounds
+const LIGHT_SQUARE: u8 = 223;
+const DARK_SQUARE: u8 = 173;
+const LIGHT_LAST_MOVE: u8 = 229;
+const DARK_LAST_MOVE: u8 = 185;
+const CHECK_SQUARE: u8 = 203;
+const LIGHT_DESTINATION: u8 = 151;
+const DARK_DESTINATION: u8 = 108;
+const CURSOR_SQUARE: u8 = 117;
+
+pub fn piece_glyph(piece: Piece, options: &RenderOptions) -> char {
+    if piece.piece == PieceType::Null {
+        return if options.ascii {'.'} else {'·'};
+    }
+    if options.ascii {
+        return piece_to_fen_char(piece);
+    }
+
+    // for black piece unicodes, add 6
+    let mut piece_unicode = match piece.piece {
+        King => 0x2654,
+        Queen => 0x2655,
+        Rook => 0x2656,
+        Bishop => 0x2657,
+        Knight => 0x2658,
+        _ => 0x2659,
+    };
+    // with colors on, the foreground tells the sides apart so the filled glyphs are used
+    // for both, without them White gets the filled glyphs which read better on dark terminals
+    if piece.color == White || options.colors {
+        piece_unicode += 6;
+    }
+
+    return char::from_u32(piece_unicode).unwrap();
+}
+
+// draws the game's board, `selected` marks every legal destination of the piece on that
+// square and `cursor` outlines a square for front ends that move a cursor around
+pub fn render_board(game: &Game, options: &RenderOptions, selected: Option<&Square>, cursor: Option<&Square>) -> String {
+    let flipped = options.is_flipped(game.turn);
+
+    let last_move = match game.move_history.last() {
+        Some(entry) if options.highlight_last_move => Some(entry.moveEntry.clone()),
+        _ => None,
+    };
+
+    let mut checked_king = None;
+    if options.highlight_check && game.is_in_check() {
+        for rank in 0..8 {
+            for file in 0..8 {
+                if game.board[rank][file] == (Piece {piece: King, color: game.turn}) {
+                    checked_king = Some(Square {file, rank});
+                }
+            }
+        }
+    }
+
+    let mut destinations: Vec<Square> = vec![];
+    if let Some(from) = selected {
+        for legal_move in game.legal_moves() {
+            if legal_move.from == *from {
+                destinations.push(legal_move.to);
+            }
+        }
+    }
+
+    let files: Vec<usize> = if flipped {(0..8).rev().collect()} else {(0..8).collect()};
+    let ranks: Vec<usize> = if flipped {(0..8).collect()} else {(0..8).rev().collect()};
+
+    let mut file_labels = String::from("  ");
+    for file in files.iter() {
+        file_labels.push_str(&format!(" {} ", (b'a' + *file as u8) as char));
+    }
+
+    let mut res = String::new();
+    res.push_str(&file_labels);
+    res.push('\n');
+
+    for rank in ranks.iter() {
+        res.push_str(&format!("{} ", rank + 1));
+        for file in files.iter() {
+            let square = Square {file: *file, rank: *rank};
+            let piece = game.board[*rank][*file];
+            let is_last_move = last_move.as_ref().is_some_and(|m| m.from == square || m.to == square);
+            let is_check = checked_king.as_ref() == Some(&square);
+            let is_destination = destinations.contains(&square);
+            let is_cursor = cursor == Some(&square);
+
+            if options.colors {
+                // a1 is a dark square
+                let light = (rank + file) % 2 == 1;
+                let background = if is_cursor {
+                    CURSOR_SQUARE
+                } else if is_check {
+                    CHECK_SQUARE
+                } else if is_destination {
+                    if light {LIGHT_DESTINATION} else {DARK_DESTINATION}
+                } else if is_last_move {
+                    if light {LIGHT_LAST_MOVE} else {DARK_LAST_MOVE}
+                } else if light {
+                    LIGHT_SQUARE
+                } else {
+                    DARK_SQUARE
+                };
+                let foreground = if piece.color == White {231} else {16};
+                let glyph = if piece.piece == PieceType::Null {
+                    if is_destination {'•'} else {' '}
+                } else {
+                    piece_glyph(piece, options)
+                };
+                res.push_str(&format!("\x1b[48;5;{};38;5;{};1m {} \x1b[0m", background, foreground, glyph));
+            } else {
+                // without colors the markers go around the piece
+                let glyph = piece_glyph(piece, options);
+                let (left, right) = if is_cursor {
+                    ('>', '<')
+                } else if is_check {
+                    ('!', '!')
+                } else if is_destination {
+                    ('*', '*')
+                } else if is_last_move {
+                    ('[', ']')
+                } else {
+                    (' ', ' ')
+                };
+                if is_destination && !is_cursor && piece.piece == PieceType::Null {
+                    res.push_str(" * ");
+                } else {
+                    res.push_str(&format!("{}{}{}", left, glyph, right));
+                }
+            }
+        }
+        res.push_str(&format!(" {}\n", rank + 1));
+    }
+    res.push_str(&file_labels);
+
+    return res;
+}
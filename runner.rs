@@ -0,0 +1,708 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::game::GameResult;
+use crate::game::evaluate_timeout;
+use crate::pgn::*;
+use crate::render::*;
+use crate::clock::Clock;
+use crate::engine;
+use crate::uci::parse_uci_move;
+use crate::uci_engine::UciEngine;
+use crate::uci_engine::EngineScore;
+use crate::network::position_message;
+use crate::network::result_message;
+use crate::random::Random;
+use crate::events::GameEvent;
+use crate::events::Observers;
+use crate::book::BookPick;
+use crate::book::OpeningBook;
+use crate::endgame::Dtm;
+use crate::endgame::EndgameTables;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+// what a player gets to see when it is asked for something
+pub struct GameView<'a> {
+    pub game: &'a Game,
+    pub color: Color, // the side of the player being asked
+    pub clock: &'a Clock,
+}
+
+// one side of a game, GameRunner asks it for moves and tells it what happens
+pub trait Player {
+    fn name(&self) -> String;
+
+    // the move to play in view.game, None to resign
+    fn choose_move(&mut self, view: &GameView) -> Option<Move>;
+
+    // asked right after choose_move, true to offer a draw along with the move
+    fn offers_draw(&mut self) -> bool {
+        return false;
+    }
+
+    // the opponent played `played`, view.game is the position after it
+    fn opponent_moved(&mut self, _view: &GameView, _played: &Move) {}
+
+    // the opponent offers a draw, true to accept it
+    fn draw_offered(&mut self, _view: &GameView) -> bool {
+        return false;
+    }
+
+    fn game_over(&mut self, _view: &GameView, _result: &GameResult) {}
+
+    // what the player thought of the position before its last move, from its own side, for
+    // players that search
+    fn last_score(&self) -> Option<EngineScore> {
+        return None;
+    }
+}
+
+fn opposite(color: Color) -> Color {
+    if color == White {Black} else {White}
+}
+
+fn side_index(color: Color) -> usize {
+    if color == White {0} else {1}
+}
+
+// ends engine games early once both engines agree on how they will end, scores are in
+// centipawns and moves count each engine's own moves
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adjudication {
+    pub resign_score: i32, // a win once both see one side this far ahead
+    pub resign_moves: usize, // for this many moves in a row
+    pub draw_score: i32, // a draw once both see neither side further ahead than this
+    pub draw_moves: usize,
+    pub draw_from_move: u32, // but not before this move
+    pub max_moves: Option<u32>, // a draw once the game gets longer than this
+    pub tables: Option<Arc<EndgameTables>>, // the exact result once the position is in these
+}
+
+impl Default for Adjudication {
+    fn default() -> Adjudication {
+        Adjudication {
+            resign_score: 1000,
+            resign_moves: 3,
+            draw_score: 10,
+            draw_moves: 8,
+            draw_from_move: 40,
+            max_moves: Some(200),
+            tables: None,
+        }
+    }
+}
+
+// plays a game between any two players, checking every move and keeping the clock
+pub struct GameRunner {
+    pub game: Game,
+    pub clock: Clock,
+    pub players: [Box<dyn Player>; 2], // White, Black
+    pub observers: Observers,
+    pub adjudication: Option<Adjudication>,
+    pub adjudicated: bool, // whether the result came from adjudication
+    scores: Vec<Option<i32>>, // the mover's score for every ply played, from White's side
+}
+
+impl GameRunner {
+    pub fn new(game: Game, clock: Clock, white: Box<dyn Player>, black: Box<dyn Player>) -> GameRunner {
+        GameRunner {
+            game,
+            clock,
+            players: [white, black],
+            observers: Observers::default(),
+            adjudication: None,
+            adjudicated: false,
+            scores: vec![],
+        }
+    }
+
+    // plays until the game has a result, a player that hands back an illegal move loses as if
+    // it resigned, nobody is trusted to know the rules
+    pub fn run(&mut self) -> GameResult {
+        // a game picked up in the middle has its clock running already
+        if !self.game.move_history.is_empty() {
+            self.clock.start(self.game.turn);
+        }
+
+        while self.game.result().is_none() {
+            let color = self.game.turn;
+            let side = side_index(color);
+            let choice = self.players[side].choose_move(&GameView {game: &self.game, color, clock: &self.clock});
+
+            if let Some(flagged) = self.clock.flagged() {
+                self.game.ended = Some(evaluate_timeout(&self.game.board, flagged));
+                break;
+            }
+            let legal_move = choice.and_then(|chosen| self.game.find_legal_move(&chosen.from, &chosen.to, chosen.promotion));
+            let legal_move = match legal_move {
+                Some(legal_move) => legal_move,
+                None => {
+                    self.game.resign(color);
+                    break;
+                }
+            };
+            let offers_draw = self.players[side].offers_draw();
+
+            self.game.play_move(legal_move.clone());
+            self.observers.move_played(&self.game);
+            if self.game.result().is_some() {
+                break;
+            }
+            let score = self.players[side].last_score().map(|score| score.centipawns());
+            self.scores.push(score.map(|score| if color == White {score} else {-score}));
+            if let Some(result) = self.adjudicate() {
+                self.game.ended = Some(result);
+                self.adjudicated = true;
+                break;
+            }
+            self.clock.press(color);
+
+            let view = GameView {game: &self.game, color: opposite(color), clock: &self.clock};
+            self.players[1 - side].opponent_moved(&view, &legal_move);
+            if offers_draw {
+                self.observers.notify(&GameEvent::DrawOffered(color), &self.game);
+                if self.players[1 - side].draw_offered(&view) {
+                    self.game.agree_draw();
+                }
+            }
+        }
+
+        self.clock.stop();
+        let result = self.game.result().unwrap();
+        self.observers.notify(&GameEvent::GameEnded(result.clone()), &self.game);
+        for color in [White, Black] {
+            self.players[side_index(color)].game_over(&GameView {game: &self.game, color, clock: &self.clock}, &result);
+        }
+
+        return result;
+    }
+
+    fn adjudicate(&self) -> Option<GameResult> {
+        let adjudication = self.adjudication.as_ref()?;
+        if let Some(dtm) = adjudication.tables.as_ref().and_then(|tables| tables.probe(&self.game)) {
+            let turn = self.game.turn;
+            return Some(match dtm {
+                Dtm::Win(_) => GameResult::Resignation(turn),
+                Dtm::Loss(_) => GameResult::Resignation(opposite(turn)),
+                Dtm::Draw => GameResult::DrawAgreed,
+            });
+        }
+        // the scores of the last few plies, if there are that many and all of them have one
+        let recent = |moves: usize| -> Option<Vec<i32>> {
+            let plies = moves * 2;
+            if plies == 0 || self.scores.len() < plies {
+                return None;
+            }
+            return self.scores[self.scores.len() - plies..].iter().copied().collect();
+        };
+
+        if let Some(scores) = recent(adjudication.resign_moves) {
+            if scores.iter().all(|score| *score >= adjudication.resign_score) {
+                return Some(GameResult::Resignation(White));
+            }
+            if scores.iter().all(|score| *score <= -adjudication.resign_score) {
+                return Some(GameResult::Resignation(Black));
+            }
+        }
+        if self.game.fullmove_number() >= adjudication.draw_from_move {
+            if let Some(scores) = recent(adjudication.draw_moves) {
+                if scores.iter().all(|score| score.abs() <= adjudication.draw_score) {
+                    return Some(GameResult::DrawAgreed);
+                }
+            }
+        }
+        if adjudication.max_moves.is_some_and(|max_moves| self.game.fullmove_number() > max_moves) {
+            return Some(GameResult::DrawAgreed);
+        }
+
+        return None;
+    }
+}
+
+// somebody at the terminal typing moves in
+pub struct TerminalPlayer {
+    pub render_options: RenderOptions,
+    offering_draw: bool,
+}
+
+impl TerminalPlayer {
+    pub fn new(render_options: RenderOptions) -> TerminalPlayer {
+        TerminalPlayer {render_options, offering_draw: false}
+    }
+}
+
+const TERMINAL_HELP: &str = "\
+moves are entered as coordinates (e2e4) or in SAN (Nf3)
+  draw      offer a draw with your next move
+  resign    give up the game";
+
+impl Player for TerminalPlayer {
+    fn name(&self) -> String {
+        return format!("human");
+    }
+
+    fn choose_move(&mut self, view: &GameView) -> Option<Move> {
+        println!("{}", render_board(view.game, &self.render_options, None, None));
+        if view.clock.time_control.is_some() {
+            println!("White {}  Black {}", view.clock.display(White), view.clock.display(Black));
+        }
+        println!("{:?} to move", view.color);
+
+        loop {
+            let line = read_input_line()?;
+            match line.trim() {
+                "" => {}
+                "help" => println!("{}", TERMINAL_HELP),
+                "resign" => return None,
+                "draw" => {
+                    self.offering_draw = true;
+                    println!("you offer a draw with your move");
+                }
+                input => match input_move(view.game, input) {
+                    Ok(legal_move) => return Some(legal_move),
+                    Err(err) => println!("{}", err),
+                },
+            }
+        }
+    }
+
+    fn offers_draw(&mut self) -> bool {
+        return std::mem::take(&mut self.offering_draw);
+    }
+
+    fn opponent_moved(&mut self, view: &GameView, played: &Move) {
+        let san = history_to_san(view.game).pop().unwrap_or_else(|| move_to_uci(played));
+        println!("{:?} plays {}", opposite(view.color), san);
+    }
+
+    fn draw_offered(&mut self, _view: &GameView) -> bool {
+        println!("your opponent offers a draw, accept? (y/n)");
+        return read_input_line().is_some_and(|answer| answer.trim().to_lowercase().starts_with('y'));
+    }
+
+    // the result itself is left to whoever ran the game
+    fn game_over(&mut self, view: &GameView, _result: &GameResult) {
+        println!("{}", render_board(view.game, &self.render_options, None, None));
+    }
+}
+
+// the built-in engine at one of its levels
+pub struct EnginePlayer {
+    pub level: u32,
+    pub book: Option<Rc<OpeningBook>>, // played from for as long as it knows the position
+    pub endgame: Option<Arc<EndgameTables>>,
+    score: Option<EngineScore>,
+}
+
+impl EnginePlayer {
+    pub fn new(level: u32) -> EnginePlayer {
+        EnginePlayer {level, book: None, endgame: None, score: None}
+    }
+}
+
+impl Player for EnginePlayer {
+    fn name(&self) -> String {
+        return format!("rustChess level {}", self.level);
+    }
+
+    fn choose_move(&mut self, view: &GameView) -> Option<Move> {
+        if let Some(book_move) = self.book.as_ref().and_then(|book| book.choose(view.game, BookPick::Weighted, &mut Random::from_time())) {
+            self.score = None;
+            return Some(book_move);
+        }
+
+        let mut limits = engine::limits_for_level(self.level);
+        limits.endgame = self.endgame.clone();
+        // with a clock running, never more than a slice of what is left on it
+        if let Some(time_control) = view.clock.time_control {
+            let budget = view.clock.current(view.color) / 30 + time_control.increment / 2;
+            limits.time = Some(limits.time.map_or(budget, |time| time.min(budget)));
+        }
+
+        let result = engine::search(view.game, &limits);
+        self.score = Some(match engine::mate_in(result.score) {
+            Some(moves) => EngineScore::Mate(moves),
+            None => EngineScore::Centipawns(result.score),
+        });
+        return result.best_move;
+    }
+
+    fn last_score(&self) -> Option<EngineScore> {
+        return self.score;
+    }
+}
+
+// how long an external engine may think when there is no time control
+pub const DEFAULT_MOVE_TIME: Duration = Duration::from_secs(1);
+// on top of the engine's own time, before it is taken to have hung
+const ENGINE_GRACE: Duration = Duration::from_secs(5);
+
+// another program speaking UCI
+pub struct UciEnginePlayer {
+    pub engine: UciEngine,
+    pub move_time: Duration, // used when the game has no time control
+    score: Option<EngineScore>,
+}
+
+impl UciEnginePlayer {
+    pub fn spawn(command: &str) -> Result<UciEnginePlayer, String> {
+        let mut engine = UciEngine::spawn(command)?;
+        engine.new_game()?;
+        return Ok(UciEnginePlayer {engine, move_time: DEFAULT_MOVE_TIME, score: None});
+    }
+}
+
+impl Player for UciEnginePlayer {
+    fn name(&self) -> String {
+        return self.engine.name.clone();
+    }
+
+    fn choose_move(&mut self, view: &GameView) -> Option<Move> {
+        let (go_arguments, timeout) = match view.clock.time_control {
+            Some(time_control) => (
+                format!("wtime {} btime {} winc {} binc {}",
+                    view.clock.current(White).as_millis(), view.clock.current(Black).as_millis(),
+                    time_control.increment.as_millis(), time_control.increment.as_millis()),
+                view.clock.current(view.color) + ENGINE_GRACE,
+            ),
+            None => (format!("movetime {}", self.move_time.as_millis()), self.move_time + ENGINE_GRACE),
+        };
+
+        match self.engine.best_move(view.game, &go_arguments, timeout) {
+            Ok(engine_move) => {
+                self.score = engine_move.score;
+                Some(engine_move.best_move)
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    fn last_score(&self) -> Option<EngineScore> {
+        return self.score;
+    }
+}
+
+// a player who joined over the network with `chess join`, speaking the protocol in network.rs
+pub struct NetworkPlayer {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+    offering_draw: bool,
+    pending: Option<String>, // a line read while waiting for something else
+}
+
+impl NetworkPlayer {
+    // waits on `port` for somebody to join and seats them as `color`
+    pub fn accept(port: u16, color: Color, game: &Game) -> Result<NetworkPlayer, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|err| format!("couldn't listen on port {}: {}", port, err))?;
+        println!("waiting for {:?} to join on port {}", color, port);
+
+        loop {
+            let (stream, _) = listener.accept().map_err(|err| err.to_string())?;
+            let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+            let mut hello = String::new();
+            if reader.read_line(&mut hello).is_err() || !hello.starts_with("HELLO") {
+                continue;
+            }
+
+            let mut player = NetworkPlayer {reader, stream, offering_draw: false, pending: None};
+            let token = format!("{:016x}", Random::from_time().next_u64());
+            player.send(&format!("WELCOME {} {}", if color == White {"white"} else {"black"}, token));
+            player.send(&position_message(game));
+            println!("{:?} joined", color);
+            return Ok(player);
+        }
+    }
+
+    fn send(&mut self, line: &str) {
+        let _ = writeln!(self.stream, "{}", line);
+    }
+
+    // None once the peer has gone
+    fn next_line(&mut self) -> Option<String> {
+        if let Some(line) = self.pending.take() {
+            return Some(line);
+        }
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+}
+
+impl Player for NetworkPlayer {
+    fn name(&self) -> String {
+        return match self.stream.peer_addr() {
+            Ok(address) => address.to_string(),
+            Err(_) => format!("network"),
+        };
+    }
+
+    fn choose_move(&mut self, view: &GameView) -> Option<Move> {
+        loop {
+            let line = self.next_line()?;
+            let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+            match command {
+                "MOVE" => match parse_uci_move(view.game, argument) {
+                    Ok(legal_move) => return Some(legal_move),
+                    Err(err) => self.send(&format!("ERROR {}", err)),
+                },
+                "RESIGN" => return None,
+                "DRAW" => self.offering_draw = true,
+                "DECLINE" => {}
+                _ => self.send(&format!("ERROR unknown command {}", command)),
+            }
+        }
+    }
+
+    fn offers_draw(&mut self) -> bool {
+        return std::mem::take(&mut self.offering_draw);
+    }
+
+    fn opponent_moved(&mut self, view: &GameView, _played: &Move) {
+        self.send(&position_message(view.game));
+    }
+
+    // anything but DRAW turns it down, a move sent instead is kept for choose_move
+    fn draw_offered(&mut self, view: &GameView) -> bool {
+        let offered_by = if view.color == White {"black"} else {"white"};
+        self.send(&format!("DRAW_OFFERED {}", offered_by));
+        let line = match self.next_line() {
+            Some(line) => line,
+            None => return false,
+        };
+        if line == "DRAW" {
+            return true;
+        }
+        self.send(&format!("DRAW_DECLINED {}", if view.color == White {"white"} else {"black"}));
+        if line != "DECLINE" {
+            self.pending = Some(line);
+        }
+
+        return false;
+    }
+
+    fn game_over(&mut self, view: &GameView, result: &GameResult) {
+        self.send(&position_message(view.game));
+        self.send(&result_message(result));
+    }
+}
+
+pub fn read_input_line() -> Option<String> {
+    let mut user_input = String::new();
+    match std::io::stdin().read_line(&mut user_input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(user_input),
+    }
+}
+
+// a move typed at the terminal, coordinates first and anything else tried as SAN, a
+// promotion without a piece asks for one
+pub fn input_move(game: &Game, user_input: &str) -> Result<Move, String> {
+    match parse_input_move(user_input) {
+        Ok(input_move) => {
+            let mut legal_move = game.find_legal_move(&input_move.from, &input_move.to, input_move.promotion)
+                .ok_or_else(|| format!("illegal move, pick another"))?;
+            if input_move.promotion.is_none() && legal_move.promotion.is_some() {
+                legal_move.promotion = Some(prompt_promotion());
+            }
+            return Ok(legal_move);
+        }
+        Err(_) => return parse_san(game, user_input).map_err(|_| format!("illegal move format, type help for the list of commands")),
+    }
+}
+
+pub fn parse_input_move(user_input: &str) -> Result<Move, ()> {
+    let file_to_num: HashMap<char, usize> = HashMap::from([
+        ('a', 0), ('A', 0),
+        ('b', 1), ('B', 1),
+        ('c', 2), ('C', 2),
+        ('d', 3), ('D', 3),
+        ('e', 4), ('E', 4),
+        ('f', 5), ('F', 5),
+        ('g', 6), ('G', 6),
+        ('h', 7), ('H', 7),
+    ]);
+
+    Ok(Move {
+        from: Square {
+            file: *file_to_num.get(&user_input.chars().next().ok_or(())?).ok_or(())?,
+            rank: user_input.chars().nth(1).ok_or(())?.to_digit(10).ok_or(())?.checked_sub(1).ok_or(())? as usize,
+        },
+        to: Square {
+            file: *file_to_num.get(&user_input.chars().nth(2).ok_or(())?).ok_or(())?,
+            rank: user_input.chars().nth(3).ok_or(())?.to_digit(10).ok_or(())?.checked_sub(1).ok_or(())? as usize,
+        },
+        // piece and special_move fields are in legal_moves array, dont need to find them here
+        piece: Piece {piece: PieceType::Null, color: Color::Null},
+        special_move: None,
+        promotion: match user_input.chars().nth(4) {
+            Some('q') | Some('Q') => Some(PieceType::Queen),
+            Some('r') | Some('R') => Some(PieceType::Rook),
+            Some('b') | Some('B') => Some(PieceType::Bishop),
+            Some('n') | Some('N') => Some(PieceType::Knight),
+            Some(_) => return Err(()),
+            None => None,
+        },
+    })
+}
+
+pub fn prompt_promotion() -> PieceType {
+    loop {
+       println!("Which piece would you like to promote to (enter Q,K,B,R)");
+
+        let mut user_input = String::new();
+        let _b = match std::io::stdin().read_line(&mut user_input) {
+            Ok(v) => v,
+            _ => {println!("Illegal input, please enter Q, K, B, or R"); continue}
+        };
+
+        if ['Q', 'K', 'B', 'R'].contains(&user_input.chars().next().unwrap()) {
+            match user_input.chars().next().unwrap() {
+                'K' => return PieceType::Knight,
+                'B' => return PieceType::Bishop,
+                'R' => return PieceType::Rook,
+                _ => return PieceType::Queen,
+            }
+        }
+
+        println!("Illegal input, please enter Q, K, B, or R");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+
+    // plays the moves it was given in order whether they are legal or not, and resigns
+    // once they run out
+    struct ScriptedPlayer {
+        moves: Vec<&'static str>,
+        played: usize,
+        score: Option<i32>,
+        offer_draw: bool,
+        accept_draw: bool,
+    }
+
+    fn scripted(moves: &[&'static str]) -> ScriptedPlayer {
+        return ScriptedPlayer {moves: moves.to_vec(), played: 0, score: None, offer_draw: false, accept_draw: false};
+    }
+
+    impl Player for ScriptedPlayer {
+        fn name(&self) -> String {
+            return format!("scripted");
+        }
+
+        fn choose_move(&mut self, _view: &GameView) -> Option<Move> {
+            let text = self.moves.get(self.played)?;
+            self.played += 1;
+            return parse_input_move(text).ok();
+        }
+
+        fn offers_draw(&mut self) -> bool {
+            return self.offer_draw;
+        }
+
+        fn draw_offered(&mut self, _view: &GameView) -> bool {
+            return self.accept_draw;
+        }
+
+        fn last_score(&self) -> Option<EngineScore> {
+            return self.score.map(EngineScore::Centipawns);
+        }
+    }
+
+    fn game_between(fen: &str, white: ScriptedPlayer, black: ScriptedPlayer) -> GameRunner {
+        let game = Game::from_start(parse_fen(fen).unwrap());
+        return GameRunner::new(game, Clock::new(None), Box::new(white), Box::new(black));
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn an_illegal_move_loses() {
+        let mut runner = game_between(START, scripted(&["e2e4", "g1f3"]), scripted(&["e7e5", "e8e6"]));
+        assert_eq!(runner.run(), GameResult::Resignation(White));
+        assert_eq!(runner.game.move_history.len(), 3);
+        assert!(!runner.adjudicated);
+
+        let mut runner = game_between(START, scripted(&["e2e5"]), scripted(&["e7e5"]));
+        assert_eq!(runner.run(), GameResult::Resignation(Black));
+        assert!(runner.game.move_history.is_empty());
+    }
+
+    #[test]
+    fn a_draw_offer_ends_the_game_once_accepted() {
+        let mut white = scripted(&["e2e4", "g1f3"]);
+        white.offer_draw = true;
+        let mut runner = game_between(START, white, scripted(&["e7e5"]));
+        // declined, so Black plays on and resigns when out of moves
+        assert_eq!(runner.run(), GameResult::Resignation(White));
+
+        let mut white = scripted(&["e2e4", "g1f3"]);
+        white.offer_draw = true;
+        let mut black = scripted(&["e7e5"]);
+        black.accept_draw = true;
+        let mut runner = game_between(START, white, black);
+        assert_eq!(runner.run(), GameResult::DrawAgreed);
+        assert_eq!(runner.game.move_history.len(), 1);
+    }
+
+    #[test]
+    fn the_tables_adjudicate_a_known_position() {
+        let mut tables = EndgameTables::empty();
+        tables.generate("KRvK").unwrap();
+        let mut runner = game_between("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", scripted(&["a1a2"]), scripted(&[]));
+        runner.adjudication = Some(Adjudication {tables: Some(Arc::new(tables)), ..Adjudication::default()});
+        assert_eq!(runner.run(), GameResult::Resignation(White));
+        assert!(runner.adjudicated);
+        assert_eq!(runner.game.move_history.len(), 1);
+    }
+
+    #[test]
+    fn both_sides_seeing_a_win_resign_the_loser() {
+        for (white_score, winner) in [(1200, White), (-1200, Black)] {
+            let mut white = scripted(&["e2e4", "g1f3"]);
+            white.score = Some(white_score);
+            let mut black = scripted(&["e7e5", "b8c6"]);
+            black.score = Some(-white_score);
+            let mut runner = game_between(START, white, black);
+            runner.adjudication = Some(Adjudication {resign_moves: 1, ..Adjudication::default()});
+            assert_eq!(runner.run(), GameResult::Resignation(winner));
+            assert!(runner.adjudicated);
+            assert_eq!(runner.game.move_history.len(), 2);
+        }
+
+        // only one of them sees it, so nothing happens
+        let mut white = scripted(&["e2e4", "g1f3"]);
+        white.score = Some(1200);
+        let mut runner = game_between(START, white, scripted(&["e7e5"]));
+        runner.adjudication = Some(Adjudication {resign_moves: 1, ..Adjudication::default()});
+        assert_eq!(runner.run(), GameResult::Resignation(White));
+        assert!(!runner.adjudicated);
+    }
+
+    #[test]
+    fn long_games_are_drawn() {
+        let mut runner = game_between(START, scripted(&["g1f3", "f3g1"]), scripted(&["g8f6", "f6g8"]));
+        runner.adjudication = Some(Adjudication {max_moves: Some(1), ..Adjudication::default()});
+        assert_eq!(runner.run(), GameResult::DrawAgreed);
+        assert!(runner.adjudicated);
+        assert_eq!(runner.game.move_history.len(), 2);
+    }
+}
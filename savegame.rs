@@ -0,0 +1,198 @@
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::game::GameResult;
+use crate::fen::*;
+use crate::clock::Clock;
+use crate::clock::TimeControl;
+use crate::players::*;
+use crate::uci::parse_uci_move;
+use crate::json::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// a game as the position it started from and the moves played since, savegame files and the
+// serde form of Game both keep games this way. The repetition counts and everything else that
+// follows from the moves come back from replaying them
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord {
+    pub start: String, // FEN
+    pub moves: Vec<String>, // UCI
+    pub ended: Option<GameResult>, // a result that can't be read off the board
+}
+
+impl GameRecord {
+    pub fn of(game: &Game) -> GameRecord {
+        let start = Game::from_start(game.start.clone());
+        return GameRecord {
+            start: game_to_fen(&start),
+            moves: game.move_history.iter().map(|entry| move_to_uci(&entry.moveEntry)).collect(),
+            ended: game.ended.clone(),
+        };
+    }
+
+    pub fn replay(&self) -> Result<Game, String> {
+        let mut game = Game::from_start(parse_fen(&self.start)?);
+        for uci_move in self.moves.iter() {
+            let legal_move = parse_uci_move(&game, uci_move)?;
+            game.play_move(legal_move);
+        }
+        game.ended = self.ended.clone();
+
+        return Ok(game);
+    }
+}
+
+// a complete game on disk: the game record, who plays which side and what is left on the
+// clocks, as JSON
+pub fn save_game(path: &PathBuf, game: &Game, players: &Players, clock: &Clock) -> Result<(), String> {
+    let record = GameRecord::of(game);
+    let moves = record.moves.iter().map(|uci_move| Json::String(uci_move.clone())).collect();
+
+    let ended = match &record.ended {
+        Some(result) => Json::object(vec![
+            ("termination", Json::string(result.termination())),
+            ("winner", match result.winner() {
+                Some(winner) => Json::string(color_name(winner)),
+                None => Json::Null,
+            }),
+        ]),
+        None => Json::Null,
+    };
+
+    let clock_json = match clock.time_control {
+        Some(time_control) => Json::object(vec![
+            ("initial_ms", Json::Number(time_control.initial.as_millis() as f64)),
+            ("increment_ms", Json::Number(time_control.increment.as_millis() as f64)),
+            ("white_ms", Json::Number(clock.current(White).as_millis() as f64)),
+            ("black_ms", Json::Number(clock.current(Black).as_millis() as f64)),
+        ]),
+        None => Json::Null,
+    };
+
+    let saved = Json::object(vec![
+        ("start", Json::String(record.start)),
+        ("moves", Json::Array(moves)),
+        ("ended", ended),
+        ("white", player_to_json(players.white)),
+        ("black", player_to_json(players.black)),
+        ("clock", clock_json),
+    ]);
+
+    // write next to the real file first so a crash mid write can't leave half a save behind
+    let temporary_path = path.with_extension("tmp");
+    std::fs::write(&temporary_path, saved.to_string()).map_err(|err| format!("couldn't write {}: {}", temporary_path.display(), err))?;
+    std::fs::rename(&temporary_path, path).map_err(|err| format!("couldn't write {}: {}", path.display(), err))?;
+
+    return Ok(());
+}
+
+pub fn load_game(path: &PathBuf) -> Result<(Game, Players, Clock), String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| format!("couldn't open {}: {}", path.display(), err))?;
+    let saved = parse_json(&contents).map_err(|err| format!("{} isn't a saved game: {}", path.display(), err))?;
+
+    let start = saved.get("start").and_then(|start| start.as_str()).ok_or("the save has no start position")?;
+    let mut moves = vec![];
+    if let Some(Json::Array(saved_moves)) = saved.get("moves") {
+        for uci_move in saved_moves {
+            moves.push(uci_move.as_str().ok_or("moves must be strings")?.to_string());
+        }
+    }
+    let ended = match saved.get("ended") {
+        Some(ended) if *ended != Json::Null => Some(result_from_json(ended)?),
+        _ => None,
+    };
+    let game = GameRecord {start: start.to_string(), moves, ended}.replay()?;
+
+    let players = Players {
+        white: player_from_json(saved.get("white"))?,
+        black: player_from_json(saved.get("black"))?,
+    };
+
+    let clock = match saved.get("clock") {
+        Some(clock) if *clock != Json::Null => {
+            let milliseconds = |name: &str| match clock.get(name) {
+                Some(Json::Number(value)) if *value >= 0.0 => Ok(Duration::from_millis(*value as u64)),
+                _ => Err(format!("the clock has no {}", name)),
+            };
+            let time_control = TimeControl {initial: milliseconds("initial_ms")?, increment: milliseconds("increment_ms")?};
+            Clock::restore(time_control, milliseconds("white_ms")?, milliseconds("black_ms")?)
+        }
+        _ => Clock::new(None),
+    };
+
+    return Ok((game, players, clock));
+}
+
+// where games are saved after every change, the home directory or else the current one
+pub fn autosave_path() -> PathBuf {
+    let directory = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    return directory.join(".rustchess-autosave.json");
+}
+
+pub fn autosave(game: &Game, players: &Players, clock: &Clock) -> Result<(), String> {
+    return save_game(&autosave_path(), game, players, clock);
+}
+
+fn color_name(color: Color) -> &'static str {
+    if color == White {"white"} else {"black"}
+}
+
+fn player_to_json(player: PlayerKind) -> Json {
+    match player {
+        PlayerKind::Human => Json::string("human"),
+        PlayerKind::Engine(level) => Json::object(vec![("engine", Json::Number(level as f64))]),
+    }
+}
+
+fn player_from_json(player: Option<&Json>) -> Result<PlayerKind, String> {
+    match player {
+        None => Ok(PlayerKind::Human),
+        Some(Json::String(name)) if name == "human" => Ok(PlayerKind::Human),
+        Some(player) => match player.get("engine") {
+            Some(Json::Number(level)) => Ok(PlayerKind::Engine(*level as u32)),
+            _ => Err(format!("unknown player {}", player)),
+        },
+    }
+}
+
+fn result_from_json(ended: &Json) -> Result<GameResult, String> {
+    let winner = match ended.get("winner").and_then(|winner| winner.as_str()) {
+        Some("white") => Some(White),
+        Some("black") => Some(Black),
+        _ => None,
+    };
+    let termination = ended.get("termination").and_then(|termination| termination.as_str()).unwrap_or("");
+
+    return GameResult::from_termination(termination, winner).ok_or(format!("unknown result {}", ended));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_replays_the_moves_and_rebuilds_the_repetition_counts() {
+        let mut game = Game::new();
+        for uci_move in "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8".split(' ') {
+            let legal_move = parse_uci_move(&game, uci_move).unwrap();
+            game.play_move(legal_move);
+        }
+        let players = Players {white: PlayerKind::Human, black: PlayerKind::Engine(3)};
+        let clock = Clock::new(None);
+
+        let path = std::env::temp_dir().join(format!("rustchess-savegame-test-{}.json", std::process::id()));
+        save_game(&path, &game, &players, &clock).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let (loaded, loaded_players, _) = load_game(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!saved.contains("repetitions"));
+        assert_eq!(GameRecord::of(&loaded), GameRecord::of(&game));
+        assert_eq!(loaded.three_fold_repetition_map, game.three_fold_repetition_map);
+        assert_eq!(loaded.result(), Some(GameResult::ThreefoldRepetition));
+        assert_eq!(loaded_players, players);
+    }
+}
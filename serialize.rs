@@ -0,0 +1,173 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::board::Board;
+use crate::position::*;
+use crate::game::Game;
+use crate::savegame::GameRecord;
+use crate::fen::*;
+use serde::de::Error;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+// Serialize/Deserialize for the types whose derived form would be needlessly verbose:
+//   Square            "e4"
+//   Move              "e7e8q", read back with a Null piece and no special move like a typed
+//                     move, look it up in the legal moves to get those
+//   MoveHistoryEntry  {"move": "e2e4", "board": <FEN placement before the move>, "capture": false},
+//                     piece and special move are filled in again from the board
+//   Game              a GameRecord, {"start": <FEN>, "moves": ["e2e4", ...], "ended": null},
+//                     the moves are replayed and checked when read back
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&square_name(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        parse_square(&name).map_err(|_| D::Error::custom(format!("invalid square {}", name)))
+    }
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&move_to_uci(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let invalid = || D::Error::custom(format!("invalid move {}", text));
+        if !(4..=5).contains(&text.len()) || !text.is_ascii() {
+            return Err(invalid());
+        }
+
+        Ok(Move {
+            from: parse_square(&text[0..2]).map_err(|_| invalid())?,
+            to: parse_square(&text[2..4]).map_err(|_| invalid())?,
+            piece: Piece {piece: PieceType::Null, color: Color::Null},
+            special_move: None,
+            promotion: match text.chars().nth(4) {
+                Some('q') => Some(PieceType::Queen),
+                Some('r') => Some(PieceType::Rook),
+                Some('b') => Some(PieceType::Bishop),
+                Some('n') => Some(PieceType::Knight),
+                Some(_) => return Err(invalid()),
+                None => None,
+            },
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryEntryRepr {
+    #[serde(rename = "move")]
+    played: Move,
+    board: String,
+    capture: bool,
+}
+
+impl Serialize for MoveHistoryEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HistoryEntryRepr {
+            played: self.moveEntry.clone(),
+            board: board_to_fen(&self.boardState),
+            capture: self.isCaptureMove,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MoveHistoryEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MoveHistoryEntry, D::Error> {
+        let repr = HistoryEntryRepr::deserialize(deserializer)?;
+        let board = parse_board_fen(&repr.board).map_err(D::Error::custom)?;
+        let moved_piece = board[repr.played.from.rank][repr.played.from.file];
+        if moved_piece.piece == PieceType::Null {
+            return Err(D::Error::custom(format!("no piece to play {}", move_to_uci(&repr.played))));
+        }
+
+        Ok(MoveHistoryEntry {
+            moveEntry: Move {
+                piece: moved_piece,
+                special_move: special_move_on(&board, &repr.played),
+                ..repr.played
+            },
+            boardState: board,
+            isCaptureMove: repr.capture,
+        })
+    }
+}
+
+// works out castling, en passant, double steps and promotions from the board the move is played on
+fn special_move_on(board: &Board, played: &Move) -> Option<SpecialMoveType> {
+    let piece = board[played.from.rank][played.from.file].piece;
+    let file_distance = played.to.file as i32 - played.from.file as i32;
+    let rank_distance = played.to.rank as i32 - played.from.rank as i32;
+
+    match piece {
+        PieceType::King if file_distance == 2 => Some(SpecialMoveType::CastleShort),
+        PieceType::King if file_distance == -2 => Some(SpecialMoveType::CastleLong),
+        PieceType::Pawn if rank_distance.abs() == 2 => Some(SpecialMoveType::PawnLongMove),
+        PieceType::Pawn if played.to.rank == 0 || played.to.rank == 7 => Some(SpecialMoveType::Promote),
+        PieceType::Pawn if file_distance != 0 && board[played.to.rank][played.to.file].piece == PieceType::Null => {
+            Some(SpecialMoveType::EnPassant)
+        }
+        _ => None,
+    }
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameRecord::of(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Game, D::Error> {
+        GameRecord::deserialize(deserializer)?.replay().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::parse_uci_move;
+
+    #[test]
+    fn game_round_trips_with_castling_en_passant_and_promotion() {
+        let mut game = Game::new();
+        // exf6 is en passant, gxh8=Q a promotion, then castling on both wings
+        for uci_move in "e2e4 d7d5 e4e5 f7f5 e5f6 b8c6 f6g7 c8e6 g7h8q d8d7 g1f3 e8c8 f1e2 c8b8 e1g1".split(' ') {
+            let legal_move = parse_uci_move(&game, uci_move).unwrap();
+            game.play_move(legal_move);
+        }
+
+        let json = serde_json::to_string(&game).unwrap();
+        let read: Game = serde_json::from_str(&json).unwrap();
+        assert_eq!(game_to_fen(&read), game_to_fen(&game));
+        assert_eq!(read.move_history, game.move_history);
+        assert_eq!(read.three_fold_repetition_map, game.three_fold_repetition_map);
+        assert_eq!(read.result(), game.result());
+
+        let specials: Vec<Option<SpecialMoveType>> = read.move_history.iter().map(|entry| entry.moveEntry.special_move.clone()).collect();
+        assert_eq!(specials[4], Some(SpecialMoveType::EnPassant));
+        assert_eq!(specials[8], Some(SpecialMoveType::Promote));
+        assert_eq!(specials[11], Some(SpecialMoveType::CastleLong));
+        assert_eq!(specials[14], Some(SpecialMoveType::CastleShort));
+    }
+
+    #[test]
+    fn squares_and_moves_are_compact() {
+        assert_eq!(serde_json::to_string(&Square {rank: 3, file: 4}).unwrap(), "\"e4\"");
+        let promotion: Move = serde_json::from_str("\"e7e8q\"").unwrap();
+        assert_eq!(promotion.promotion, Some(PieceType::Queen));
+        assert_eq!(serde_json::to_string(&promotion).unwrap(), "\"e7e8q\"");
+        assert!(serde_json::from_str::<Move>("\"e7e8k\"").is_err());
+    }
+}
@@ -0,0 +1,606 @@
+// same lint choices as the library
+#![allow(clippy::needless_return, clippy::useless_format)]
+
+use chess::piece::Color;
+use chess::piece::Color::*;
+use chess::position::*;
+use chess::game::Game;
+use chess::game::evaluate_timeout;
+use chess::fen::*;
+use chess::pgn::*;
+use chess::clock::Clock;
+use chess::cli::parse_time_control;
+use chess::uci::parse_uci_move;
+use chess::json::*;
+use chess::json_api::color_name;
+use chess::json_api::game_state;
+use chess::json_api::result_json;
+use chess::http::*;
+use chess::ws;
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 8080;
+const FLAG_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+const USAGE: &str = "\
+usage: chess-server [--port N]
+
+Keeps any number of games in memory and serves them as JSON over HTTP:
+  POST   /games              start a game, the body may hold {\"fen\": ...} or {\"pgn\": ...}
+                             and a time control like {\"time\": \"5+3\"}
+  GET    /games              list the games
+  GET    /games/ID           the state of a game
+  GET    /games/ID/fen       the position as FEN text
+  GET    /games/ID/pgn       the game so far as PGN text
+  GET    /games/ID/moves     legal moves, ?square=e2 for the moves of one piece
+  POST   /games/ID/moves     play {\"move\": \"e2e4\"}, UCI or SAN
+  GET    /games/ID/result    the result, null while the game goes on
+  DELETE /games/ID           forget a game
+
+and live over a WebSocket at /games/ID/ws, ?color=white or ?color=black to play a side,
+without it to watch. The server sends
+  {\"type\": \"resync\", ...}   the whole game: start_fen, moves, fen, clock, result, sent on
+                            connecting (and reconnecting) and whenever the client asks
+  {\"type\": \"move\", ...}     ply, uci, san and the new fen
+  {\"type\": \"clock\", ...}    white_ms, black_ms and the running side after every move
+  {\"type\": \"result\", ...}   when the game ends
+  {\"type\": \"error\", ...}    a message the client sent was refused
+players send {\"type\": \"move\", \"move\": \"e2e4\"} or {\"type\": \"resign\"}, anyone may send
+{\"type\": \"resync\"}, for instance after noticing a gap in the ply numbers";
+
+enum Outgoing {
+    Text(String),
+    Pong(Vec<u8>),
+    Close,
+}
+
+// a game with its clock and everyone watching it over a WebSocket
+struct ServerGame {
+    game: Game,
+    clock: Option<Clock>, // only with a time control, it starts with the first move
+    next_subscriber: u64,
+    subscribers: Vec<(u64, Sender<Outgoing>)>,
+}
+
+// each game has its own lock so requests for different games never wait on each other,
+// the table lock is only held to look a game up
+struct Games {
+    next_id: u64,
+    games: HashMap<u64, Arc<Mutex<ServerGame>>>,
+}
+
+type SharedGames = Arc<Mutex<Games>>;
+
+impl ServerGame {
+    fn broadcast(&mut self, event: &Json) {
+        let text = event.to_string();
+        self.subscribers.retain(|(_, sender)| sender.send(Outgoing::Text(text.clone())).is_ok());
+    }
+
+    // ends the game when a flag has fallen, true if that happened just now
+    fn check_flag(&mut self) -> bool {
+        if self.game.result().is_some() {
+            return false;
+        }
+        let flagged = match self.clock.as_ref().and_then(|clock| clock.flagged()) {
+            Some(flagged) => flagged,
+            None => return false,
+        };
+        self.game.ended = Some(evaluate_timeout(&self.game.board, flagged));
+        self.finish();
+
+        return true;
+    }
+
+    // plays a UCI or SAN move, `by` is the side of a WebSocket player, None over plain HTTP
+    fn play(&mut self, text: &str, by: Option<Color>) -> Result<(), (u16, String)> {
+        self.check_flag();
+        if let Some(result) = self.game.result() {
+            return Err((409, format!("the game is over: {}", result)));
+        }
+        if by.is_some() && by != Some(self.game.turn) {
+            return Err((409, format!("it is {}'s turn", color_name(self.game.turn))));
+        }
+        let legal_move = parse_uci_move(&self.game, text)
+            .or_else(|_| parse_san(&self.game, text))
+            .map_err(|_| (400, format!("illegal move {}", text)))?;
+
+        let san = move_to_san(&self.game, &legal_move);
+        let mover = self.game.turn;
+        self.game.play_move(legal_move.clone());
+        let event = Json::object(vec![
+            ("type", Json::string("move")),
+            ("ply", Json::Number(self.game.move_history.len() as f64)),
+            ("uci", Json::String(move_to_uci(&legal_move))),
+            ("san", Json::String(san)),
+            ("fen", Json::String(game_to_fen(&self.game))),
+            ("turn", Json::string(color_name(self.game.turn))),
+            ("check", Json::Bool(self.game.is_in_check())),
+        ]);
+        self.broadcast(&event);
+
+        if self.game.result().is_some() {
+            self.finish();
+        } else if let Some(clock) = &mut self.clock {
+            clock.press(mover);
+            let event = clock_event(clock);
+            self.broadcast(&event);
+        }
+
+        return Ok(());
+    }
+
+    fn resign(&mut self, color: Color) -> Result<(), (u16, String)> {
+        self.check_flag();
+        if let Some(result) = self.game.result() {
+            return Err((409, format!("the game is over: {}", result)));
+        }
+        self.game.resign(color);
+        self.finish();
+
+        return Ok(());
+    }
+
+    // stops the clock and tells everyone how the game ended
+    fn finish(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.stop();
+            let event = clock_event(clock);
+            self.broadcast(&event);
+        }
+        let event = Json::object(vec![("type", Json::string("result")), ("result", result_json(&self.game))]);
+        self.broadcast(&event);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let port = match parse_port(&args) {
+        Ok(port) => port,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("could not listen on port {}: {}", port, err);
+            std::process::exit(1);
+        }
+    };
+    println!("serving games on http://localhost:{}/games", port);
+
+    let games: SharedGames = Arc::new(Mutex::new(Games {next_id: 1, games: HashMap::new()}));
+    let flag_watcher_games = games.clone();
+    thread::spawn(move || watch_flags(&flag_watcher_games));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let games = games.clone();
+        thread::spawn(move || handle_connection(stream, &games));
+    }
+}
+
+fn parse_port(args: &[String]) -> Result<u16, String> {
+    let mut port = DEFAULT_PORT;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = args.next().ok_or("--port needs a value")?;
+                port = value.parse().map_err(|_| format!("invalid port {}", value))?;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+
+    return Ok(port);
+}
+
+// a flag can fall while nobody sends anything, so the clocks are looked at all the time
+fn watch_flags(games: &SharedGames) {
+    loop {
+        thread::sleep(FLAG_CHECK_INTERVAL);
+        let entries: Vec<Arc<Mutex<ServerGame>>> = games.lock().unwrap().games.values().cloned().collect();
+        for entry in entries {
+            entry.lock().unwrap().check_flag();
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream, games: &SharedGames) {
+    let mut reader = match stream.try_clone() {
+        Ok(read_half) => BufReader::new(read_half),
+        Err(_) => return,
+    };
+    let response = match read_request(&mut reader) {
+        Ok(Some(request)) => {
+            let upgrade = request.header("upgrade").unwrap_or("");
+            if upgrade.eq_ignore_ascii_case("websocket") {
+                serve_websocket(&request, reader, stream, games);
+                return;
+            }
+            route(&request, games)
+        }
+        Ok(None) => return,
+        Err(err) => Response::error(400, &err),
+    };
+    let _ = response.write_to(&stream);
+}
+
+fn find_game(games: &SharedGames, id: &str) -> Result<(u64, Arc<Mutex<ServerGame>>), Response> {
+    let not_found = || Response::error(404, &format!("no game {}", id));
+    let id: u64 = id.parse().map_err(|_| not_found())?;
+    let entry = games.lock().unwrap().games.get(&id).cloned().ok_or_else(not_found)?;
+
+    return Ok((id, entry));
+}
+
+fn route(request: &Request, games: &SharedGames) -> Response {
+    if request.method == "OPTIONS" {
+        return Response::empty(204);
+    }
+
+    let segments = request.segments();
+    if segments.first() != Some(&"games") {
+        return Response::error(404, "no such endpoint, the API lives under /games");
+    }
+    if segments.len() == 1 {
+        return match request.method.as_str() {
+            "GET" => list_games(games),
+            "POST" => create_game(request, games),
+            _ => Response::error(405, "use GET or POST on /games"),
+        };
+    }
+
+    let (id, entry) = match find_game(games, segments[1]) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let mut entry = entry.lock().unwrap();
+    if request.method == "DELETE" && segments.len() == 2 {
+        games.lock().unwrap().games.remove(&id);
+        for (_, sender) in entry.subscribers.drain(..) {
+            let _ = sender.send(Outgoing::Close);
+        }
+        return Response::empty(204);
+    }
+    entry.check_flag();
+
+    match (request.method.as_str(), &segments[2..]) {
+        ("GET", []) => Response::json(200, &game_json(id, &entry)),
+        ("GET", ["fen"]) => Response::text(200, "text/plain", game_to_fen(&entry.game)),
+        ("GET", ["pgn"]) => Response::text(200, "application/x-chess-pgn", game_to_pgn(&entry.game)),
+        ("GET", ["moves"]) => legal_moves(request, &entry.game),
+        ("POST", ["moves"]) => play_move(request, id, &mut entry),
+        ("GET", ["result"]) => Response::json(200, &Json::object(vec![("result", result_json(&entry.game))])),
+        ("GET", ["ws"]) => Response::error(400, "this endpoint needs a WebSocket upgrade"),
+        (_, [] | ["fen"] | ["pgn"] | ["moves"] | ["result"]) => Response::error(405, "method not allowed here"),
+        _ => Response::error(404, "no such endpoint"),
+    }
+}
+
+fn list_games(games: &SharedGames) -> Response {
+    let entries: Vec<(u64, Arc<Mutex<ServerGame>>)> = games.lock().unwrap().games.iter()
+        .map(|(id, entry)| (*id, entry.clone()))
+        .collect();
+    let mut summaries: Vec<(u64, Json)> = entries.iter()
+        .map(|(id, entry)| {
+            let entry = entry.lock().unwrap();
+            (*id, Json::object(vec![
+                ("id", Json::Number(*id as f64)),
+                ("turn", Json::string(color_name(entry.game.turn))),
+                ("plies", Json::Number(entry.game.move_history.len() as f64)),
+                ("watchers", Json::Number(entry.subscribers.len() as f64)),
+                ("result", result_json(&entry.game)),
+            ]))
+        })
+        .collect();
+    summaries.sort_by_key(|(id, _)| *id);
+
+    return Response::json(200, &Json::object(vec![
+        ("games", Json::Array(summaries.into_iter().map(|(_, summary)| summary).collect())),
+    ]));
+}
+
+fn create_game(request: &Request, games: &SharedGames) -> Response {
+    let body = match parse_body(request) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let field = |name: &str| body.get(name).and_then(|value| value.as_str());
+    let game = if let Some(fen) = field("fen") {
+        match parse_fen(fen) {
+            Ok(start) => Game::from_start(start),
+            Err(err) => return Response::error(400, &format!("invalid FEN: {}", err)),
+        }
+    } else if let Some(pgn) = field("pgn") {
+        match parse_pgn(pgn) {
+            Ok(game) => game,
+            Err(err) => return Response::error(400, &format!("invalid PGN: {}", err)),
+        }
+    } else {
+        Game::new()
+    };
+    let clock = match field("time").map(parse_time_control) {
+        Some(Ok(time_control)) => Some(Clock::new(Some(time_control))),
+        Some(Err(err)) => return Response::error(400, &err),
+        None => None,
+    };
+
+    let entry = ServerGame {game, clock, next_subscriber: 0, subscribers: vec![]};
+    let mut games = games.lock().unwrap();
+    let id = games.next_id;
+    games.next_id += 1;
+    let json = game_json(id, &entry);
+    games.games.insert(id, Arc::new(Mutex::new(entry)));
+
+    return Response::json(201, &json);
+}
+
+fn legal_moves(request: &Request, game: &Game) -> Response {
+    let square = match request.query_param("square").map(parse_square) {
+        Some(Ok(square)) => Some(square),
+        Some(Err(_)) => return Response::error(400, "square should look like e2"),
+        None => None,
+    };
+
+    let moves = match game.result() {
+        Some(_) => vec![],
+        None => game.legal_moves(),
+    };
+    let moves = moves.iter()
+        .filter(|legal_move| square.is_none() || square.as_ref() == Some(&legal_move.from))
+        .map(|legal_move| Json::object(vec![
+            ("uci", Json::String(move_to_uci(legal_move))),
+            ("san", Json::String(move_to_san(game, legal_move))),
+            ("from", Json::String(square_name(&legal_move.from))),
+            ("to", Json::String(square_name(&legal_move.to))),
+        ]))
+        .collect();
+
+    return Response::json(200, &Json::object(vec![("moves", Json::Array(moves))]));
+}
+
+fn play_move(request: &Request, id: u64, entry: &mut ServerGame) -> Response {
+    let body = match parse_body(request) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let text = match body.get("move").and_then(|value| value.as_str()) {
+        Some(text) => text,
+        None => return Response::error(400, "the body needs a \"move\" field"),
+    };
+    if let Err((status, message)) = entry.play(text, None) {
+        return Response::error(status, &message);
+    }
+
+    return Response::json(200, &game_json(id, entry));
+}
+
+// an empty body counts as an empty object
+fn parse_body(request: &Request) -> Result<Json, Response> {
+    let text = String::from_utf8_lossy(&request.body);
+    if text.trim().is_empty() {
+        return Ok(Json::Object(vec![]));
+    }
+
+    return parse_json(&text).map_err(|err| Response::error(400, &format!("invalid JSON: {}", err)));
+}
+
+fn game_json(id: u64, entry: &ServerGame) -> Json {
+    return Json::object(vec![
+        ("id", Json::Number(id as f64)),
+        ("state", game_state(&entry.game)),
+        ("clock", match &entry.clock {
+            Some(clock) => Json::object(clock_fields(clock)),
+            None => Json::Null,
+        }),
+    ]);
+}
+
+fn clock_fields(clock: &Clock) -> Vec<(&'static str, Json)> {
+    return vec![
+        ("white_ms", Json::Number(clock.current(White).as_millis() as f64)),
+        ("black_ms", Json::Number(clock.current(Black).as_millis() as f64)),
+        ("running", match clock.running() {
+            Some(color) => Json::string(color_name(color)),
+            None => Json::Null,
+        }),
+    ];
+}
+
+fn clock_event(clock: &Clock) -> Json {
+    let mut fields = vec![("type", Json::string("clock"))];
+    fields.extend(clock_fields(clock));
+
+    return Json::object(fields);
+}
+
+// everything a client needs to rebuild the game from nothing
+fn resync_event(id: u64, entry: &ServerGame, role: Option<Color>) -> Json {
+    let start = Game::from_start(entry.game.start.clone());
+    let moves = entry.game.move_history.iter()
+        .map(|history_entry| Json::String(move_to_uci(&history_entry.moveEntry)))
+        .collect();
+    let sans = history_to_san(&entry.game).into_iter().map(Json::String).collect();
+
+    return Json::object(vec![
+        ("type", Json::string("resync")),
+        ("id", Json::Number(id as f64)),
+        ("role", Json::string(match role {
+            Some(color) => color_name(color),
+            None => "spectator",
+        })),
+        ("start_fen", Json::String(game_to_fen(&start))),
+        ("moves", Json::Array(moves)),
+        ("san", Json::Array(sans)),
+        ("ply", Json::Number(entry.game.move_history.len() as f64)),
+        ("fen", Json::String(game_to_fen(&entry.game))),
+        ("turn", Json::string(color_name(entry.game.turn))),
+        ("clock", match &entry.clock {
+            Some(clock) => Json::object(clock_fields(clock)),
+            None => Json::Null,
+        }),
+        ("result", result_json(&entry.game)),
+    ]);
+}
+
+fn error_event(message: &str) -> Json {
+    return Json::object(vec![("type", Json::string("error")), ("message", Json::string(message))]);
+}
+
+// one thread reads the client's messages, another writes whatever the game sends out
+fn serve_websocket(request: &Request, mut reader: BufReader<TcpStream>, mut stream: TcpStream, games: &SharedGames) {
+    let segments = request.segments();
+    let found = match segments[..] {
+        ["games", id, "ws"] => find_game(games, id),
+        _ => Err(Response::error(404, "WebSockets live at /games/ID/ws")),
+    };
+    let (id, entry) = match found {
+        Ok(found) => found,
+        Err(response) => {
+            let _ = response.write_to(&stream);
+            return;
+        }
+    };
+    let role = match request.query_param("color") {
+        None | Some("spectator") => None,
+        Some("white") => Some(White),
+        Some("black") => Some(Black),
+        Some(other) => {
+            let _ = Response::error(400, &format!("color should be white or black, not {}", other)).write_to(&stream);
+            return;
+        }
+    };
+    let key = match request.header("sec-websocket-key") {
+        Some(key) => key,
+        None => {
+            let _ = Response::error(400, "missing Sec-WebSocket-Key").write_to(&stream);
+            return;
+        }
+    };
+    if stream.write_all(ws::handshake_response(key).as_bytes()).is_err() {
+        return;
+    }
+
+    let (sender, receiver) = channel();
+    let subscriber = {
+        let mut entry = entry.lock().unwrap();
+        entry.check_flag();
+        let _ = sender.send(Outgoing::Text(resync_event(id, &entry, role).to_string()));
+        entry.next_subscriber += 1;
+        let subscriber = entry.next_subscriber;
+        entry.subscribers.push((subscriber, sender.clone()));
+        subscriber
+    };
+
+    let writer = thread::spawn(move || {
+        for outgoing in receiver {
+            let written = match outgoing {
+                Outgoing::Text(text) => ws::write_text(&mut stream, &text),
+                Outgoing::Pong(payload) => ws::write_pong(&mut stream, &payload),
+                Outgoing::Close => {
+                    let _ = ws::write_close(&mut stream);
+                    break;
+                }
+            };
+            if written.is_err() {
+                break;
+            }
+        }
+        let _ = stream.shutdown(Shutdown::Both);
+    });
+
+    let mut partial = vec![];
+    loop {
+        match ws::read_message(&mut reader, &mut partial) {
+            Ok(ws::Message::Text(text)) => {
+                if let Some(reply) = handle_client_message(&text, id, &entry, role) {
+                    let _ = sender.send(Outgoing::Text(reply.to_string()));
+                }
+            }
+            Ok(ws::Message::Ping(payload)) => {
+                let _ = sender.send(Outgoing::Pong(payload));
+            }
+            Ok(ws::Message::Close) | Err(_) => break,
+        }
+    }
+
+    entry.lock().unwrap().subscribers.retain(|(other, _)| *other != subscriber);
+    let _ = sender.send(Outgoing::Close);
+    drop(sender);
+    let _ = writer.join();
+}
+
+// the reply for the sender alone, if any, whatever happens to the game goes to everyone
+fn handle_client_message(text: &str, id: u64, entry: &Mutex<ServerGame>, role: Option<Color>) -> Option<Json> {
+    let message = match parse_json(text) {
+        Ok(message) => message,
+        Err(err) => return Some(error_event(&format!("invalid JSON: {}", err))),
+    };
+    let field = |name: &str| message.get(name).and_then(|value| value.as_str());
+
+    let mut entry = entry.lock().unwrap();
+    let outcome = match (field("type"), role) {
+        (Some("resync"), _) => return Some(resync_event(id, &entry, role)),
+        (Some("move") | Some("resign"), None) => Err(format!("spectators can't change the game")),
+        (Some("move"), Some(color)) => match field("move") {
+            Some(text) => entry.play(text, Some(color)).map_err(|(_, message)| message),
+            None => Err(format!("move needs a \"move\" field")),
+        },
+        (Some("resign"), Some(color)) => entry.resign(color).map_err(|(_, message)| message),
+        (Some(other), _) => Err(format!("unknown type {}", other)),
+        (None, _) => Err(format!("messages need a \"type\" field")),
+    };
+
+    return match outcome {
+        Ok(()) => None,
+        Err(message) => Some(error_event(&message)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post_games(body: &str) -> Response {
+        let games: SharedGames = Arc::new(Mutex::new(Games {next_id: 1, games: HashMap::new()}));
+        let request = Request {
+            method: "POST".to_string(),
+            path: "/games".to_string(),
+            query: vec![],
+            headers: vec![],
+            body: body.as_bytes().to_vec(),
+        };
+        return route(&request, &games);
+    }
+
+    #[test]
+    fn bad_new_game_requests_are_rejected() {
+        assert_eq!(post_games("{\"time\": \"5+3\"}").status, 201);
+        assert_eq!(post_games(&"[".repeat(200000)).status, 400);
+        assert_eq!(post_games("{\"time\": \"nan\"}").status, 400);
+        assert_eq!(post_games("{\"time\": \"1e300\"}").status, 400);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::engine::SearchPosition;
+use crate::pgn::move_to_san;
+use std::collections::HashMap;
+use std::fmt;
+
+// solves chess problems by trying everything. The side to move is White in the usual wording:
+// it mates in direct mates, forces the other side to mate it in selfmates and reflexmates, and
+// in helpmates it moves first and gets mated with the other side's help
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stipulation {
+    Mate(u32), // #n
+    Helpmate(u32), // h#n in plies, h#2 is 4 and h#2.5 is 5
+    Selfmate(u32), // s#n, the other side resists but has to mate on its nth move
+    Reflexmate(u32), // r#n, a selfmate where either side has to mate in one when it can
+}
+
+impl fmt::Display for Stipulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stipulation::Mate(moves) => write!(f, "#{}", moves),
+            Stipulation::Helpmate(plies) if plies % 2 == 1 => write!(f, "h#{}.5", plies / 2),
+            Stipulation::Helpmate(plies) => write!(f, "h#{}", plies / 2),
+            Stipulation::Selfmate(moves) => write!(f, "s#{}", moves),
+            Stipulation::Reflexmate(moves) => write!(f, "r#{}", moves),
+        }
+    }
+}
+
+// "#2", "h#3", "h#2.5", "s#4" or "r#2"
+pub fn parse_stipulation(text: &str) -> Result<Stipulation, String> {
+    let invalid = || format!("invalid stipulation {}, expected #n, h#n, h#n.5, s#n or r#n", text);
+    let (kind, count) = text.split_once('#').ok_or_else(invalid)?;
+    if kind == "h" {
+        let (moves, half) = match count.strip_suffix(".5") {
+            Some(moves) => (moves, 1),
+            None => (count, 0),
+        };
+        let moves: u32 = moves.parse().map_err(|_| invalid())?;
+        if moves * 2 + half == 0 {
+            return Err(invalid());
+        }
+        return Ok(Stipulation::Helpmate(moves * 2 + half));
+    }
+
+    let moves: u32 = count.parse().map_err(|_| invalid())?;
+    if moves == 0 {
+        return Err(invalid());
+    }
+    match kind {
+        "" => Ok(Stipulation::Mate(moves)),
+        "s" => Ok(Stipulation::Selfmate(moves)),
+        "r" => Ok(Stipulation::Reflexmate(moves)),
+        _ => Err(invalid()),
+    }
+}
+
+// a solution from the attacker's move on, with the attacker's answer to every defense. A
+// defense without an answer is the defender's forced mate in selfmates and reflexmates
+#[derive(Debug, Clone, PartialEq)]
+pub struct MateTree {
+    pub played: Move,
+    pub moves: u32, // the attacker's moves to the end, this one included
+    pub defenses: Vec<(Move, Option<MateTree>)>, // empty when this move mates
+}
+
+fn is_mate(position: &SearchPosition) -> bool {
+    return position.in_check() && position.legal_moves().is_empty();
+}
+
+struct Solver {
+    stipulation: Stipulation,
+    // whether the side to move gets there within so many moves, positions reached by different
+    // orders of the same moves come up a lot
+    known: HashMap<(SearchPosition, u32), bool>,
+}
+
+impl Solver {
+    fn new(stipulation: Stipulation) -> Solver {
+        return Solver {stipulation, known: HashMap::new()};
+    }
+
+    fn reflex(&self) -> bool {
+        return matches!(self.stipulation, Stipulation::Reflexmate(_));
+    }
+
+    // whether the side to move can force the stipulation within `moves` moves
+    fn attacker_wins(&mut self, position: &SearchPosition, moves: u32) -> bool {
+        if moves == 0 {
+            return false;
+        }
+        if let Some(known) = self.known.get(&(position.clone(), moves)) {
+            return *known;
+        }
+
+        let attacks = position.legal_moves();
+        // a reflexmate attacker able to mate has to, which fails the stipulation
+        let must_mate = self.reflex() && attacks.iter().any(|attack| is_mate(&position.after(attack)));
+        let mut wins = false;
+        if !must_mate {
+            for attack in attacks.iter() {
+                if self.defender_loses(&position.after(attack), moves) {
+                    wins = true;
+                    break;
+                }
+            }
+        }
+        self.known.insert((position.clone(), moves), wins);
+
+        return wins;
+    }
+
+    // whether the defender to move can't escape within `moves` of the attacker's moves,
+    // counting the one just played
+    fn defender_loses(&mut self, position: &SearchPosition, moves: u32) -> bool {
+        if let Stipulation::Mate(_) = self.stipulation {
+            // the last move has to be check, which saves looking at the defenses of the rest
+            if moves == 1 && !position.in_check() {
+                return false;
+            }
+            let defenses = position.legal_moves();
+            if defenses.is_empty() {
+                return position.in_check();
+            }
+            if moves == 1 {
+                return false;
+            }
+            return defenses.iter().all(|defense| self.attacker_wins(&position.after(defense), moves - 1));
+        }
+
+        // selfmates and reflexmates, the defender has to be able to move and mate
+        let defenses = position.legal_moves();
+        if defenses.is_empty() {
+            return false;
+        }
+        let mating: Vec<bool> = defenses.iter().map(|defense| is_mate(&position.after(defense))).collect();
+        if self.reflex() && mating.contains(&true) {
+            return true;
+        }
+        for (defense, mates) in defenses.iter().zip(mating) {
+            if !mates && (moves == 1 || !self.attacker_wins(&position.after(defense), moves - 1)) {
+                return false;
+            }
+        }
+
+        return true;
+    }
+
+    // the defenses worth showing, only the forced mates when a reflexmate defender has some
+    fn defenses(&self, position: &SearchPosition) -> Vec<Move> {
+        let defenses = position.legal_moves();
+        if self.reflex() {
+            let mating: Vec<Move> = defenses.iter().filter(|defense| is_mate(&position.after(defense))).cloned().collect();
+            if !mating.is_empty() {
+                return mating;
+            }
+        }
+        return defenses;
+    }
+
+    // the tree below an attacking move known to work within `moves`, with the quickest answer
+    // to each defense
+    fn tree(&mut self, position: &SearchPosition, attack: &Move, moves: u32) -> MateTree {
+        let after = position.after(attack);
+        let moves = (1..=moves).find(|shortest| self.defender_loses(&after, *shortest)).unwrap_or(moves);
+
+        let mut defenses = vec![];
+        for defense in self.defenses(&after) {
+            let next = after.after(&defense);
+            if is_mate(&next) {
+                defenses.push((defense, None));
+                continue;
+            }
+            let shortest = match (1..moves).find(|shortest| self.attacker_wins(&next, *shortest)) {
+                Some(shortest) => shortest,
+                None => continue, // can't happen below a move that works
+            };
+            let answer = next.legal_moves().into_iter()
+                .find(|answer| self.defender_loses(&next.after(answer), shortest));
+            if let Some(answer) = answer {
+                defenses.push((defense, Some(self.tree(&next, &answer, shortest))));
+            }
+        }
+
+        return MateTree {played: attack.clone(), moves, defenses};
+    }
+
+    // whether some line of exactly `plies` moves ends in mate
+    fn helpmate_exists(&mut self, position: &SearchPosition, plies: u32) -> bool {
+        if plies == 0 {
+            return is_mate(position);
+        }
+        if let Some(known) = self.known.get(&(position.clone(), plies)) {
+            return *known;
+        }
+
+        let mut exists = false;
+        for played in position.legal_moves() {
+            let next = position.after(&played);
+            // the mating move has to give check
+            if (plies > 1 || next.in_check()) && self.helpmate_exists(&next, plies - 1) {
+                exists = true;
+                break;
+            }
+        }
+        self.known.insert((position.clone(), plies), exists);
+
+        return exists;
+    }
+
+    fn helpmates(&mut self, position: &SearchPosition, plies: u32) -> Vec<Vec<Move>> {
+        if plies == 0 {
+            return vec![vec![]];
+        }
+        let mut solutions = vec![];
+        for played in position.legal_moves() {
+            let next = position.after(&played);
+            if !self.helpmate_exists(&next, plies - 1) {
+                continue;
+            }
+            for mut rest in self.helpmates(&next, plies - 1) {
+                rest.insert(0, played.clone());
+                solutions.push(rest);
+            }
+        }
+
+        return solutions;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Solutions {
+    Keys(Vec<MateTree>), // every first move that works, quickest first
+    Lines(Vec<Vec<Move>>), // every helpmate line
+}
+
+impl Solutions {
+    pub fn len(&self) -> usize {
+        match self {
+            Solutions::Keys(keys) => keys.len(),
+            Solutions::Lines(lines) => lines.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
+
+// every solution, more than one means a composed problem is cooked and none refutes it
+pub fn solve(game: &Game, stipulation: Stipulation) -> Solutions {
+    let mut solver = Solver::new(stipulation);
+    let root = SearchPosition::from_game(game);
+
+    let moves = match stipulation {
+        Stipulation::Helpmate(plies) => return Solutions::Lines(solver.helpmates(&root, plies)),
+        Stipulation::Mate(moves) | Stipulation::Selfmate(moves) | Stipulation::Reflexmate(moves) => moves,
+    };
+    if !solver.attacker_wins(&root, moves) {
+        return Solutions::Keys(vec![]);
+    }
+    let mut keys = vec![];
+    for key in root.legal_moves() {
+        if solver.defender_loses(&root.after(&key), moves) {
+            keys.push(solver.tree(&root, &key, moves));
+        }
+    }
+    keys.sort_by_key(|key| key.moves);
+
+    return Solutions::Keys(keys);
+}
+
+// every first move (key) that forces mate within `moves` moves, quickest first
+pub fn solve_mate(game: &Game, moves: u32) -> Vec<MateTree> {
+    match solve(game, Stipulation::Mate(moves)) {
+        Solutions::Keys(keys) => keys,
+        Solutions::Lines(_) => vec![],
+    }
+}
+
+fn move_number(game: &Game) -> String {
+    return format!("{}{}", game.fullmove_number(), if game.turn == White {"."} else {"..."});
+}
+
+// the tree in the usual problem layout, the key marked with '!' and each defense on its own
+// line followed by the answer to it
+pub fn format_mate_tree(game: &Game, tree: &MateTree) -> String {
+    let mut res = format!("{} {}!\n", move_number(game), move_to_san(game, &tree.played));
+    write_defenses(game, tree, 1, &mut res);
+    return res;
+}
+
+fn write_defenses(game: &Game, tree: &MateTree, depth: usize, res: &mut String) {
+    let mut after = game.clone();
+    after.play_move(tree.played.clone());
+    for (defense, answer) in tree.defenses.iter() {
+        let mut next = after.clone();
+        let mut line = format!("{}{} {}", "    ".repeat(depth), move_number(&next), move_to_san(&next, defense));
+        next.play_move(defense.clone());
+        if let Some(answer) = answer {
+            line.push_str(&format!(" {} {}", move_number(&next), move_to_san(&next, &answer.played)));
+        }
+        res.push_str(&line);
+        res.push('\n');
+        if let Some(answer) = answer {
+            write_defenses(&next, answer, depth + 1, res);
+        }
+    }
+}
+
+// a line of moves numbered like PGN movetext, eg "1... Kd4 2. Qb3 Ke4 3. Qd3#"
+pub fn format_line(game: &Game, line: &[Move]) -> String {
+    let mut replay = game.clone();
+    let mut tokens = vec![];
+    for (index, played) in line.iter().enumerate() {
+        if replay.turn == White || index == 0 {
+            tokens.push(move_number(&replay));
+        }
+        tokens.push(move_to_san(&replay, played));
+        replay.play_move(played.clone());
+    }
+
+    return tokens.join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+    use crate::game::GameResult;
+
+    fn game(fen: &str) -> Game {
+        return Game::from_start(parse_fen(fen).unwrap());
+    }
+
+    #[test]
+    fn mate_in_two_has_a_unique_key() {
+        // Morphy's problem, 1. Ra6! and 2. b7# or Rxa7#
+        let problem = game("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1");
+        assert!(solve_mate(&problem, 1).is_empty());
+
+        let keys = solve_mate(&problem, 2);
+        assert_eq!(keys.len(), 1);
+        let key = &keys[0];
+        assert_eq!(move_to_uci(&key.played), "a1a6");
+        assert_eq!(key.moves, 2);
+        // bxa6 and the six bishop moves, each met by a mate
+        assert_eq!(key.defenses.len(), 7);
+        for (_, answer) in key.defenses.iter() {
+            let answer = answer.as_ref().unwrap();
+            assert_eq!(answer.moves, 1);
+            assert!(answer.defenses.is_empty());
+        }
+
+        let tree = format_mate_tree(&problem, key);
+        assert!(tree.starts_with("1. Ra6!\n"));
+        assert!(tree.contains("1... bxa6 2. b7#"));
+        assert!(tree.contains("1... Bc7 2. Rxa7#"));
+    }
+
+    #[test]
+    fn a_cooked_mate_in_two_reports_every_key() {
+        // 1. Rh8# works, and so does almost anything that leaves it for the second move, the
+        // quickest comes first
+        let keys = solve_mate(&game("k7/8/1K6/8/8/8/8/7R w - - 0 1"), 2);
+        assert!(keys.len() > 1);
+        assert_eq!(move_to_uci(&keys[0].played), "h1h8");
+        assert_eq!(keys[0].moves, 1);
+    }
+
+    fn lines(solutions: Solutions) -> Vec<Vec<Move>> {
+        match solutions {
+            Solutions::Lines(lines) => lines,
+            Solutions::Keys(_) => panic!("expected helpmate lines"),
+        }
+    }
+
+    fn keys(solutions: Solutions) -> Vec<MateTree> {
+        match solutions {
+            Solutions::Keys(keys) => keys,
+            Solutions::Lines(_) => panic!("expected keys"),
+        }
+    }
+
+    #[test]
+    fn stipulations() {
+        assert_eq!(parse_stipulation("#2"), Ok(Stipulation::Mate(2)));
+        assert_eq!(parse_stipulation("h#2"), Ok(Stipulation::Helpmate(4)));
+        assert_eq!(parse_stipulation("h#2.5"), Ok(Stipulation::Helpmate(5)));
+        assert_eq!(parse_stipulation("s#3"), Ok(Stipulation::Selfmate(3)));
+        assert_eq!(parse_stipulation("r#1"), Ok(Stipulation::Reflexmate(1)));
+        for text in ["#0", "h#0", "x#2", "2", "#two"] {
+            assert!(parse_stipulation(text).is_err(), "{}", text);
+        }
+        assert_eq!(Stipulation::Helpmate(5).to_string(), "h#2.5");
+    }
+
+    #[test]
+    fn helpmate_in_two_with_one_solution() {
+        let problem = game("1B6/8/8/6k1/8/6B1/8/1R5K b - - 0 1");
+        let solutions = lines(solve(&problem, Stipulation::Helpmate(4)));
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(format_line(&problem, &solutions[0]), "1... Kg4 2. Rb4+ Kh3 3. Rh4#");
+    }
+
+    #[test]
+    fn cooked_helpmate_reports_every_solution() {
+        let problem = game("6N1/8/4K3/8/5N2/1Q6/6B1/5k2 b - - 0 1");
+        let solutions = lines(solve(&problem, Stipulation::Helpmate(4)));
+        assert_eq!(solutions.len(), 13);
+        for line in solutions.iter() {
+            let mut replay = problem.clone();
+            for played in line {
+                replay.play_move(played.clone());
+            }
+            assert_eq!(replay.result(), Some(GameResult::Checkmate(White)));
+        }
+    }
+
+    #[test]
+    fn selfmate_and_reflexmate_keys() {
+        let selfmate = game("8/6Q1/8/8/r7/5KQ1/2q5/5k2 w - - 0 1");
+        let selfmate_keys = keys(solve(&selfmate, Stipulation::Selfmate(1)));
+        assert_eq!(selfmate_keys.len(), 1);
+        assert_eq!(move_to_uci(&selfmate_keys[0].played), "g3f2");
+
+        let reflexmate = game("8/8/8/2q4k/2R2K2/8/4P3/7B w - - 0 1");
+        let reflexmate_keys = keys(solve(&reflexmate, Stipulation::Reflexmate(2)));
+        assert_eq!(reflexmate_keys.len(), 1);
+        assert_eq!(move_to_uci(&reflexmate_keys[0].played), "h1f3");
+    }
+}
@@ -0,0 +1,1142 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::position::*;
+use crate::legal_moves::*;
+use crate::game::Game;
+use crate::game::play_move;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+// probing of Syzygy endgame tablebases: .rtbw files hold win/draw/loss for every position
+// of a material balance, .rtbz files the distance to the next capture or pawn move (DTZ)
+// that keeps the result, both compressed with canonical Huffman codes over symbols built by
+// recursive pairing, and indexed by a numbering of the positions that folds away symmetry
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+const MAX_PIECES: usize = 7;
+
+// per table flags, all but SINGLE_VALUE only mean something in DTZ tables
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+// the result with best play, cursed wins and blessed losses are won or lost on the board
+// but drawn by the 50 move rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+}
+
+impl fmt::Display for Wdl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Wdl::Loss => write!(f, "loss"),
+            Wdl::BlessedLoss => write!(f, "blessed loss"),
+            Wdl::Draw => write!(f, "draw"),
+            Wdl::CursedWin => write!(f, "cursed win"),
+            Wdl::Win => write!(f, "win"),
+        }
+    }
+}
+
+// a root move with what it leads to, dtz counts plies from the root and is negative when losing
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootMove {
+    pub played: Move,
+    pub wdl: Wdl,
+    pub dtz: i32,
+}
+
+// the numbering tables every table's index is built from, the same for all of them
+struct Indexing {
+    map_b1h1h7: [usize; 64], // squares below the a1-h8 diagonal to 0..27
+    map_a1d1d4: [usize; 64], // the a1-d1-d4 triangle to 0..9, diagonal squares last
+    map_kk: [[usize; 64]; 10], // the 462 ways to place two kings with the first in the triangle
+    binomial: [[u64; 64]; 6], // [k][n] ways to pick k of n
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6], // [leading pawns][square]
+    lead_pawns_size: [[u64; 4]; 6], // [leading pawns][file a..d]
+}
+
+fn off_diagonal(square: usize) -> i32 {
+    return (square / 8) as i32 - (square % 8) as i32;
+}
+
+fn flip_file(square: usize) -> usize {
+    return square ^ 7;
+}
+
+impl Indexing {
+    fn new() -> Indexing {
+        let mut indexing = Indexing {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                indexing.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = vec![];
+        code = 0;
+        for square in 0..28 {
+            if off_diagonal(square) < 0 && square % 8 <= 3 {
+                indexing.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 && square % 8 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            indexing.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // kings next to each other can't be, and with the first king on the diagonal the
+        // second stays on or below it, positions with both on the diagonal come last
+        let mut both_on_diagonal = vec![];
+        code = 0;
+        for index in 0..10 {
+            for first in 0..28 {
+                if indexing.map_a1d1d4[first] != index || (index == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    let touching = (first % 8).abs_diff(second % 8) <= 1 && (first / 8).abs_diff(second / 8) <= 1;
+                    if touching || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        indexing.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            indexing.map_kk[index][second] = code;
+            code += 1;
+        }
+
+        indexing.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                indexing.binomial[k][n] = if k > 0 {indexing.binomial[k - 1][n - 1]} else {0}
+                    + if k < n {indexing.binomial[k][n - 1]} else {0};
+            }
+        }
+
+        // the leading pawn is the one nearest the edge and then the lowest, it has the highest
+        // map_pawns, which counts the squares left for the other pawns
+        let mut available = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        indexing.map_pawns[square] = available;
+                        available -= 1;
+                        indexing.map_pawns[flip_file(square)] = available;
+                        available = available.saturating_sub(1);
+                    }
+                    indexing.lead_pawn_idx[lead_pawns][square] = index;
+                    index += indexing.binomial[lead_pawns - 1][indexing.map_pawns[square]];
+                }
+                indexing.lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+
+        return indexing;
+    }
+}
+
+// what's needed to decode one of the up to eight subtables of a file, offsets are into the file
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    max_sym_len: usize,
+    min_sym_len: usize, // the stored value for single value tables
+    num_blocks: usize,
+    size_of_block: usize,
+    span: usize, // there is a sparse index entry about every span values
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u32>, // values a symbol stands for, minus one
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    map_idx: [usize; 4], // DTZ value maps for win, loss, cursed win and blessed loss
+}
+
+// a loaded .rtbw or .rtbz file
+struct Table {
+    bytes: Vec<u8>,
+    symmetric: bool, // both sides have the same pieces
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    piece_count: usize,
+    pawn_count: [usize; 2], // the leading color's, then the other's
+    items: Vec<Vec<PairsData>>, // [side to move][leading pawn file]
+    map: usize,
+}
+
+fn read_u8(bytes: &[u8], at: usize) -> Result<u8, String> {
+    return bytes.get(at).copied().ok_or_else(|| format!("truncated table"));
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Result<u16, String> {
+    return Ok(u16::from_le_bytes([read_u8(bytes, at)?, read_u8(bytes, at + 1)?]));
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Result<u32, String> {
+    return Ok(u32::from_le_bytes([read_u8(bytes, at)?, read_u8(bytes, at + 1)?, read_u8(bytes, at + 2)?, read_u8(bytes, at + 3)?]));
+}
+
+// piece codes used in the files, black is white plus 8
+fn piece_code(piece: &Piece) -> u8 {
+    let code = match piece.piece {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+        PieceType::Null => 0,
+    };
+    return if piece.color == Black {code + 8} else {code};
+}
+
+impl Table {
+    fn parse(bytes: Vec<u8>, name: &str, dtz: bool, indexing: &Indexing) -> Result<Table, String> {
+        let magic = if dtz {DTZ_MAGIC} else {WDL_MAGIC};
+        if bytes.len() < 5 || bytes[0..4] != magic {
+            return Err(format!("{} is not a Syzygy table", name));
+        }
+        let (white, black) = name.split_once('v').ok_or_else(|| format!("bad table name {}", name))?;
+        let white_pawns = white.matches('P').count();
+        let black_pawns = black.matches('P').count();
+        // the side with fewer pawns leads, it compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        let mut table = Table {
+            bytes: vec![],
+            symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: [white, black].iter().any(|side| "QRBNP".chars().any(|letter| side.matches(letter).count() == 1)),
+            piece_count: white.len() + black.len(),
+            pawn_count: if white_leads {[white_pawns, black_pawns]} else {[black_pawns, white_pawns]},
+            items: vec![],
+            map: 0,
+        };
+        if table.piece_count > MAX_PIECES {
+            return Err(format!("{} has too many pieces", name));
+        }
+        if (bytes[4] & 1 != 0) == table.symmetric && !dtz {
+            return Err(format!("{} doesn't match its name", name));
+        }
+
+        let sides = if !dtz && !table.symmetric {2} else {1};
+        let files = if table.has_pawns {4} else {1};
+        let pawns_on_both_sides = table.has_pawns && table.pawn_count[1] > 0;
+        table.items = vec![vec![PairsData::default(); files]; sides];
+
+        let mut at = 5;
+        for file in 0..files {
+            let first = read_u8(&bytes, at)?;
+            let second = if pawns_on_both_sides {read_u8(&bytes, at + 1)?} else {0xFF};
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            at += if pawns_on_both_sides {2} else {1};
+
+            for k in 0..table.piece_count {
+                let byte = read_u8(&bytes, at)?;
+                for side in 0..sides {
+                    table.items[side][file].pieces[k] = if side == 1 {byte >> 4} else {byte & 0xF};
+                }
+                at += 1;
+            }
+            for side in 0..sides {
+                let mut item = std::mem::take(&mut table.items[side][file]);
+                table.set_groups(&mut item, order[side], file, indexing);
+                table.items[side][file] = item;
+            }
+        }
+        at += at & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                at = set_sizes(&mut table.items[side][file], &bytes, at)?;
+            }
+        }
+
+        if dtz {
+            table.map = at;
+            for file in 0..files {
+                let item = &mut table.items[0][file];
+                if item.flags & MAPPED == 0 {
+                    continue;
+                }
+                if item.flags & WIDE != 0 {
+                    at += at & 1;
+                    for index in 0..4 {
+                        item.map_idx[index] = at + 2 - table.map;
+                        at += 2 * read_u16(&bytes, at)? as usize + 2;
+                    }
+                } else {
+                    for index in 0..4 {
+                        item.map_idx[index] = at + 1 - table.map;
+                        at += read_u8(&bytes, at)? as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let item = &mut table.items[side][file];
+                item.sparse_index = at;
+                at += item.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let item = &mut table.items[side][file];
+                item.block_length = at;
+                at += item.block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let item = &mut table.items[side][file];
+                at = (at + 0x3F) & !0x3F;
+                item.data = at;
+                at += item.num_blocks * item.size_of_block;
+            }
+        }
+        if at > bytes.len() {
+            return Err(format!("{} is truncated", name));
+        }
+
+        table.bytes = bytes;
+        return Ok(table);
+    }
+
+    // pieces of one type and color are encoded together, and so are the first three pieces
+    // (or the two kings) of pawnless tables, `order` says in which order the groups multiply
+    fn set_groups(&self, item: &mut PairsData, order: [u8; 2], file: usize, indexing: &Indexing) {
+        let mut first_len: i32 = if self.has_pawns {0} else if self.has_unique_pieces {3} else {2};
+        let mut n = 0;
+        item.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || item.pieces[i] == item.pieces[i - 1] {
+                item.group_len[n] += 1;
+            } else {
+                n += 1;
+                item.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        item.group_len[n] = 0;
+
+        let pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if pawns_on_both_sides {2} else {1};
+        let mut free_squares = 64 - item.group_len[0] - if pawns_on_both_sides {item.group_len[1]} else {0};
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                item.group_idx[0] = index;
+                index *= if self.has_pawns {
+                    indexing.lead_pawns_size[item.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                item.group_idx[1] = index;
+                index *= indexing.binomial[item.group_len[1]][48 - item.group_len[0]];
+            } else {
+                item.group_idx[next] = index;
+                index *= indexing.binomial[item.group_len[next]][free_squares];
+                free_squares -= item.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        item.group_idx[n] = index;
+    }
+
+    fn left(&self, item: &PairsData, symbol: usize) -> usize {
+        let at = item.btree + symbol * 3;
+        return ((self.bytes[at + 1] as usize & 0xF) << 8) | self.bytes[at] as usize;
+    }
+
+    fn right(&self, item: &PairsData, symbol: usize) -> usize {
+        let at = item.btree + symbol * 3;
+        return ((self.bytes[at + 2] as usize) << 4) | (self.bytes[at + 1] as usize >> 4);
+    }
+
+    // the value stored at `index`
+    fn decompress(&self, item: &PairsData, index: u64) -> Option<i32> {
+        if item.flags & SINGLE_VALUE != 0 {
+            return Some(item.min_sym_len as i32);
+        }
+        let bytes = &self.bytes;
+
+        // the sparse index points into block_length near every span-th value, from there
+        // walk the blocks to the one holding our value
+        let k = (index / item.span as u64) as usize;
+        if k >= item.sparse_index_size {
+            return None;
+        }
+        let mut block = read_u32(bytes, item.sparse_index + k * 6).ok()? as i64;
+        let mut offset = read_u16(bytes, item.sparse_index + k * 6 + 4).ok()? as i64;
+        offset += (index % item.span as u64) as i64 - (item.span / 2) as i64;
+
+        let block_length = |block: i64| -> Option<i64> {
+            if block < 0 || block as usize >= item.block_length_size {
+                return None;
+            }
+            return Some(read_u16(bytes, item.block_length + block as usize * 2).ok()? as i64);
+        };
+        while offset < 0 {
+            block -= 1;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut at = item.data + block as usize * item.size_of_block;
+        let mut buffer = u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?);
+        at += 8;
+        let mut buffer_bits = 64;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while len < item.base64.len() - 1 && buffer < item.base64[len] {
+                len += 1;
+            }
+            symbol = ((buffer - item.base64[len]) >> (64 - len - item.min_sym_len)) as usize;
+            symbol += read_u16(bytes, item.lowest_sym + len * 2).ok()? as usize;
+            let count = *item.symlen.get(symbol)? as i64 + 1;
+            if offset < count {
+                break;
+            }
+            offset -= count;
+            let consumed = len + item.min_sym_len;
+            buffer <<= consumed;
+            buffer_bits -= consumed;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                let word = u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?);
+                at += 4;
+                buffer |= (word as u64) << (64 - buffer_bits);
+            }
+        }
+
+        // the symbol stands for a run of values, go down its pairs to the one we want
+        while item.symlen[symbol] != 0 {
+            let left = self.left(item, symbol);
+            let left_count = *item.symlen.get(left)? as i64 + 1;
+            if offset < left_count {
+                symbol = left;
+            } else {
+                offset -= left_count;
+                symbol = self.right(item, symbol);
+            }
+        }
+
+        return Some(self.left(item, symbol) as i32);
+    }
+
+    // a DTZ table's stored value to plies
+    fn map_dtz(&self, file: usize, value: i32, wdl: i32) -> Option<i32> {
+        let item = &self.items[0][file];
+        let mut value = value;
+        if item.flags & MAPPED != 0 {
+            let map_index = item.map_idx[[1, 3, 0, 2, 0][(wdl + 2) as usize]];
+            value = if item.flags & WIDE != 0 {
+                read_u16(&self.bytes, self.map + map_index + 2 * value as usize).ok()? as i32
+            } else {
+                read_u8(&self.bytes, self.map + map_index + value as usize).ok()? as i32
+            };
+        }
+        // some tables count full moves
+        if (wdl == 2 && item.flags & WIN_PLIES == 0) || (wdl == -2 && item.flags & LOSS_PLIES == 0) || wdl == 1 || wdl == -1 {
+            value *= 2;
+        }
+
+        return Some(value + 1);
+    }
+}
+
+fn set_sizes(item: &mut PairsData, bytes: &[u8], mut at: usize) -> Result<usize, String> {
+    item.flags = read_u8(bytes, at)?;
+    at += 1;
+    if item.flags & SINGLE_VALUE != 0 {
+        item.span = 1;
+        item.min_sym_len = read_u8(bytes, at)? as usize;
+        return Ok(at + 1);
+    }
+
+    let groups = item.group_len.iter().position(|len| *len == 0).unwrap_or(MAX_PIECES);
+    let table_size = item.group_idx[groups];
+    item.size_of_block = 1 << read_u8(bytes, at)?;
+    item.span = 1 << read_u8(bytes, at + 1)?;
+    item.sparse_index_size = table_size.div_ceil(item.span as u64) as usize;
+    let padding = read_u8(bytes, at + 2)? as usize;
+    item.num_blocks = read_u32(bytes, at + 3)? as usize;
+    item.block_length_size = item.num_blocks + padding;
+    item.max_sym_len = read_u8(bytes, at + 7)? as usize;
+    item.min_sym_len = read_u8(bytes, at + 8)? as usize;
+    at += 9;
+    if item.max_sym_len < item.min_sym_len || item.min_sym_len == 0 || item.max_sym_len > 32 {
+        return Err(format!("bad symbol lengths"));
+    }
+    item.lowest_sym = at;
+
+    // canonical Huffman codes: base64[len] is the smallest code of that length, padded to 64
+    // bits, longer codes having lower values
+    let lengths = item.max_sym_len - item.min_sym_len + 1;
+    item.base64 = vec![0; lengths];
+    for i in (0..lengths - 1).rev() {
+        let lowest = read_u16(bytes, at + i * 2)? as u64;
+        let next_lowest = read_u16(bytes, at + (i + 1) * 2)? as u64;
+        item.base64[i] = item.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+    }
+    for i in 0..lengths {
+        let shift = 64 - i - item.min_sym_len;
+        item.base64[i] = if shift >= 64 {0} else {item.base64[i] << shift};
+    }
+    at += lengths * 2;
+
+    let symbols = read_u16(bytes, at)? as usize;
+    at += 2;
+    item.btree = at;
+    if at + symbols * 3 > bytes.len() {
+        return Err(format!("truncated table"));
+    }
+
+    // every symbol is a value or a pair of symbols, count the values each one stands for
+    item.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for symbol in 0..symbols {
+        if !visited[symbol] {
+            item.symlen[symbol] = symbol_length(item, bytes, symbol, &mut visited)?;
+        }
+    }
+
+    return Ok(at + symbols * 3 + (symbols & 1));
+}
+
+fn symbol_length(item: &mut PairsData, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Result<u32, String> {
+    visited[symbol] = true;
+    let at = item.btree + symbol * 3;
+    let right = ((bytes[at + 2] as usize) << 4) | (bytes[at + 1] as usize >> 4);
+    if right == 0xFFF {
+        return Ok(0);
+    }
+    let left = ((bytes[at + 1] as usize & 0xF) << 8) | bytes[at] as usize;
+    if left >= visited.len() || right >= visited.len() {
+        return Err(format!("bad symbol tree"));
+    }
+    if !visited[left] {
+        item.symlen[left] = symbol_length(item, bytes, left, visited)?;
+    }
+    if !visited[right] {
+        item.symlen[right] = symbol_length(item, bytes, right, visited)?;
+    }
+
+    return Ok(item.symlen[left] + item.symlen[right] + 1);
+}
+
+// the parts of a position a table cares about
+#[derive(Clone)]
+struct TablePosition {
+    board: Board,
+    turn: Color,
+    en_passant: Option<Square>,
+}
+
+impl TablePosition {
+    fn legal_moves(&self) -> Vec<Move> {
+        return find_all_legal_moves(&self.board, self.turn, &NO_CASTLING, &self.en_passant);
+    }
+
+    fn after(&self, played_move: &Move) -> TablePosition {
+        let mut board = self.board;
+        play_move(&mut board, played_move.clone());
+        return TablePosition {board, turn: if self.turn == White {Black} else {White}, en_passant: en_passant_target(played_move)};
+    }
+
+    fn is_zeroing(&self, played_move: &Move) -> bool {
+        return self.is_capture(played_move) || played_move.piece.piece == PieceType::Pawn;
+    }
+
+    fn is_capture(&self, played_move: &Move) -> bool {
+        return self.board[played_move.to.rank][played_move.to.file].piece != PieceType::Null
+            || played_move.special_move == Some(SpecialMoveType::EnPassant);
+    }
+
+    fn is_mate(&self) -> bool {
+        return is_in_check(&self.board, self.turn) && self.legal_moves().is_empty();
+    }
+
+    // the pieces of one side the way table names write them, eg KRP
+    fn material(&self, color: Color) -> String {
+        let mut material = String::new();
+        for (piece_type, letter) in [(PieceType::King, 'K'), (PieceType::Queen, 'Q'), (PieceType::Rook, 'R'), (PieceType::Bishop, 'B'), (PieceType::Knight, 'N'), (PieceType::Pawn, 'P')] {
+            for rank in 0..8 {
+                for file in 0..8 {
+                    if self.board[rank][file] == (Piece {piece: piece_type, color}) {
+                        material.push(letter);
+                    }
+                }
+            }
+        }
+
+        return material;
+    }
+}
+
+fn count_pieces(board: &Board) -> usize {
+    return board.iter().flatten().filter(|piece| piece.piece != PieceType::Null).count();
+}
+
+// what probing a table gave
+enum TableProbe {
+    Value(i32),
+    ChangeStm, // a DTZ table that only has the other side to move
+}
+
+// the DTZ just before a zeroing move into a position of this result
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+// the tables in one or more directories, loaded as they are first needed
+pub struct Tablebase {
+    pub directories: Vec<PathBuf>,
+    pub max_pieces: usize, // the most pieces of any table found
+    available: HashSet<String>, // file names
+    indexing: Indexing,
+    tables: Mutex<HashMap<String, Option<Arc<Table>>>>,
+}
+
+impl fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tablebase {:?} up to {} pieces", self.directories, self.max_pieces)
+    }
+}
+
+// two handles are the same if they read the same directories
+impl PartialEq for Tablebase {
+    fn eq(&self, other: &Tablebase) -> bool {
+        return self.directories == other.directories;
+    }
+}
+
+impl Tablebase {
+    // `paths` is one directory or several separated like PATH is
+    pub fn open(paths: &str) -> Result<Tablebase, String> {
+        let directories: Vec<PathBuf> = std::env::split_paths(paths).filter(|path| !path.as_os_str().is_empty()).collect();
+        let mut available = HashSet::new();
+        let mut max_pieces = 0;
+        for directory in directories.iter() {
+            let entries = std::fs::read_dir(directory).map_err(|err| format!("couldn't open {}: {}", directory.display(), err))?;
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(table) = name.strip_suffix(".rtbw").or_else(|| name.strip_suffix(".rtbz")) {
+                    if table.contains('v') && table.chars().all(|c| "KQRBNPv".contains(c)) {
+                        max_pieces = max_pieces.max(table.len() - 1);
+                        available.insert(name);
+                    }
+                }
+            }
+        }
+        if available.is_empty() {
+            return Err(format!("no Syzygy tables in {}", paths));
+        }
+
+        return Ok(Tablebase {
+            directories,
+            max_pieces: max_pieces.min(MAX_PIECES),
+            available,
+            indexing: Indexing::new(),
+            tables: Mutex::new(HashMap::new()),
+        });
+    }
+
+    fn table(&self, name: &str, dtz: bool) -> Option<Arc<Table>> {
+        let file_name = format!("{}.{}", name, if dtz {"rtbz"} else {"rtbw"});
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(table) = tables.get(&file_name) {
+            return table.clone();
+        }
+
+        // a table that can't be read is remembered as missing
+        let table = self.directories.iter()
+            .map(|directory| directory.join(&file_name))
+            .find(|path| path.exists())
+            .and_then(|path| std::fs::read(&path).ok())
+            .and_then(|bytes| match Table::parse(bytes, name, dtz, &self.indexing) {
+                Ok(table) => Some(Arc::new(table)),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    None
+                }
+            });
+        tables.insert(file_name, table.clone());
+
+        return table;
+    }
+
+    // whether a position could be in the tables, castling isn't
+    pub fn covers(&self, board: &Board, castling_rights: &CastlingRights) -> bool {
+        return *castling_rights == NO_CASTLING && count_pieces(board) <= self.max_pieces;
+    }
+
+    fn probe_table(&self, position: &TablePosition, dtz: bool, wdl: i32) -> Option<TableProbe> {
+        if count_pieces(&position.board) == 2 {
+            return Some(TableProbe::Value(0));
+        }
+
+        // tables are named with the stronger side first and that side is White in them, so
+        // when the position has it the other way around colors and ranks get flipped
+        let white = position.material(White);
+        let black = position.material(Black);
+        let straight = format!("{}v{}", white, black);
+        let extension = if dtz {"rtbz"} else {"rtbw"};
+        let (name, black_stronger) = if self.available.contains(&format!("{}.{}", straight, extension)) {
+            (straight, false)
+        } else {
+            (format!("{}v{}", black, white), true)
+        };
+        let table = self.table(&name, dtz)?;
+        let indexing = &self.indexing;
+
+        // symmetric tables only have White to move
+        let flip = black_stronger || (table.symmetric && position.turn == Black);
+        let flip_color: u8 = if flip {8} else {0};
+        let flip_squares: usize = if flip {56} else {0};
+        let stm = (flip as usize) ^ (position.turn == Black) as usize;
+
+        let mut squares: Vec<usize> = vec![];
+        let mut pieces: Vec<u8> = vec![];
+        let mut lead_pawns = 0;
+        let mut table_file = 0;
+        let mut lead_color = Color::Null;
+        if table.has_pawns {
+            let lead_code = table.items[0][0].pieces[0] ^ flip_color;
+            lead_color = if lead_code & 8 != 0 {Black} else {White};
+            for square in 0..64 {
+                if position.board[square / 8][square % 8] == (Piece {piece: PieceType::Pawn, color: lead_color}) {
+                    squares.push(square ^ flip_squares);
+                    pieces.push(lead_code ^ flip_color);
+                }
+            }
+            lead_pawns = squares.len();
+            // the leading pawn is the first with the highest map_pawns
+            let mut lead = 0;
+            for i in 1..lead_pawns {
+                if indexing.map_pawns[squares[i]] > indexing.map_pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            squares.swap(0, lead);
+            table_file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+
+        let item = &table.items[stm % table.items.len()][table_file];
+        if dtz && item.flags & STM != stm as u8 && (!table.symmetric || table.has_pawns) {
+            return Some(TableProbe::ChangeStm);
+        }
+
+        for square in 0..64 {
+            let piece = position.board[square / 8][square % 8];
+            if piece.piece == PieceType::Null || (piece.piece == PieceType::Pawn && piece.color == lead_color) {
+                continue;
+            }
+            squares.push(square ^ flip_squares);
+            pieces.push(piece_code(&piece) ^ flip_color);
+        }
+        let size = squares.len();
+        if size != table.piece_count {
+            return None;
+        }
+
+        // put the pieces in the order the table lists them
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if item.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // mirror so the leading piece is on files a to d
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut() {
+                *square = flip_file(*square);
+            }
+        }
+
+        let mut index: u64;
+        if table.has_pawns {
+            index = indexing.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|square| indexing.map_pawns[*square]);
+            for i in 1..lead_pawns {
+                index += indexing.binomial[i][indexing.map_pawns[squares[i]]];
+            }
+        } else {
+            // and on ranks 1 to 4, and below the a1-h8 diagonal
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..item.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if table.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+                let rank = |square: usize| square / 8;
+                index = if off_diagonal(squares[0]) != 0 {
+                    ((indexing.map_a1d1d4[squares[0]] * 63 + squares[1] - adjust1) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[1]) != 0 {
+                    ((6 * 63 + rank(squares[0]) * 28 + indexing.map_b1h1h7[squares[1]]) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[2]) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62 + rank(squares[0]) * 7 * 28 + (rank(squares[1]) - adjust1) * 28
+                        + indexing.map_b1h1h7[squares[2]]) as u64
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(squares[0]) * 7 * 6 + (rank(squares[1]) - adjust1) * 6
+                        + (rank(squares[2]) - adjust2)) as u64
+                };
+            } else {
+                index = indexing.map_kk[indexing.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+
+        // the other groups, each as a combination of the squares left by the ones before
+        index *= item.group_idx[0];
+        let mut start = item.group_len[0];
+        let mut remaining_pawns = table.has_pawns && table.pawn_count[1] > 0;
+        let mut next = 1;
+        while item.group_len[next] != 0 {
+            let len = item.group_len[next];
+            squares[start..start + len].sort();
+            let mut group_index = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|earlier| square > **earlier).count();
+                group_index += indexing.binomial[i + 1][square - adjust - if remaining_pawns {8} else {0}];
+            }
+            remaining_pawns = false;
+            index += group_index * item.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = table.decompress(item, index)?;
+        if dtz {
+            return Some(TableProbe::Value(table.map_dtz(table_file, value, wdl)?));
+        }
+        return Some(TableProbe::Value(value - 2));
+    }
+
+    // tables leave out positions where a capture wins, so captures (and pawn moves, for DTZ)
+    // are tried first and the best of them and the table is the answer, the flag says whether
+    // a zeroing move was best
+    fn search(&self, position: &TablePosition, check_zeroing: bool) -> Option<(i32, bool)> {
+        let moves = position.legal_moves();
+        let mut best = -2;
+        let mut searched = 0;
+        for candidate in moves.iter() {
+            if !position.is_capture(candidate) && (!check_zeroing || candidate.piece.piece != PieceType::Pawn) {
+                continue;
+            }
+            searched += 1;
+            let value = -self.search(&position.after(candidate), false)?.0;
+            if value > best {
+                best = value;
+                if value >= 2 {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        let all_searched = searched > 0 && searched == moves.len();
+        let value = if all_searched {
+            best
+        } else {
+            match self.probe_table(position, false, 0)? {
+                TableProbe::Value(value) => value,
+                TableProbe::ChangeStm => return None,
+            }
+        };
+        if best >= value {
+            return Some((best, best > 0 || all_searched));
+        }
+
+        return Some((value, false));
+    }
+
+    fn dtz(&self, position: &TablePosition) -> Option<i32> {
+        let (wdl, zeroing_best) = self.search(position, true)?;
+        if wdl == 0 {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        match self.probe_table(position, true, wdl)? {
+            TableProbe::Value(dtz) => {
+                let cursed = if wdl.abs() == 1 {100} else {0};
+                return Some((dtz + cursed) * wdl.signum());
+            }
+            TableProbe::ChangeStm => {
+                // the table has the other side to move, so look one move ahead
+                let mut min_dtz = 0xFFFF;
+                for candidate in position.legal_moves() {
+                    let next = position.after(&candidate);
+                    let zeroing = position.is_zeroing(&candidate);
+                    let mut dtz = if zeroing {
+                        -dtz_before_zeroing(self.search(&next, false)?.0)
+                    } else {
+                        -self.dtz(&next)?
+                    };
+                    if dtz == 1 && next.is_mate() {
+                        min_dtz = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                        min_dtz = dtz;
+                    }
+                }
+                return Some(if min_dtz == 0xFFFF {-1} else {min_dtz});
+            }
+        }
+    }
+
+    pub fn probe_wdl_board(&self, board: &Board, turn: Color, castling_rights: &CastlingRights, en_passant: &Option<Square>) -> Option<Wdl> {
+        if !self.covers(board, castling_rights) {
+            return None;
+        }
+        let position = TablePosition {board: *board, turn, en_passant: en_passant.clone()};
+        return self.search(&position, false).map(|(value, _)| Wdl::from_value(value));
+    }
+
+    // the result for the side to move, assuming the 50 move count starts afresh
+    pub fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+        return self.probe_wdl_board(&game.board, game.turn, &game.castling_rights(), &game.en_passant_square());
+    }
+
+    // plies to the next capture or pawn move on the way to the result, negative when losing,
+    // 0 for draws, cursed wins and blessed losses are 100 further out
+    pub fn probe_dtz(&self, game: &Game) -> Option<i32> {
+        if !self.covers(&game.board, &game.castling_rights()) {
+            return None;
+        }
+        let position = TablePosition {board: game.board, turn: game.turn, en_passant: game.en_passant_square()};
+        return self.dtz(&position);
+    }
+
+    // every legal move with its result and DTZ counted from here, best first: the quickest
+    // wins that the 50 move rule can't spoil, then draws, then the slowest losses
+    pub fn root_moves(&self, game: &Game) -> Option<Vec<RootMove>> {
+        if !self.covers(&game.board, &game.castling_rights()) {
+            return None;
+        }
+        let position = TablePosition {board: game.board, turn: game.turn, en_passant: game.en_passant_square()};
+
+        let mut root_moves = vec![];
+        for candidate in position.legal_moves() {
+            let next = position.after(&candidate);
+            let (wdl, dtz) = if position.is_zeroing(&candidate) {
+                let wdl = -self.search(&next, false)?.0;
+                (wdl, dtz_before_zeroing(wdl))
+            } else {
+                let dtz = -self.dtz(&next)?;
+                let wdl = -self.search(&next, false)?.0;
+                (wdl, dtz + dtz.signum())
+            };
+            let dtz = if next.is_mate() {1} else {dtz};
+            root_moves.push(RootMove {played: candidate, wdl: Wdl::from_value(wdl), dtz});
+        }
+
+        let halfmove_clock = game.halfmove_clock() as i32;
+        let rank = |root_move: &RootMove| -> (i32, i32) {
+            match root_move.dtz {
+                dtz if dtz > 0 && dtz + halfmove_clock <= 100 => (3, -dtz),
+                dtz if dtz > 0 => (2, -dtz),
+                0 => (1, 0),
+                dtz if -dtz + halfmove_clock > 100 => (0, -dtz),
+                dtz => (-1, -dtz),
+            }
+        };
+        root_moves.sort_by_key(|root_move| std::cmp::Reverse(rank(root_move)));
+
+        return Some(root_moves);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+
+    fn game(fen: &str) -> Game {
+        return Game::from_start(parse_fen(fen).unwrap());
+    }
+
+    // the real tables are too big to keep in the repository, the tests that need them are
+    // ignored and read the 3 and 4 piece ones from SYZYGY_PATH when asked for with
+    // cargo test -- --ignored
+    fn tables() -> Tablebase {
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH should name the directory with the 3 and 4 piece tables");
+        return Tablebase::open(&path).unwrap();
+    }
+
+    fn scratch_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("rustchess-syzygy-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        return directory;
+    }
+
+    #[test]
+    #[ignore = "needs the Syzygy 3 and 4 piece tables in SYZYGY_PATH"]
+    fn known_wdl_values() {
+        let tables = tables();
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/4K2Q w - - 0 1")), Some(Wdl::Win));
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/4K2Q b - - 0 1")), Some(Wdl::Loss));
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/R3K3 b - - 0 1")), Some(Wdl::Loss));
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1")), Some(Wdl::Draw));
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1")), Some(Wdl::Draw));
+        // the king in front of a rook pawn, and the king on the sixth in front of a center pawn
+        assert_eq!(tables.probe_wdl(&game("7k/8/8/8/8/8/7P/7K w - - 0 1")), Some(Wdl::Draw));
+        assert_eq!(tables.probe_wdl(&game("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1")), Some(Wdl::Win));
+        assert_eq!(tables.probe_wdl(&game("4k3/1r6/8/8/8/8/6R1/4K3 w - - 0 1")), Some(Wdl::Draw));
+    }
+
+    #[test]
+    #[ignore = "needs the Syzygy 3 and 4 piece tables in SYZYGY_PATH"]
+    fn known_dtz_values() {
+        let tables = tables();
+        // Qb8 and Ra8 mate at once
+        let queen_mate = game("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1");
+        assert_eq!(tables.probe_dtz(&queen_mate), Some(1));
+        let rook_mate = game("7k/8/6K1/8/8/8/8/R7 w - - 0 1");
+        assert_eq!(tables.probe_dtz(&rook_mate), Some(1));
+        let best = &tables.root_moves(&rook_mate).unwrap()[0];
+        assert_eq!(move_to_uci(&best.played), "a1a8");
+        assert_eq!((best.wdl, best.dtz), (Wdl::Win, 1));
+
+        // a loss counts negative, a draw is 0
+        assert!(tables.probe_dtz(&game("4k3/8/8/8/8/8/8/4K2Q b - - 0 1")).unwrap() < 0);
+        assert_eq!(tables.probe_dtz(&game("7k/8/8/8/8/8/7P/7K w - - 0 1")), Some(0));
+    }
+
+    #[test]
+    fn bare_kings_need_no_table() {
+        let directory = scratch_directory("kings");
+        std::fs::write(directory.join("KQvK.rtbw"), b"not a table").unwrap();
+        let tables = Tablebase::open(directory.to_str().unwrap()).unwrap();
+
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/4K3 w - - 0 1")), Some(Wdl::Draw));
+        // a broken table is a table that isn't there
+        assert_eq!(tables.probe_wdl(&game("4k3/8/8/8/8/8/8/4K2Q w - - 0 1")), None);
+        // and so is anything with more pieces or castling
+        assert_eq!(tables.probe_wdl(&Game::new()), None);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn directories_without_tables_are_refused() {
+        let directory = scratch_directory("empty");
+        assert!(Tablebase::open(directory.to_str().unwrap()).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let indexing = Indexing::new();
+        let mut bytes = WDL_MAGIC.to_vec();
+        bytes.extend([0u8; 64]);
+        for length in 0..bytes.len() {
+            assert!(Table::parse(bytes[..length].to_vec(), "KQvK", false, &indexing).is_err());
+        }
+        assert!(Table::parse(DTZ_MAGIC.to_vec(), "KQvK", false, &indexing).is_err());
+    }
+}
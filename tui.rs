@@ -0,0 +1,446 @@
+use crate::piece::Piece;
+use crate::piece::PieceType;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::game::evaluate_timeout;
+use crate::pgn::history_to_san;
+use crate::pgn::move_to_san;
+use crate::render::*;
+use crate::clock::Clock;
+use crate::engine;
+use crate::engine::SearchLimits;
+use crate::players::Players;
+use crate::book::OpeningBook;
+use crate::savegame::autosave;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+const BOARD_ROW: usize = 2;
+const BOARD_COL: usize = 3;
+const PANEL_COL: usize = 35;
+const MOVE_LIST_LINES: usize = 8;
+const EVALUATION_DEPTH: u32 = 2;
+const HINT_DEPTH: u32 = 3;
+
+// the full screen front end needs a terminal on both ends
+pub fn is_supported() -> bool {
+    return std::io::stdout().is_terminal() && std::io::stdin().is_terminal();
+}
+
+// raw mode, alternate screen and mouse reporting for as long as it is alive
+struct RawTerminal {
+    saved_settings: String,
+}
+
+impl RawTerminal {
+    fn enable() -> Result<RawTerminal, String> {
+        let saved = Command::new("stty").arg("-g").stdin(Stdio::inherit()).output()
+            .map_err(|err| format!("couldn't run stty: {}", err))?;
+        if !saved.status.success() {
+            return Err("stty couldn't read the terminal settings".to_string());
+        }
+
+        // reads give up after 0.2s without input so the clocks keep ticking
+        let status = Command::new("stty").args(["raw", "-echo", "min", "0", "time", "2"]).stdin(Stdio::inherit()).status()
+            .map_err(|err| format!("couldn't run stty: {}", err))?;
+        if !status.success() {
+            return Err("stty couldn't switch to raw mode".to_string());
+        }
+
+        // alternate screen, hidden cursor, mouse clicks in SGR encoding
+        print!("\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h");
+        let _ = std::io::stdout().flush();
+
+        Ok(RawTerminal {saved_settings: String::from_utf8_lossy(&saved.stdout).trim().to_string()})
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?1006l\x1b[?1000l\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        let _ = Command::new("stty").arg(&self.saved_settings).stdin(Stdio::inherit()).status();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Char(char),
+    Click(usize, usize), // 1 based column and row
+}
+
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut res = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            0x1b if bytes.get(i + 1) == Some(&b'[') => {
+                match bytes.get(i + 2) {
+                    Some(b'A') => res.push(Key::Up),
+                    Some(b'B') => res.push(Key::Down),
+                    Some(b'C') => res.push(Key::Right),
+                    Some(b'D') => res.push(Key::Left),
+                    Some(b'<') => {
+                        // mouse report: ESC [ < button ; column ; row M (press) or m (release)
+                        let end = bytes[i..].iter().position(|b| *b == b'M' || *b == b'm').map(|p| p + i);
+                        if let Some(end) = end {
+                            let fields: Vec<usize> = String::from_utf8_lossy(&bytes[i + 3..end])
+                                .split(';')
+                                .filter_map(|field| field.parse().ok())
+                                .collect();
+                            if bytes[end] == b'M' && fields.len() == 3 && fields[0] == 0 {
+                                res.push(Key::Click(fields[1], fields[2]));
+                            }
+                            i = end + 1;
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 3;
+                continue;
+            }
+            0x1b => res.push(Key::Escape),
+            b'\r' | b'\n' | b' ' => res.push(Key::Enter),
+            0x03 => res.push(Key::Char('q')), // ctrl-c, raw mode doesn't turn it into a signal
+            b if b.is_ascii_graphic() => res.push(Key::Char(b as char)),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    return res;
+}
+
+struct Tui {
+    game: Game,
+    players: Players,
+    book: Option<OpeningBook>,
+    options: RenderOptions,
+    clock: Clock,
+    cursor: Square,
+    selected: Option<Square>,
+    pending_promotion: Option<(Square, Square)>,
+    message: String,
+    sans: Vec<String>,
+    evaluation: Option<i32>, // centipawns from White's side
+}
+
+// runs the full screen front end until the player quits, returns the game as it was left
+pub fn run(game: Game, options: RenderOptions, clock: Clock, players: Players, book: Option<OpeningBook>) -> Result<Game, String> {
+    let _raw_terminal = RawTerminal::enable()?;
+
+    let mut tui = Tui {
+        game,
+        players,
+        book,
+        options,
+        clock,
+        cursor: Square {file: 4, rank: 1},
+        selected: None,
+        pending_promotion: None,
+        message: String::new(),
+        sans: vec![],
+        evaluation: None,
+    };
+    tui.refresh_analysis();
+
+    let mut stdin = std::io::stdin();
+    let mut buffer = [0u8; 64];
+    loop {
+        tui.check_flag();
+        tui.draw();
+
+        if tui.players.is_engine(tui.game.turn) && tui.game.result().is_none() {
+            tui.message = "thinking...".to_string();
+            tui.draw();
+            if let Some(engine_move) = tui.players.engine_move(&tui.game, tui.book.as_ref()) {
+                tui.play(engine_move);
+            }
+            continue;
+        }
+
+        let read = stdin.read(&mut buffer).map_err(|err| format!("couldn't read the terminal: {}", err))?;
+        for key in parse_keys(&buffer[..read]) {
+            if !tui.handle_key(key) {
+                return Ok(tui.game);
+            }
+        }
+    }
+}
+
+impl Tui {
+    fn flipped(&self) -> bool {
+        return self.options.is_flipped(self.game.turn);
+    }
+
+    // recomputes everything derived from the position after it changed
+    fn refresh_analysis(&mut self) {
+        self.sans = history_to_san(&self.game);
+        self.selected = None;
+        self.pending_promotion = None;
+
+        if self.game.result().is_some() {
+            self.clock.stop();
+            self.evaluation = None;
+            return;
+        }
+
+        if !self.game.move_history.is_empty() {
+            self.clock.start(self.game.turn);
+        }
+        let search_result = engine::search(&self.game, &SearchLimits::depth(EVALUATION_DEPTH));
+        self.evaluation = Some(if self.game.turn == White {search_result.score} else {-search_result.score});
+    }
+
+    // after a move, undo, redo, new game or a fallen flag
+    fn position_changed(&mut self) {
+        self.refresh_analysis();
+        if let Err(err) = autosave(&self.game, &self.players, &self.clock) {
+            self.message = format!("autosave failed: {}", err);
+        }
+    }
+
+    fn check_flag(&mut self) {
+        if self.game.result().is_some() {
+            return;
+        }
+        if let Some(flagged) = self.clock.flagged() {
+            self.game.ended = Some(evaluate_timeout(&self.game.board, flagged));
+            self.position_changed();
+        }
+    }
+
+    fn square_at(&self, column: usize, row: usize) -> Option<Square> {
+        // the board starts with a line of file labels and every rank starts with its label
+        if !(BOARD_ROW + 1..=BOARD_ROW + 8).contains(&row) || !(BOARD_COL + 2..BOARD_COL + 2 + 24).contains(&column) {
+            return None;
+        }
+        let line = row - BOARD_ROW - 1;
+        let cell = (column - BOARD_COL - 2) / 3;
+
+        if self.flipped() {
+            return Some(Square {file: 7 - cell, rank: line});
+        }
+        return Some(Square {file: cell, rank: 7 - line});
+    }
+
+    // returns false once the player wants to leave
+    fn handle_key(&mut self, key: Key) -> bool {
+        if let Some((from, to)) = self.pending_promotion.clone() {
+            let promotion = match key {
+                Key::Char('q') => Some(PieceType::Queen),
+                Key::Char('r') => Some(PieceType::Rook),
+                Key::Char('b') => Some(PieceType::Bishop),
+                Key::Char('n') => Some(PieceType::Knight),
+                Key::Escape => None,
+                _ => return true,
+            };
+            self.pending_promotion = None;
+            self.message.clear();
+            if promotion.is_some() {
+                if let Some(legal_move) = self.game.find_legal_move(&from, &to, promotion) {
+                    self.play(legal_move);
+                }
+            }
+            return true;
+        }
+
+        // screen directions, the board may be upside down
+        let (up, right) = if self.flipped() {(-1, -1)} else {(1, 1)};
+        match key {
+            Key::Up => self.move_cursor(up, 0),
+            Key::Down => self.move_cursor(-up, 0),
+            Key::Right => self.move_cursor(0, right),
+            Key::Left => self.move_cursor(0, -right),
+            Key::Enter => self.choose(self.cursor.clone()),
+            Key::Click(column, row) => {
+                if let Some(square) = self.square_at(column, row) {
+                    self.cursor = square.clone();
+                    self.choose(square);
+                }
+            }
+            Key::Escape => self.selected = None,
+            Key::Char('q') => return false,
+            Key::Char('u') => {
+                if self.players.undo(&mut self.game) {
+                    self.message.clear();
+                    self.position_changed();
+                } else {
+                    self.message = "nothing to undo".to_string();
+                }
+            }
+            Key::Char('r') => {
+                if self.players.redo(&mut self.game) {
+                    self.message.clear();
+                    self.position_changed();
+                } else {
+                    self.message = "nothing to redo".to_string();
+                }
+            }
+            Key::Char('f') => {
+                self.options.perspective = if self.flipped() {Perspective::White} else {Perspective::Black};
+            }
+            Key::Char('c') => self.options.colors = !self.options.colors,
+            Key::Char('a') => self.options.ascii = !self.options.ascii,
+            Key::Char('n') => {
+                self.game = Game::new();
+                self.clock = Clock::new(self.clock.time_control);
+                self.message.clear();
+                self.position_changed();
+            }
+            Key::Char('h') if self.game.result().is_none() => {
+                let search_result = engine::search(&self.game, &SearchLimits::depth(HINT_DEPTH));
+                if let Some(best_move) = search_result.best_move {
+                    self.message = format!("hint: {}", move_to_san(&self.game, &best_move));
+                }
+            }
+            _ => {}
+        }
+
+        return true;
+    }
+
+    fn move_cursor(&mut self, rank_step: i32, file_step: i32) {
+        let rank = (self.cursor.rank as i32 + rank_step).clamp(0, 7);
+        let file = (self.cursor.file as i32 + file_step).clamp(0, 7);
+        self.cursor = Square {file: file as usize, rank: rank as usize};
+    }
+
+    // first pick selects one of the side to move's pieces, the second one plays it
+    fn choose(&mut self, square: Square) {
+        if self.game.result().is_some() {
+            return;
+        }
+
+        let piece = self.game.board[square.rank][square.file];
+        if piece.color == self.game.turn {
+            self.selected = Some(square);
+            return;
+        }
+
+        let from = match self.selected.take() {
+            Some(from) => from,
+            None => return,
+        };
+
+        let candidates: Vec<_> = self.game.legal_moves().into_iter()
+            .filter(|legal_move| legal_move.from == from && legal_move.to == square)
+            .collect();
+        if candidates.len() > 1 {
+            // one move per promotion piece
+            self.pending_promotion = Some((from, square));
+            self.message = "promote to (q)ueen, (r)ook, (b)ishop or k(n)ight".to_string();
+        } else if let Some(legal_move) = candidates.into_iter().next() {
+            self.play(legal_move);
+        } else {
+            self.message = "illegal move".to_string();
+        }
+    }
+
+    fn play(&mut self, legal_move: Move) {
+        let mover = self.game.turn;
+        self.game.play_move(legal_move);
+        self.clock.press(mover);
+        self.message.clear();
+        self.position_changed();
+    }
+
+    fn captured_by(&self, color: Color) -> String {
+        let mut captured: Vec<Piece> = vec![];
+        for entry in self.game.move_history.iter() {
+            if entry.isCaptureMove && entry.moveEntry.piece.color == color {
+                let target = entry.moveEntry.to.clone();
+                let mut piece = entry.boardState[target.rank][target.file];
+                if piece.piece == PieceType::Null {
+                    // en passant, the pawn wasn't on the destination square
+                    piece = Piece {piece: PieceType::Pawn, color: if color == White {Black} else {White}};
+                }
+                captured.push(piece);
+            }
+        }
+
+        let plain = RenderOptions {colors: false, ..self.options.clone()};
+        return captured.iter().map(|piece| piece_glyph(*piece, &plain)).collect();
+    }
+
+    fn draw(&self) {
+        let mut screen = String::from("\x1b[H");
+        let mut put = |row: usize, column: usize, text: &str| {
+            screen.push_str(&format!("\x1b[{};{}H{}\x1b[K", row, column, text));
+        };
+
+        put(1, BOARD_COL, "rustChess");
+
+        let board = render_board(&self.game, &self.options, self.selected.as_ref(), Some(&self.cursor));
+        for (i, line) in board.lines().enumerate() {
+            put(BOARD_ROW + i, BOARD_COL, line);
+        }
+
+        let to_move_marker = if self.options.ascii {"<"} else {"◀"};
+        let running = self.game.result().is_none();
+        for (i, color) in [White, Black].iter().enumerate() {
+            let marker = if running && self.game.turn == *color {to_move_marker} else {""};
+            put(BOARD_ROW + i, PANEL_COL, &format!("{:?}  {} {}", color, self.clock.display(*color), marker));
+        }
+
+        let evaluation = match self.evaluation {
+            Some(score) => match engine::mate_in(score) {
+                Some(moves) => format!("Evaluation  {}#{}", if moves > 0 {""} else {"-"}, moves.abs()),
+                None => format!("Evaluation  {:+.2}", score as f64 / 100.0),
+            },
+            None => String::new(),
+        };
+        put(BOARD_ROW + 3, PANEL_COL, &evaluation);
+
+        put(BOARD_ROW + 5, PANEL_COL, &format!("White took  {}", self.captured_by(White)));
+        put(BOARD_ROW + 6, PANEL_COL, &format!("Black took  {}", self.captured_by(Black)));
+
+        // move list in pairs, only the most recent ones fit
+        let mut move_lines: Vec<String> = vec![];
+        let mut fullmove_number = self.game.start.fullmove_number;
+        let mut sans = self.sans.iter();
+        if self.game.start.turn == Black && !self.sans.is_empty() {
+            move_lines.push(format!("{:>3}. ...     {}", fullmove_number, sans.next().unwrap()));
+            fullmove_number += 1;
+        }
+        while let Some(white_san) = sans.next() {
+            let black_san = sans.next().map(|san| san.as_str()).unwrap_or("");
+            move_lines.push(format!("{:>3}. {:<8} {}", fullmove_number, white_san, black_san));
+            fullmove_number += 1;
+        }
+        put(BOARD_ROW + 8, PANEL_COL, "Moves");
+        let first_line = move_lines.len().saturating_sub(MOVE_LIST_LINES);
+        for i in 0..MOVE_LIST_LINES {
+            let line = move_lines.get(first_line + i).map(|line| line.as_str()).unwrap_or("");
+            put(BOARD_ROW + 9 + i, PANEL_COL, line);
+        }
+
+        let status = match self.game.result() {
+            Some(result) => result.to_string(),
+            None if self.game.is_in_check() => format!("{:?} to move, check", self.game.turn),
+            None => format!("{:?} to move", self.game.turn),
+        };
+        // below both panels so long results don't run into the move list
+        put(BOARD_ROW + 18, BOARD_COL, &status);
+        put(BOARD_ROW + 19, BOARD_COL, &self.message);
+        put(BOARD_ROW + 20, BOARD_COL, "arrows/click move  enter select  esc cancel  u undo  r redo  h hint");
+        put(BOARD_ROW + 21, BOARD_COL, "f flip  c colors  a ascii  n new game  q quit");
+
+        screen.push_str("\x1b[J");
+        print!("{}", screen);
+        let _ = std::io::stdout().flush();
+    }
+}
@@ -0,0 +1,198 @@
+use crate::piece::Color::*;
+use crate::position::*;
+use crate::game::Game;
+use crate::fen::parse_fen;
+use crate::engine;
+use crate::engine::SearchLimits;
+use crate::book::BookPick;
+use crate::book::OpeningBook;
+use crate::random::Random;
+use crate::syzygy::Tablebase;
+use crate::endgame::EndgameTables;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+// speaks the Universal Chess Interface on stdin/stdout so GUIs can use the engine,
+// searches run to completion before the next command is read, so `stop` has nothing to stop
+pub fn run_uci() {
+    let mut game = Game::new();
+    // a Polyglot book set with BookFile, played from when OwnBook is on
+    let mut book: Option<OpeningBook> = None;
+    let mut own_book = false;
+    // endgame tables set with SyzygyPath
+    let mut tablebase: Option<Arc<Tablebase>> = None;
+    // our own endgame tables from make-tables, set with EndgameTables
+    let mut endgame: Option<Arc<EndgameTables>> = None;
+
+    loop {
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("uci") => {
+                println!("id name rustChess");
+                println!("id author b-alkhateeb");
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("option name EndgameTables type string default <empty>");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => game = Game::new(),
+            Some("position") => match parse_position(&words.collect::<Vec<&str>>()) {
+                Ok(position) => game = position,
+                Err(err) => println!("info string {}", err),
+            },
+            Some("setoption") => {
+                let (name, value) = parse_setoption(&words.collect::<Vec<&str>>());
+                match name.to_lowercase().as_str() {
+                    "ownbook" => own_book = value == "true",
+                    "bookfile" if value.is_empty() || value == "<empty>" => book = None,
+                    "bookfile" => match OpeningBook::open(Path::new(&value)) {
+                        Ok(opened) => book = Some(opened),
+                        Err(err) => println!("info string {}", err),
+                    },
+                    "syzygypath" if value.is_empty() || value == "<empty>" => tablebase = None,
+                    "syzygypath" => match Tablebase::open(&value) {
+                        Ok(opened) => {
+                            println!("info string found tables with up to {} pieces", opened.max_pieces);
+                            tablebase = Some(Arc::new(opened));
+                        }
+                        Err(err) => println!("info string {}", err),
+                    },
+                    "endgametables" if value.is_empty() || value == "<empty>" => endgame = None,
+                    "endgametables" => match EndgameTables::open(Path::new(&value)) {
+                        Ok(opened) => endgame = Some(Arc::new(opened)),
+                        Err(err) => println!("info string {}", err),
+                    },
+                    _ => {}
+                }
+            }
+            Some("go") => {
+                let book_move = book.as_ref().filter(|_| own_book).and_then(|book| book.choose(&game, BookPick::Weighted, &mut Random::from_time()));
+                if let Some(book_move) = book_move {
+                    println!("info string book move");
+                    println!("bestmove {}", move_to_uci(&book_move));
+                    let _ = std::io::stdout().flush();
+                    continue;
+                }
+                let mut limits = parse_go(&game, &words.collect::<Vec<&str>>());
+                limits.tablebase = tablebase.clone();
+                limits.endgame = endgame.clone();
+                let search_result = engine::search(&game, &limits);
+                let score = match engine::mate_in(search_result.score) {
+                    Some(moves) => format!("mate {}", moves),
+                    None => format!("cp {}", search_result.score),
+                };
+                println!("info depth {} score {} nodes {}", search_result.depth, score, search_result.nodes);
+                match search_result.best_move {
+                    Some(best_move) => println!("bestmove {}", move_to_uci(&best_move)),
+                    None => println!("bestmove 0000"),
+                }
+            }
+            Some("quit") => return,
+            _ => {} // unknown commands are ignored as the protocol asks
+        }
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// `setoption name <name> value <value>`, either of which may have spaces
+fn parse_setoption(words: &[&str]) -> (String, String) {
+    let value_index = words.iter().position(|word| *word == "value").unwrap_or(words.len());
+    let name = words[..value_index].iter().skip_while(|word| **word == "name").copied().collect::<Vec<&str>>().join(" ");
+    let value = words.get(value_index + 1..).unwrap_or(&[]).join(" ");
+
+    return (name, value);
+}
+
+// `position startpos moves e2e4 ...` or `position fen <fen> moves ...`
+pub fn parse_position(words: &[&str]) -> Result<Game, String> {
+    let moves_index = words.iter().position(|word| *word == "moves").unwrap_or(words.len());
+
+    let mut game = match words.first() {
+        Some(&"startpos") => Game::new(),
+        Some(&"fen") => Game::from_start(parse_fen(&words[1..moves_index].join(" "))?),
+        _ => return Err(format!("position needs startpos or fen")),
+    };
+
+    for uci_move in words.iter().skip(moves_index + 1) {
+        let legal_move = parse_uci_move(&game, uci_move)?;
+        game.play_move(legal_move);
+    }
+
+    return Ok(game);
+}
+
+// finds the legal move written as "e2e4" or "e7e8q"
+pub fn parse_uci_move(game: &Game, uci_move: &str) -> Result<Move, String> {
+    let illegal = || format!("illegal move {}", uci_move);
+    if !(4..=5).contains(&uci_move.len()) || !uci_move.is_ascii() {
+        return Err(illegal());
+    }
+
+    let from = parse_square(&uci_move[0..2]).map_err(|_| illegal())?;
+    let to = parse_square(&uci_move[2..4]).map_err(|_| illegal())?;
+    let promotion = match uci_move.chars().nth(4) {
+        Some('q') => Some(crate::piece::PieceType::Queen),
+        Some('r') => Some(crate::piece::PieceType::Rook),
+        Some('b') => Some(crate::piece::PieceType::Bishop),
+        Some('n') => Some(crate::piece::PieceType::Knight),
+        Some(_) => return Err(illegal()),
+        None => None,
+    };
+
+    let legal_move = game.find_legal_move(&from, &to, promotion).ok_or_else(illegal)?;
+    // a promotion has to name its piece
+    if legal_move.promotion != promotion {
+        return Err(illegal());
+    }
+
+    return Ok(legal_move);
+}
+
+fn parse_go(game: &Game, words: &[&str]) -> SearchLimits {
+    let mut limits = engine::limits_for_level(engine::MAX_LEVEL);
+    let mut time_left: Option<u64> = None;
+    let mut increment: u64 = 0;
+
+    let mut i = 0;
+    while i < words.len() {
+        let value = words.get(i + 1).and_then(|value| value.parse::<u64>().ok());
+        match (words[i], value) {
+            ("depth", Some(depth)) => {
+                limits = SearchLimits::depth(depth as u32);
+            }
+            ("nodes", Some(nodes)) => {
+                limits.depth = 64;
+                limits.time = None;
+                limits.nodes = Some(nodes);
+            }
+            ("movetime", Some(milliseconds)) => {
+                limits.depth = 64;
+                limits.time = Some(Duration::from_millis(milliseconds));
+            }
+            ("wtime", Some(milliseconds)) if game.turn == White => time_left = Some(milliseconds),
+            ("btime", Some(milliseconds)) if game.turn == Black => time_left = Some(milliseconds),
+            ("winc", Some(milliseconds)) if game.turn == White => increment = milliseconds,
+            ("binc", Some(milliseconds)) if game.turn == Black => increment = milliseconds,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    // with a clock running spend a thirtieth of what is left plus most of the increment
+    if let Some(time_left) = time_left {
+        limits.depth = 64;
+        limits.time = Some(Duration::from_millis((time_left / 30 + increment * 3 / 4).min(time_left / 2)));
+    }
+
+    return limits;
+}
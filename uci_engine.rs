@@ -0,0 +1,188 @@
+use crate::position::*;
+use crate::game::Game;
+use crate::fen::game_to_fen;
+use crate::uci::parse_uci_move;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+// how long an engine gets to answer uci and isready
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// an external engine speaking UCI on its stdin/stdout, the other end of uci.rs
+pub struct UciEngine {
+    pub name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>, // the engine's output, read on a thread so waits can time out
+}
+
+// what the engine said about the position while searching, from its last info line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineScore {
+    Centipawns(i32),
+    Mate(i32), // moves to mate, negative when the engine is getting mated
+}
+
+// further from zero than any count of material, so mates always outrank it
+const MATE_CENTIPAWNS: i32 = 100000;
+
+impl EngineScore {
+    // a single number to compare scores by, quicker mates are worth more
+    pub fn centipawns(&self) -> i32 {
+        match *self {
+            EngineScore::Centipawns(centipawns) => centipawns,
+            EngineScore::Mate(moves) if moves > 0 => MATE_CENTIPAWNS - moves,
+            EngineScore::Mate(moves) => -MATE_CENTIPAWNS - moves,
+        }
+    }
+}
+
+pub struct EngineMove {
+    pub best_move: Move,
+    pub score: Option<EngineScore>, // from the side to move's point of view
+}
+
+impl UciEngine {
+    // runs `command` (a program and its arguments split at spaces) and does the uci/isready handshake
+    pub fn spawn(command: &str) -> Result<UciEngine, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("empty engine command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("couldn't start {}: {}", program, err))?;
+
+        let stdin = child.stdin.take().ok_or("no stdin for the engine")?;
+        let stdout = child.stdout.take().ok_or("no stdout for the engine")?;
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => return,
+                }
+            }
+        });
+
+        let mut engine = UciEngine {name: program.to_string(), child, stdin, lines};
+        engine.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.read_line(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            }
+            if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.wait_ready()?;
+
+        return Ok(engine);
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command).and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("{} stopped listening: {}", self.name, err))
+    }
+
+    fn read_line(&mut self, deadline: Instant) -> Result<String, String> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(left) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(format!("{} didn't answer in time", self.name)),
+            Err(RecvTimeoutError::Disconnected) => Err(format!("{} exited", self.name)),
+        }
+    }
+
+    pub fn wait_ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.read_line(deadline)?.trim() != "readyok" {}
+
+        return Ok(());
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        return self.send(&format!("setoption name {} value {}", name, value));
+    }
+
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        return self.wait_ready();
+    }
+
+    // searches the game's position with `go <go_arguments>`, eg "movetime 1000" or
+    // "wtime 60000 btime 60000 winc 0 binc 0", giving up after `timeout`
+    pub fn best_move(&mut self, game: &Game, go_arguments: &str, timeout: Duration) -> Result<EngineMove, String> {
+        let start = Game::from_start(game.start.clone());
+        let mut position = format!("position fen {}", game_to_fen(&start));
+        if !game.move_history.is_empty() {
+            position.push_str(" moves");
+            for entry in game.move_history.iter() {
+                position.push(' ');
+                position.push_str(&move_to_uci(&entry.moveEntry));
+            }
+        }
+        self.send(&position)?;
+        self.send(&format!("go {}", go_arguments))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut score = None;
+        loop {
+            let line = self.read_line(deadline)?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first() {
+                Some(&"info") => {
+                    if let Some(index) = words.iter().position(|word| *word == "score") {
+                        let value = words.get(index + 2).and_then(|value| value.parse::<i32>().ok());
+                        score = match (words.get(index + 1), value) {
+                            (Some(&"cp"), Some(value)) => Some(EngineScore::Centipawns(value)),
+                            (Some(&"mate"), Some(value)) => Some(EngineScore::Mate(value)),
+                            _ => score,
+                        };
+                    }
+                }
+                Some(&"bestmove") => {
+                    let text = words.get(1).copied().unwrap_or("(none)");
+                    let best_move = parse_uci_move(game, text).map_err(|_| format!("{} played an illegal move {}", self.name, text))?;
+                    return Ok(EngineMove {best_move, score});
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // give it a moment to leave on its own before pulling the plug
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,267 @@
+use crate::piece::PieceType;
+use crate::piece::PieceType::*;
+use crate::piece::Color;
+use crate::piece::Color::*;
+use crate::board::Board;
+use crate::position::*;
+use crate::legal_moves::find_basic_legal_moves;
+use crate::legal_moves::is_in_check;
+use crate::game::StartPosition;
+
+// checks that a position could come up in a game, move generation and the engine count on
+// exactly one king a side and the like, so positions from outside go through here
+
+fn color_name(color: Color) -> &'static str {
+    return if color == White {"White"} else {"Black"};
+}
+
+fn opposite(color: Color) -> Color {
+    return if color == White {Black} else {White};
+}
+
+fn find_king(board: &Board, color: Color) -> Option<Square> {
+    for rank in 0..8 {
+        for file in 0..8 {
+            if board[rank][file].piece == King && board[rank][file].color == color {
+                return Some(Square {rank, file});
+            }
+        }
+    }
+    return None;
+}
+
+fn is_piece(board: &Board, square: &Square, piece: PieceType, color: Color) -> bool {
+    let found = board[square.rank][square.file];
+    return found.piece == piece && found.color == color;
+}
+
+// the squares of the pieces giving check to the king of `color`
+fn checkers(board: &Board, color: Color) -> Vec<Square> {
+    let king = match find_king(board, color) {
+        Some(king) => king,
+        None => return vec![],
+    };
+    let mut res: Vec<Square> = vec![];
+    for attack in find_basic_legal_moves(board, opposite(color)) {
+        // promotions come once per piece
+        if attack.to == king && !res.contains(&attack.from) {
+            res.push(attack.from);
+        }
+    }
+    return res;
+}
+
+fn piece_problems(board: &Board, color: Color, res: &mut Vec<String>) {
+    let count = |piece: PieceType| {
+        let mut n = 0;
+        for rank in 0..8 {
+            for file in 0..8 {
+                if board[rank][file].piece == piece && board[rank][file].color == color {
+                    n += 1;
+                }
+            }
+        }
+        return n;
+    };
+    let kings = count(King);
+    let pawns = count(Pawn);
+    let queens = count(Queen);
+    let rooks = count(Rook);
+    let knights = count(Knight);
+
+    if kings == 0 {
+        res.push(format!("{} has no king", color_name(color)));
+    } else if kings > 1 {
+        res.push(format!("{} has {} kings", color_name(color), kings));
+    }
+    if pawns > 8 {
+        res.push(format!("{} has {} pawns", color_name(color), pawns));
+    }
+
+    // bishops stay on their square color, so a second one on either color was promoted
+    let mut light_bishops = 0;
+    let mut dark_bishops = 0;
+    for rank in 0..8 {
+        for file in 0..8 {
+            if board[rank][file].piece == Bishop && board[rank][file].color == color {
+                if (rank + file) % 2 == 1 {
+                    light_bishops += 1;
+                } else {
+                    dark_bishops += 1;
+                }
+            }
+        }
+    }
+    let promoted = (queens - 1).max(0) + (rooks - 2).max(0) + (knights - 2).max(0)
+        + (light_bishops - 1).max(0) + (dark_bishops - 1).max(0);
+    let missing_pawns = 8 - pawns.min(8);
+    if promoted > missing_pawns {
+        res.push(format!("{} has {} promoted pieces but only {} pawns could have promoted", color_name(color), promoted, missing_pawns));
+    }
+}
+
+fn castling_problems(board: &Board, castling_rights: &CastlingRights, res: &mut Vec<String>) {
+    let rights = [
+        (castling_rights.white_short, White, 0, 7, 'K'),
+        (castling_rights.white_long, White, 0, 0, 'Q'),
+        (castling_rights.black_short, Black, 7, 7, 'k'),
+        (castling_rights.black_long, Black, 7, 0, 'q'),
+    ];
+    for (allowed, color, rank, rook_file, name) in rights {
+        if !allowed {
+            continue;
+        }
+        if !is_piece(board, &Square {rank, file: 4}, King, color) {
+            res.push(format!("castling right {} but the {} king isn't on e{}", name, color_name(color), rank + 1));
+        } else if !is_piece(board, &Square {rank, file: rook_file}, Rook, color) {
+            res.push(format!("castling right {} but there is no {} rook on {}", name, color_name(color), square_name(&Square {rank, file: rook_file})));
+        }
+    }
+}
+
+fn en_passant_problems(board: &Board, turn: Color, en_passant: &Square, res: &mut Vec<String>) {
+    // the square a pawn of the side not to move skipped, with the pawn right in front of it
+    let (rank, pawn_rank, start_rank) = if turn == White {(5, 4, 6)} else {(2, 3, 1)};
+    let name = square_name(en_passant);
+    if en_passant.rank != rank {
+        res.push(format!("en passant square {} isn't on rank {}", name, rank + 1));
+        return;
+    }
+    let file = en_passant.file;
+    if !is_piece(board, &Square {rank: pawn_rank, file}, Pawn, opposite(turn)) {
+        res.push(format!("en passant square {} but no {} pawn in front of it", name, color_name(opposite(turn))));
+    } else if board[rank][file].piece != PieceType::Null || board[start_rank][file].piece != PieceType::Null {
+        res.push(format!("en passant square {} but the pawn couldn't have just moved two squares", name));
+    }
+}
+
+// every reason the position can't come up in a game, empty when it's fine
+pub fn position_problems(board: &Board, turn: Color, castling_rights: &CastlingRights, en_passant: &Option<Square>) -> Vec<String> {
+    let mut res = vec![];
+    piece_problems(board, White, &mut res);
+    piece_problems(board, Black, &mut res);
+
+    for rank in [0, 7] {
+        for file in 0..8 {
+            if board[rank][file].piece == Pawn {
+                res.push(format!("pawn on {}", square_name(&Square {rank, file})));
+            }
+        }
+    }
+
+    // the rest needs both kings
+    if find_king(board, White).is_none() || find_king(board, Black).is_none() {
+        return res;
+    }
+
+    if is_in_check(board, opposite(turn)) {
+        res.push(format!("{} is in check but it's {}'s move", color_name(opposite(turn)), color_name(turn)));
+    }
+    // a double check has to be discovered by a bishop, rook or queen, two of those at most
+    // when an en passant capture uncovers both. The move can only uncover a line it left, so
+    // the two can't be on opposite sides of the king along one rank, file or diagonal
+    let checkers = checkers(board, turn);
+    let sliders = checkers.iter().filter(|square| matches!(board[square.rank][square.file].piece, Bishop | Rook | Queen)).count();
+    let king = find_king(board, turn).unwrap();
+    let on_one_line = checkers.len() == 2 && {
+        let offsets: Vec<(i32, i32)> = checkers.iter()
+            .map(|square| (square.rank as i32 - king.rank as i32, square.file as i32 - king.file as i32))
+            .collect();
+        offsets[0].0 * offsets[1].1 == offsets[0].1 * offsets[1].0
+    };
+    if checkers.len() > 2 || (checkers.len() == 2 && sliders == 0) || on_one_line {
+        let names: Vec<String> = checkers.iter().map(square_name).collect();
+        res.push(format!("{} can't be in check from {} at once", color_name(turn), names.join(", ")));
+    }
+
+    if let Some(en_passant) = en_passant {
+        en_passant_problems(board, turn, en_passant, &mut res);
+    }
+    castling_problems(board, castling_rights, &mut res);
+
+    return res;
+}
+
+pub fn validate_position(start: &StartPosition) -> Result<(), String> {
+    let problems = position_problems(&start.board, start.turn, &start.castling_rights, &start.en_passant);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    return Err(format!("illegal position: {}", problems.join(", ")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::parse_fen;
+    use crate::fen::parse_fen_unchecked;
+
+    fn problems(fen: &str) -> Vec<String> {
+        let start = parse_fen_unchecked(fen).unwrap();
+        return position_problems(&start.board, start.turn, &start.castling_rights, &start.en_passant);
+    }
+
+    #[test]
+    fn one_king_a_side() {
+        assert_eq!(problems("8/8/8/8/8/8/8/4K3 w - - 0 1"), vec!["Black has no king"]);
+        assert_eq!(problems("4k3/8/8/8/8/8/8/2K1K3 w - - 0 1"), vec!["White has 2 kings"]);
+    }
+
+    #[test]
+    fn no_pawns_on_the_first_or_last_rank() {
+        assert_eq!(problems("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"), vec!["pawn on h8"]);
+        assert_eq!(problems("4k3/8/8/8/8/8/8/p3K3 w - - 0 1"), vec!["pawn on a1"]);
+    }
+
+    #[test]
+    fn the_side_not_to_move_is_not_in_check() {
+        assert_eq!(problems("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"), vec!["Black is in check but it's White's move"]);
+    }
+
+    #[test]
+    fn impossible_double_checks() {
+        assert_eq!(problems("4k3/8/3N1N2/8/8/8/8/4K3 b - - 0 1"), vec!["Black can't be in check from d6, f6 at once"]);
+        assert_eq!(problems("4k3/8/8/8/8/8/8/r3K2r w - - 0 1"), vec!["White can't be in check from a1, h1 at once"]);
+        assert_eq!(problems("7B/8/8/8/3k4/8/8/B3K3 b - - 0 1"), vec!["Black can't be in check from a1, h8 at once"]);
+        // Rd2-d1 uncovers the bishop, Nc1-d3 the rook
+        assert_eq!(problems("4k3/8/8/8/1b6/8/8/3rK3 w - - 0 1"), vec![] as Vec<String>);
+        assert_eq!(problems("4k3/8/8/8/8/3n4/8/r3K3 w - - 0 1"), vec![] as Vec<String>);
+        // a pawn taking on b8 and promoting to a rook uncovers the rook on a1
+        assert_eq!(problems("kR6/8/8/8/8/8/8/R3K3 b - - 0 1"), vec![] as Vec<String>);
+    }
+
+    #[test]
+    fn en_passant_needs_the_pawn_that_just_moved() {
+        assert_eq!(problems("4k3/8/8/8/8/8/8/4K3 w - d6 0 1"), vec!["en passant square d6 but no Black pawn in front of it"]);
+        assert_eq!(problems("4k3/8/8/3p4/8/8/8/4K3 w - d3 0 1"), vec!["en passant square d3 isn't on rank 6"]);
+        assert_eq!(problems("4k3/3p4/8/3p4/8/8/8/4K3 w - d6 0 1"), vec!["en passant square d6 but the pawn couldn't have just moved two squares"]);
+    }
+
+    #[test]
+    fn promoted_pieces_need_missing_pawns() {
+        assert_eq!(problems("4k3/8/8/8/8/8/PPPPPPPP/QQQ1K3 w - - 0 1"), vec!["White has 2 promoted pieces but only 0 pawns could have promoted"]);
+        assert_eq!(problems("4k3/8/8/8/8/8/1PPPPPPP/QQ2K3 w - - 0 1"), vec![] as Vec<String>);
+        // bishops on the same square color
+        assert_eq!(problems("2b1kb2/pppppppp/8/8/8/8/8/4K3 w - - 0 1"), vec![] as Vec<String>);
+        assert_eq!(problems("1b2kb2/pppppppp/8/8/8/8/8/4K3 w - - 0 1"), vec!["Black has 1 promoted pieces but only 0 pawns could have promoted"]);
+    }
+
+    #[test]
+    fn castling_rights_need_the_king_and_rook_at_home() {
+        assert_eq!(problems("r3k2r/8/8/8/8/8/8/R4K1R w KQkq - 0 1"), vec![
+            "castling right K but the White king isn't on e1",
+            "castling right Q but the White king isn't on e1",
+        ]);
+        assert_eq!(problems("r3k3/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), vec!["castling right k but there is no Black rook on h8"]);
+    }
+
+    #[test]
+    fn positions_from_games_are_legal() {
+        assert!(parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_ok());
+        // 1.e4
+        assert!(parse_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").is_ok());
+        // 1.e4 a6 2.e5 d5, exd6 en passant is possible
+        assert!(parse_fen("rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").is_ok());
+        assert!(parse_fen("4k3/8/8/8/8/8/8/r3K2r w - - 0 1").is_err());
+    }
+}
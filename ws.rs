@@ -0,0 +1,230 @@
+use std::io::Read;
+use std::io::Write;
+
+// the server side of RFC 6455 WebSockets, just what a game feed needs: the opening handshake,
+// text messages in both directions, ping/pong and close
+
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_MESSAGE: usize = 1 << 20;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+pub enum Message {
+    Text(String),
+    Ping(Vec<u8>),
+    Close,
+}
+
+// the reply to an upgrade request carrying Sec-WebSocket-Key
+pub fn handshake_response(key: &str) -> String {
+    return format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key));
+}
+
+pub fn accept_key(key: &str) -> String {
+    return base64(&sha1(format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes()));
+}
+
+// the next text, ping or close, fragmented messages are put back together and pongs skipped.
+// `partial` holds a fragmented message still coming in, a ping can arrive between its frames so
+// it has to outlive the call that answers the ping
+pub fn read_message(reader: &mut impl Read, partial: &mut Vec<u8>) -> Result<Message, String> {
+    loop {
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header).map_err(|err| err.to_string())?;
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        let masked = header[1] & 0x80 != 0;
+        let length = match header[1] & 0x7F {
+            126 => {
+                let mut bytes = [0u8; 2];
+                reader.read_exact(&mut bytes).map_err(|err| err.to_string())?;
+                u16::from_be_bytes(bytes) as u64
+            }
+            127 => {
+                let mut bytes = [0u8; 8];
+                reader.read_exact(&mut bytes).map_err(|err| err.to_string())?;
+                u64::from_be_bytes(bytes)
+            }
+            length => length as u64,
+        };
+        // clients have to mask every frame, the connection is failed otherwise
+        if !masked {
+            return Err(format!("unmasked frame from the client"));
+        }
+        let is_control = opcode & 0x08 != 0;
+        if is_control && (!fin || length > 125) {
+            return Err(format!("fragmented or oversized control frame"));
+        }
+        let length = match usize::try_from(length).ok().and_then(|length| partial.len().checked_add(length)) {
+            Some(total) if total <= MAX_MESSAGE => length as usize,
+            _ => return Err(format!("message too large")),
+        };
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask).map_err(|err| err.to_string())?;
+        let mut payload = vec![0u8; length];
+        reader.read_exact(&mut payload).map_err(|err| err.to_string())?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        match opcode {
+            OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                partial.extend(payload);
+                if fin {
+                    let message = std::mem::take(partial);
+                    return String::from_utf8(message).map(Message::Text).map_err(|_| format!("message is not UTF-8"));
+                }
+            }
+            OPCODE_PING => return Ok(Message::Ping(payload)),
+            OPCODE_PONG => {}
+            OPCODE_CLOSE => return Ok(Message::Close),
+            _ => return Err(format!("unknown opcode {}", opcode)),
+        }
+    }
+}
+
+pub fn write_text(stream: &mut impl Write, text: &str) -> std::io::Result<()> {
+    return write_frame(stream, OPCODE_TEXT, text.as_bytes());
+}
+
+pub fn write_pong(stream: &mut impl Write, payload: &[u8]) -> std::io::Result<()> {
+    return write_frame(stream, OPCODE_PONG, payload);
+}
+
+pub fn write_close(stream: &mut impl Write) -> std::io::Result<()> {
+    return write_frame(stream, OPCODE_CLOSE, &[]);
+}
+
+// server frames go out whole and unmasked
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    if payload.len() < 126 {
+        frame.push(payload.len() as u8);
+    } else if payload.len() <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend((payload.len() as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend((payload.len() as u64).to_be_bytes());
+    }
+    frame.extend(payload);
+    stream.write_all(&frame)?;
+    return stream.flush();
+}
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for i in 0..80 {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[i]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+    }
+
+    return digest;
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut res = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a frame the way a client sends it, masked
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![if fin {0x80 | opcode} else {opcode}, 0x80 | payload.len() as u8];
+        frame.extend(mask);
+        frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+        return frame;
+    }
+
+    #[test]
+    fn a_ping_between_fragments_keeps_the_message() {
+        let mut input = client_frame(false, OPCODE_TEXT, b"{\"type\":");
+        input.extend(client_frame(true, OPCODE_PING, b"hi"));
+        input.extend(client_frame(true, OPCODE_CONTINUATION, b"\"resync\"}"));
+        let mut reader = input.as_slice();
+        let mut partial = vec![];
+
+        assert!(matches!(read_message(&mut reader, &mut partial), Ok(Message::Ping(payload)) if payload == b"hi"));
+        assert!(matches!(read_message(&mut reader, &mut partial), Ok(Message::Text(text)) if text == "{\"type\":\"resync\"}"));
+        assert!(partial.is_empty());
+    }
+
+    #[test]
+    fn unmasked_and_oversized_frames_fail_the_connection() {
+        let unmasked = [0x81, 0x02, b'h', b'i'];
+        assert!(read_message(&mut unmasked.as_slice(), &mut vec![]).is_err());
+
+        let mut huge = vec![0x81, 0x80 | 127];
+        huge.extend(u64::MAX.to_be_bytes());
+        huge.extend([0; 4]);
+        assert!(read_message(&mut huge.as_slice(), &mut vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn accept_key_matches_the_rfc_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+}